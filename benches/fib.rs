@@ -0,0 +1,80 @@
+//! Times recursive function calls, which is dominated by environment setup and lookups, along
+//! with a couple of other common workloads. Run with `cargo bench --bench fib`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use donkey_lang::evaluator::{Evaluator, environment::Environment};
+use donkey_lang::lexer::Lexer;
+use donkey_lang::parser::parse;
+
+const ITERATIONS: u32 = 10;
+
+const FIB: &str = "
+let fib = fun(n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } };
+fib(25);
+";
+
+const CLOSURES: &str = "
+let make_adder = fun(x) { fun(y) { x + y } };
+let mut total = 0;
+for i in range(100000) {
+    let add = make_adder(i);
+    total = add(total) % 1000;
+}
+total;
+";
+
+const ARITHMETIC: &str = "
+let mut total = 0;
+let mut i = 0;
+while i < 200000 {
+    total = (total + i * i - i / 3) % 1000003;
+    i = i + 1;
+}
+total;
+";
+
+const LISTS: &str = "
+let mut items = [];
+for i in range(100000) {
+    items = push(items, i);
+}
+let mut total = 0;
+while len(items) > 0 {
+    total = total + items[0];
+    items = tail(items);
+}
+total;
+";
+
+fn bench(name: &str, source: &str) {
+    let (program, errors) = parse(Lexer::new(source));
+    assert!(errors.is_empty(), "{name} failed to parse");
+
+    let mut timings = Vec::new();
+    for _ in 0..ITERATIONS {
+        let mut env = Environment::new();
+        let mut evaluator = Evaluator::new();
+        let start = Instant::now();
+        let result = evaluator.eval(black_box(&program), &mut env);
+        timings.push(start.elapsed());
+        black_box(result.unwrap_or_else(|_| panic!("{name} failed to evaluate")));
+    }
+
+    timings.sort();
+    let total: Duration = timings.iter().sum();
+    println!(
+        "{name:<10} min {:>10.2?}  median {:>10.2?}  mean {:>10.2?}",
+        timings[0],
+        timings[timings.len() / 2],
+        total / ITERATIONS,
+    );
+}
+
+fn main() {
+    bench("fib(25)", FIB);
+    bench("closures", CLOSURES);
+    bench("arithmetic", ARITHMETIC);
+    bench("lists", LISTS);
+}
@@ -1,27 +1,37 @@
 mod tests;
 
 use std::any::Any;
+use std::rc::Rc;
 
 use dyn_clone::DynClone;
+use num_bigint::BigInt;
 
-use crate::token::Token;
+use crate::lexer::escape_string;
+use crate::token::{Span, Token};
 
 /// Represents a node in the AST. Each node implements the `token_literal` function, which
 /// is mainly used for debugging purposes. It returns the literal of the token associated
-/// with this node.
+/// with this node. The `span` function returns the region of source code the node was parsed
+/// from, which is used when reporting errors.
 pub trait Node: DynClone {
     fn token_literal(&self) -> String;
     fn as_any(&self) -> &dyn Any;
     fn string(&self) -> String;
+    fn span(&self) -> Span;
 }
 
 pub trait Statement: Node {}
 
 pub trait Expression: Node {}
 
+/// Describes the shape of a value on the left of a let statement or in a parameter list, and the
+/// names its parts are bound to.
+pub trait Pattern: Node {}
+
 dyn_clone::clone_trait_object!(Node);
 dyn_clone::clone_trait_object!(Statement);
 dyn_clone::clone_trait_object!(Expression);
+dyn_clone::clone_trait_object!(Pattern);
 
 #[derive(Clone)]
 pub struct Program {
@@ -43,6 +53,13 @@ impl Node for Program {
         self
     }
 
+    fn span(&self) -> Span {
+        match (self.statements.first(), self.statements.last()) {
+            (Some(first), Some(last)) => first.span().to(last.span()),
+            _ => Span::default(),
+        }
+    }
+
     fn string(&self) -> String {
         let mut ret = Vec::new();
         for statements in &self.statements {
@@ -57,12 +74,14 @@ impl Node for Program {
 #[derive(Clone)]
 pub struct Identifier {
     pub token: Token,
+    pub span: Span,
     pub value: String,
 }
 
 impl Identifier {
     pub fn new(token: Token, value: &str) -> Self {
         Identifier {
+            span: token.span,
             token,
             value: value.to_string(),
         }
@@ -78,6 +97,10 @@ impl Node for Identifier {
         self
     }
 
+    fn span(&self) -> Span {
+        self.span
+    }
+
     fn string(&self) -> String {
         self.value.clone()
     }
@@ -85,6 +108,9 @@ impl Node for Identifier {
 
 impl Expression for Identifier {}
 
+// An identifier pattern matches any value, and binds it to the identifier
+impl Pattern for Identifier {}
+
 // ========== Identifier End ==========
 
 // ========== Let statement Start ==========
@@ -92,19 +118,55 @@ impl Expression for Identifier {}
 #[derive(Clone)]
 pub struct LetStatement {
     pub token: Token,
-    pub name: Identifier,
+    pub span: Span,
+    // Always an identifier in `let rec` statements
+    pub pattern: Box<dyn Pattern>,
     pub value: Box<dyn Expression>,
     pub rec: bool,
+    // Bindings declared with `let mut` can be assigned to
+    pub mutable: bool,
+    // The functions after the first in `let rec f = ... and g = ...;`. Always empty unless `rec`
+    // is set.
+    pub and_bindings: Vec<(Box<dyn Pattern>, Box<dyn Expression>)>,
 }
 
 impl LetStatement {
-    pub fn new(token: Token, name: Identifier, value: Box<dyn Expression>, rec: bool) -> Self {
+    pub fn new(
+        token: Token,
+        pattern: Box<dyn Pattern>,
+        value: Box<dyn Expression>,
+        rec: bool,
+        mutable: bool,
+    ) -> Self {
         LetStatement {
+            span: token.span.to(value.span()),
             token,
-            name,
+            pattern,
             value,
             rec,
+            mutable,
+            and_bindings: Vec::new(),
+        }
+    }
+
+    pub fn with_and_bindings(
+        mut self,
+        and_bindings: Vec<(Box<dyn Pattern>, Box<dyn Expression>)>,
+    ) -> Self {
+        if let Some((_, value)) = and_bindings.last() {
+            self.span = self.span.to(value.span());
         }
+        self.and_bindings = and_bindings;
+        self
+    }
+
+    /// Every pattern bound by the statement, along with its value
+    pub fn bindings(&self) -> impl Iterator<Item = (&dyn Pattern, &dyn Expression)> {
+        std::iter::once((self.pattern.as_ref(), self.value.as_ref())).chain(
+            self.and_bindings
+                .iter()
+                .map(|(pattern, value)| (pattern.as_ref(), value.as_ref())),
+        )
     }
 }
 
@@ -117,12 +179,19 @@ impl Node for LetStatement {
         self
     }
 
+    fn span(&self) -> Span {
+        self.span
+    }
+
     fn string(&self) -> String {
-        if self.rec {
-            format!("let rec {} = {};", self.name.string(), self.value.string())
-        } else {
-            format!("let {} = {};", self.name.string(), self.value.string())
-        }
+        let rec = if self.rec { "rec " } else { "" };
+        let mutable = if self.mutable { "mut " } else { "" };
+        let bindings = self
+            .bindings()
+            .map(|(pattern, value)| format!("{} = {}", pattern.string(), value.string()))
+            .collect::<Vec<_>>()
+            .join(" and ");
+        format!("let {rec}{mutable}{bindings};")
     }
 }
 
@@ -135,12 +204,14 @@ impl Statement for LetStatement {}
 #[derive(Clone)]
 pub struct ReturnStatement {
     pub token: Token,
+    pub span: Span,
     pub return_value: Box<dyn Expression>,
 }
 
 impl ReturnStatement {
     pub fn new(token: Token, return_value: Box<dyn Expression>) -> Self {
         Self {
+            span: token.span.to(return_value.span()),
             token,
             return_value,
         }
@@ -156,6 +227,10 @@ impl Node for ReturnStatement {
         self
     }
 
+    fn span(&self) -> Span {
+        self.span
+    }
+
     fn string(&self) -> String {
         format!("return {};", self.return_value.string())
     }
@@ -165,21 +240,28 @@ impl Statement for ReturnStatement {}
 
 // ========== Return statement End ==========
 
-// ========== Expression statement Start ==========
+// ========== While statement Start ==========
 
 #[derive(Clone)]
-pub struct ExpressionStatement {
+pub struct WhileStatement {
     pub token: Token,
-    pub expression: Box<dyn Expression>,
+    pub span: Span,
+    pub condition: Box<dyn Expression>,
+    pub body: BlockStatement,
 }
 
-impl ExpressionStatement {
-    pub fn new(token: Token, expression: Box<dyn Expression>) -> Self {
-        Self { token, expression }
+impl WhileStatement {
+    pub fn new(token: Token, condition: Box<dyn Expression>, body: BlockStatement) -> Self {
+        Self {
+            span: token.span.to(body.span),
+            token,
+            condition,
+            body,
+        }
     }
 }
 
-impl Node for ExpressionStatement {
+impl Node for WhileStatement {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
@@ -188,30 +270,50 @@ impl Node for ExpressionStatement {
         self
     }
 
+    fn span(&self) -> Span {
+        self.span
+    }
+
     fn string(&self) -> String {
-        format!("{};", self.expression.string())
+        format!("while {} {}", self.condition.string(), self.body.string())
     }
 }
 
-impl Statement for ExpressionStatement {}
+impl Statement for WhileStatement {}
 
-// ========== Expression statement End ==========
+// ========== While statement End ==========
 
-// ========== Integer literal Start ==========
+// ========== For statement Start ==========
 
+/// `for variable in iterable { body }`. The variable is bound in a new scope for each iteration,
+/// so it isn't visible after the loop.
 #[derive(Clone)]
-pub struct IntegerLiteral {
+pub struct ForStatement {
     pub token: Token,
-    pub value: i64,
+    pub span: Span,
+    pub variable: Identifier,
+    pub iterable: Box<dyn Expression>,
+    pub body: BlockStatement,
 }
 
-impl IntegerLiteral {
-    pub fn new(token: Token, value: i64) -> Self {
-        Self { token, value }
+impl ForStatement {
+    pub fn new(
+        token: Token,
+        variable: Identifier,
+        iterable: Box<dyn Expression>,
+        body: BlockStatement,
+    ) -> Self {
+        Self {
+            span: token.span.to(body.span),
+            token,
+            variable,
+            iterable,
+            body,
+        }
     }
 }
 
-impl Node for IntegerLiteral {
+impl Node for ForStatement {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
@@ -220,30 +322,42 @@ impl Node for IntegerLiteral {
         self
     }
 
+    fn span(&self) -> Span {
+        self.span
+    }
+
     fn string(&self) -> String {
-        self.value.to_string()
+        format!(
+            "for {} in {} {}",
+            self.variable.string(),
+            self.iterable.string(),
+            self.body.string()
+        )
     }
 }
 
-impl Expression for IntegerLiteral {}
+impl Statement for ForStatement {}
 
-// ========== Integer literal End ==========
+// ========== For statement End ==========
 
-// ========== Boolean literal Start ==========
+// ========== Break statement Start ==========
 
 #[derive(Clone)]
-pub struct BooleanLiteral {
+pub struct BreakStatement {
     pub token: Token,
-    pub value: bool,
+    pub span: Span,
 }
 
-impl BooleanLiteral {
-    pub fn new(token: Token, value: bool) -> Self {
-        Self { token, value }
+impl BreakStatement {
+    pub fn new(token: Token) -> Self {
+        Self {
+            span: token.span,
+            token,
+        }
     }
 }
 
-impl Node for BooleanLiteral {
+impl Node for BreakStatement {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
@@ -252,29 +366,37 @@ impl Node for BooleanLiteral {
         self
     }
 
+    fn span(&self) -> Span {
+        self.span
+    }
+
     fn string(&self) -> String {
-        self.value.to_string()
+        "break;".to_string()
     }
 }
 
-impl Expression for BooleanLiteral {}
+impl Statement for BreakStatement {}
 
-// ========== Boolean literal End ==========
+// ========== Break statement End ==========
 
-// ========== Null literal Start ==========
+// ========== Continue statement Start ==========
 
 #[derive(Clone)]
-pub struct NullLiteral {
+pub struct ContinueStatement {
     pub token: Token,
+    pub span: Span,
 }
 
-impl NullLiteral {
+impl ContinueStatement {
     pub fn new(token: Token) -> Self {
-        Self { token }
+        Self {
+            span: token.span,
+            token,
+        }
     }
 }
 
-impl Node for NullLiteral {
+impl Node for ContinueStatement {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
@@ -283,35 +405,43 @@ impl Node for NullLiteral {
         self
     }
 
+    fn span(&self) -> Span {
+        self.span
+    }
+
     fn string(&self) -> String {
-        "null".to_string()
+        "continue;".to_string()
     }
 }
 
-impl Expression for NullLiteral {}
+impl Statement for ContinueStatement {}
 
-// ========== Null literal End ==========
+// ========== Continue statement End ==========
 
-// ========== Prefix expression Start ==========
+// ========== Struct statement Start ==========
 
+/// `struct Name { field, ... }` declares a record type with the given fields, and binds it to
+/// `name`. Calling it with a value for each field, in order, creates a record.
 #[derive(Clone)]
-pub struct PrefixExpression {
+pub struct StructStatement {
     pub token: Token,
-    pub operator: String,
-    pub right: Box<dyn Expression>,
+    pub span: Span,
+    pub name: Identifier,
+    pub fields: Vec<Identifier>,
 }
 
-impl PrefixExpression {
-    pub fn new(token: Token, operator: &str, right: Box<dyn Expression>) -> Self {
+impl StructStatement {
+    pub fn new(token: Token, name: Identifier, fields: Vec<Identifier>) -> Self {
         Self {
+            span: token.span,
             token,
-            operator: operator.to_string(),
-            right,
+            name,
+            fields,
         }
     }
 }
 
-impl Node for PrefixExpression {
+impl Node for StructStatement {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
@@ -320,42 +450,45 @@ impl Node for PrefixExpression {
         self
     }
 
+    fn span(&self) -> Span {
+        self.span
+    }
+
     fn string(&self) -> String {
-        format!("({}{})", self.operator, self.right.string())
+        let fields = self
+            .fields
+            .iter()
+            .map(|field| field.string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        format!("struct {} {{ {fields} }}", self.name.string())
     }
 }
 
-impl Expression for PrefixExpression {}
+impl Statement for StructStatement {}
 
-// ========== Prefix expression End ==========
+// ========== Struct statement End ==========
 
-// ========== Infix expression Start ==========
+// ========== Expression statement Start ==========
 
 #[derive(Clone)]
-pub struct InfixExpression {
+pub struct ExpressionStatement {
     pub token: Token,
-    pub operator: String,
-    pub left: Box<dyn Expression>,
-    pub right: Box<dyn Expression>,
+    pub span: Span,
+    pub expression: Box<dyn Expression>,
 }
 
-impl InfixExpression {
-    pub fn new(
-        token: Token,
-        operator: &str,
-        left: Box<dyn Expression>,
-        right: Box<dyn Expression>,
-    ) -> Self {
+impl ExpressionStatement {
+    pub fn new(token: Token, expression: Box<dyn Expression>) -> Self {
         Self {
+            span: token.span.to(expression.span()),
             token,
-            operator: operator.to_string(),
-            left,
-            right,
+            expression,
         }
     }
 }
 
-impl Node for InfixExpression {
+impl Node for ExpressionStatement {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
@@ -364,48 +497,39 @@ impl Node for InfixExpression {
         self
     }
 
+    fn span(&self) -> Span {
+        self.span
+    }
+
     fn string(&self) -> String {
-        format!(
-            "({} {} {})",
-            self.left.string(),
-            self.operator,
-            self.right.string()
-        )
+        format!("{};", self.expression.string())
     }
 }
 
-impl Expression for InfixExpression {}
+impl Statement for ExpressionStatement {}
 
-// ========== Infix expression End ==========
+// ========== Expression statement End ==========
 
-// ========== IfExpression Start ==========
+// ========== Integer literal Start ==========
 
 #[derive(Clone)]
-pub struct IfExpression {
+pub struct IntegerLiteral {
     pub token: Token,
-    pub consequences: Vec<(Box<dyn Expression>, BlockStatement)>,
-    pub alternative: Option<BlockStatement>,
+    pub span: Span,
+    pub value: i64,
 }
 
-impl IfExpression {
-    pub fn new(
-        token: Token,
-        consequences: Vec<(Box<dyn Expression>, BlockStatement)>,
-        alternative: Option<BlockStatement>,
-    ) -> Self {
-        assert!(
-            !consequences.is_empty(),
-            "IfExpression must have at least one consequence"
-        );
+impl IntegerLiteral {
+    pub fn new(token: Token, value: i64) -> Self {
         Self {
+            span: token.span,
             token,
-            consequences,
-            alternative,
+            value,
         }
     }
 }
 
-impl Node for IfExpression {
+impl Node for IntegerLiteral {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
@@ -414,45 +538,40 @@ impl Node for IfExpression {
         self
     }
 
+    fn span(&self) -> Span {
+        self.span
+    }
+
     fn string(&self) -> String {
-        let mut ret = format!(
-            "if ({}) {}",
-            self.consequences[0].0.string(),
-            self.consequences[0].1.string()
-        );
-        for cons in self.consequences.iter().skip(1) {
-            ret.push_str(&format!(
-                " else if ({}) {}",
-                cons.0.string(),
-                cons.1.string()
-            ))
-        }
-        if let Some(alternative) = self.alternative.as_ref() {
-            ret.push_str(&format!("else {}", alternative.string()));
-        }
-        ret
+        self.value.to_string()
     }
 }
 
-impl Expression for IfExpression {}
+impl Expression for IntegerLiteral {}
 
-// ========== IfExpression End ==========
+// ========== Integer literal End ==========
 
-// ========== BlockStatement Start ==========
+// ========== Big integer literal Start ==========
 
+// An integer literal too large to fit in an i64
 #[derive(Clone)]
-pub struct BlockStatement {
+pub struct BigIntegerLiteral {
     pub token: Token,
-    pub statements: Vec<Box<dyn Statement>>,
+    pub span: Span,
+    pub value: BigInt,
 }
 
-impl BlockStatement {
-    pub fn new(token: Token, statements: Vec<Box<dyn Statement>>) -> Self {
-        Self { token, statements }
+impl BigIntegerLiteral {
+    pub fn new(token: Token, value: BigInt) -> Self {
+        Self {
+            span: token.span,
+            token,
+            value,
+        }
     }
 }
 
-impl Node for BlockStatement {
+impl Node for BigIntegerLiteral {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
@@ -461,41 +580,39 @@ impl Node for BlockStatement {
         self
     }
 
+    fn span(&self) -> Span {
+        self.span
+    }
+
     fn string(&self) -> String {
-        let statements = self
-            .statements
-            .iter()
-            .map(|statement| statement.string())
-            .collect::<Vec<String>>()
-            .join(" ");
-        format!("{{ {statements} }}")
+        self.value.to_string()
     }
 }
 
-impl Statement for BlockStatement {}
+impl Expression for BigIntegerLiteral {}
 
-// ========== BlockStatement End ==========
+// ========== Big integer literal End ==========
 
-// ========== Function literal Start ==========
+// ========== Float literal Start ==========
 
 #[derive(Clone)]
-pub struct FunctionLiteral {
+pub struct FloatLiteral {
     pub token: Token,
-    pub parameters: Vec<Identifier>,
-    pub body: BlockStatement,
+    pub span: Span,
+    pub value: f64,
 }
 
-impl FunctionLiteral {
-    pub fn new(token: Token, parameters: Vec<Identifier>, body: BlockStatement) -> Self {
+impl FloatLiteral {
+    pub fn new(token: Token, value: f64) -> Self {
         Self {
+            span: token.span,
             token,
-            parameters,
-            body,
+            value,
         }
     }
 }
 
-impl Node for FunctionLiteral {
+impl Node for FloatLiteral {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
@@ -504,45 +621,39 @@ impl Node for FunctionLiteral {
         self
     }
 
+    fn span(&self) -> Span {
+        self.span
+    }
+
     fn string(&self) -> String {
-        let parameter_string = self
-            .parameters
-            .iter()
-            .map(|identifier| identifier.string())
-            .collect::<Vec<_>>()
-            .join(",");
-        format!("fun({}) {}", parameter_string, self.body.string())
+        format!("{:?}", self.value)
     }
 }
 
-impl Expression for FunctionLiteral {}
+impl Expression for FloatLiteral {}
 
-// ========== Function literal End ==========
+// ========== Float literal End ==========
 
-// ========== Call expression Start ==========
+// ========== Boolean literal Start ==========
 
 #[derive(Clone)]
-pub struct CallExpression {
-    pub token: Token,                  // The ( token
-    pub function: Box<dyn Expression>, // Even though the type allows for any Expression here, in practice this should only be an identifier or a function literal
-    pub arguments: Vec<Box<dyn Expression>>,
+pub struct BooleanLiteral {
+    pub token: Token,
+    pub span: Span,
+    pub value: bool,
 }
 
-impl CallExpression {
-    pub fn new(
-        token: Token,
-        function: Box<dyn Expression>,
-        arguments: Vec<Box<dyn Expression>>,
-    ) -> Self {
+impl BooleanLiteral {
+    pub fn new(token: Token, value: bool) -> Self {
         Self {
+            span: token.span,
             token,
-            function,
-            arguments,
+            value,
         }
     }
 }
 
-impl Node for CallExpression {
+impl Node for BooleanLiteral {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
@@ -551,9 +662,500 @@ impl Node for CallExpression {
         self
     }
 
+    fn span(&self) -> Span {
+        self.span
+    }
+
     fn string(&self) -> String {
-        let function = self.function.string();
-        let arguments = self
+        self.value.to_string()
+    }
+}
+
+impl Expression for BooleanLiteral {}
+
+// ========== Boolean literal End ==========
+
+// ========== Null literal Start ==========
+
+#[derive(Clone)]
+pub struct NullLiteral {
+    pub token: Token,
+    pub span: Span,
+}
+
+impl NullLiteral {
+    pub fn new(token: Token) -> Self {
+        Self {
+            span: token.span,
+            token,
+        }
+    }
+}
+
+impl Node for NullLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn string(&self) -> String {
+        "null".to_string()
+    }
+}
+
+impl Expression for NullLiteral {}
+
+// ========== Null literal End ==========
+
+// ========== Prefix expression Start ==========
+
+#[derive(Clone)]
+pub struct PrefixExpression {
+    pub token: Token,
+    pub span: Span,
+    pub operator: String,
+    pub right: Box<dyn Expression>,
+}
+
+impl PrefixExpression {
+    pub fn new(token: Token, operator: &str, right: Box<dyn Expression>) -> Self {
+        Self {
+            span: token.span.to(right.span()),
+            token,
+            operator: operator.to_string(),
+            right,
+        }
+    }
+}
+
+impl Node for PrefixExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn string(&self) -> String {
+        format!("({}{})", self.operator, self.right.string())
+    }
+}
+
+impl Expression for PrefixExpression {}
+
+// ========== Prefix expression End ==========
+
+// ========== Infix expression Start ==========
+
+#[derive(Clone)]
+pub struct InfixExpression {
+    pub token: Token,
+    pub span: Span,
+    pub operator: String,
+    pub left: Box<dyn Expression>,
+    pub right: Box<dyn Expression>,
+}
+
+impl InfixExpression {
+    pub fn new(
+        token: Token,
+        operator: &str,
+        left: Box<dyn Expression>,
+        right: Box<dyn Expression>,
+    ) -> Self {
+        Self {
+            span: left.span().to(right.span()),
+            token,
+            operator: operator.to_string(),
+            left,
+            right,
+        }
+    }
+}
+
+impl Node for InfixExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn string(&self) -> String {
+        format!(
+            "({} {} {})",
+            self.left.string(),
+            self.operator,
+            self.right.string()
+        )
+    }
+}
+
+impl Expression for InfixExpression {}
+
+// ========== Infix expression End ==========
+
+// ========== Assign expression Start ==========
+
+/// `name = value`, which updates a binding declared with `let mut` and evaluates to the new value.
+#[derive(Clone)]
+pub struct AssignExpression {
+    pub token: Token,
+    pub span: Span,
+    pub name: Identifier,
+    pub value: Box<dyn Expression>,
+}
+
+impl AssignExpression {
+    pub fn new(token: Token, name: Identifier, value: Box<dyn Expression>) -> Self {
+        Self {
+            span: name.span.to(value.span()),
+            token,
+            name,
+            value,
+        }
+    }
+}
+
+impl Node for AssignExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn string(&self) -> String {
+        format!("({} = {})", self.name.string(), self.value.string())
+    }
+}
+
+impl Expression for AssignExpression {}
+
+// ========== Assign expression End ==========
+
+// ========== IfExpression Start ==========
+
+#[derive(Clone)]
+pub struct IfExpression {
+    pub token: Token,
+    pub span: Span,
+    pub consequences: Vec<(Box<dyn Expression>, BlockStatement)>,
+    pub alternative: Option<BlockStatement>,
+}
+
+impl IfExpression {
+    pub fn new(
+        token: Token,
+        consequences: Vec<(Box<dyn Expression>, BlockStatement)>,
+        alternative: Option<BlockStatement>,
+    ) -> Self {
+        assert!(
+            !consequences.is_empty(),
+            "IfExpression must have at least one consequence"
+        );
+        let last_block = alternative
+            .as_ref()
+            .unwrap_or(&consequences[consequences.len() - 1].1);
+        Self {
+            span: token.span.to(last_block.span),
+            token,
+            consequences,
+            alternative,
+        }
+    }
+}
+
+impl Node for IfExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn string(&self) -> String {
+        let mut ret = format!(
+            "if ({}) {}",
+            self.consequences[0].0.string(),
+            self.consequences[0].1.string()
+        );
+        for cons in self.consequences.iter().skip(1) {
+            ret.push_str(&format!(
+                " else if ({}) {}",
+                cons.0.string(),
+                cons.1.string()
+            ))
+        }
+        if let Some(alternative) = self.alternative.as_ref() {
+            ret.push_str(&format!("else {}", alternative.string()));
+        }
+        ret
+    }
+}
+
+impl Expression for IfExpression {}
+
+// ========== IfExpression End ==========
+
+// ========== MatchExpression Start ==========
+
+/// `match subject { pattern => value, ... }` evaluates to the value of the first arm whose
+/// pattern matches the subject and whose guard, if it has one, is truthy
+#[derive(Clone)]
+pub struct MatchExpression {
+    pub token: Token,
+    pub span: Span,
+    pub subject: Box<dyn Expression>,
+    pub arms: Vec<MatchArm>,
+}
+
+impl MatchExpression {
+    pub fn new(token: Token, subject: Box<dyn Expression>, arms: Vec<MatchArm>) -> Self {
+        Self {
+            span: token.span,
+            token,
+            subject,
+            arms,
+        }
+    }
+}
+
+impl Node for MatchExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn string(&self) -> String {
+        let arms = self
+            .arms
+            .iter()
+            .map(|arm| arm.string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        format!("match {} {{ {arms} }}", self.subject.string())
+    }
+}
+
+impl Expression for MatchExpression {}
+
+#[derive(Clone)]
+pub struct MatchArm {
+    pub pattern: Box<dyn Pattern>,
+    // `if condition` after the pattern. The arm is only chosen if the condition is truthy, and the
+    // condition can use the names bound by the pattern.
+    pub guard: Option<Box<dyn Expression>>,
+    pub value: Box<dyn Expression>,
+}
+
+impl MatchArm {
+    pub fn new(
+        pattern: Box<dyn Pattern>,
+        guard: Option<Box<dyn Expression>>,
+        value: Box<dyn Expression>,
+    ) -> Self {
+        Self {
+            pattern,
+            guard,
+            value,
+        }
+    }
+
+    pub fn string(&self) -> String {
+        match &self.guard {
+            Some(guard) => format!(
+                "{} if {} => {}",
+                self.pattern.string(),
+                guard.string(),
+                self.value.string()
+            ),
+            None => format!("{} => {}", self.pattern.string(), self.value.string()),
+        }
+    }
+}
+
+// ========== MatchExpression End ==========
+
+// ========== BlockStatement Start ==========
+
+#[derive(Clone)]
+pub struct BlockStatement {
+    pub token: Token,
+    pub span: Span,
+    pub statements: Vec<Box<dyn Statement>>,
+}
+
+impl BlockStatement {
+    pub fn new(token: Token, statements: Vec<Box<dyn Statement>>) -> Self {
+        let span = match statements.last() {
+            Some(statement) => token.span.to(statement.span()),
+            None => token.span,
+        };
+        Self {
+            span,
+            token,
+            statements,
+        }
+    }
+}
+
+impl Node for BlockStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn string(&self) -> String {
+        let statements = self
+            .statements
+            .iter()
+            .map(|statement| statement.string())
+            .collect::<Vec<String>>()
+            .join(" ");
+        format!("{{ {statements} }}")
+    }
+}
+
+impl Statement for BlockStatement {}
+
+// A block can be the value of a match arm
+impl Expression for BlockStatement {}
+
+// ========== BlockStatement End ==========
+
+// ========== Function literal Start ==========
+
+#[derive(Clone)]
+pub struct FunctionLiteral {
+    pub token: Token,
+    pub span: Span,
+    // The parameters and body are shared with every function created from this literal
+    pub parameters: Rc<[Box<dyn Pattern>]>,
+    pub body: Rc<BlockStatement>,
+}
+
+impl FunctionLiteral {
+    pub fn new(token: Token, parameters: Vec<Box<dyn Pattern>>, body: BlockStatement) -> Self {
+        Self {
+            span: token.span.to(body.span),
+            token,
+            parameters: parameters.into(),
+            body: Rc::new(body),
+        }
+    }
+}
+
+impl Node for FunctionLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn string(&self) -> String {
+        let parameter_string = self
+            .parameters
+            .iter()
+            .map(|identifier| identifier.string())
+            .collect::<Vec<_>>()
+            .join(",");
+        format!("fun({}) {}", parameter_string, self.body.string())
+    }
+}
+
+impl Expression for FunctionLiteral {}
+
+// ========== Function literal End ==========
+
+// ========== Call expression Start ==========
+
+#[derive(Clone)]
+pub struct CallExpression {
+    pub token: Token, // The ( token
+    pub span: Span,
+    pub function: Box<dyn Expression>, // Even though the type allows for any Expression here, in practice this should only be an identifier or a function literal
+    pub arguments: Vec<Box<dyn Expression>>,
+}
+
+impl CallExpression {
+    pub fn new(
+        token: Token,
+        function: Box<dyn Expression>,
+        arguments: Vec<Box<dyn Expression>>,
+    ) -> Self {
+        Self {
+            span: function.span().to(token.span),
+            token,
+            function,
+            arguments,
+        }
+    }
+}
+
+impl Node for CallExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn string(&self) -> String {
+        let function = self.function.string();
+        let arguments = self
             .arguments
             .iter()
             .map(|arg| arg.string())
@@ -563,34 +1165,144 @@ impl Node for CallExpression {
     }
 }
 
-impl Expression for CallExpression {}
+impl Expression for CallExpression {}
+
+// ========== Call expression End ==========
+
+// ========== Index expression Start ==========
+
+#[derive(Clone)]
+pub struct IndexExpression {
+    pub token: Token,
+    pub span: Span,
+    // Even though the type allows any Expression here, this should only be an array, string or
+    // hash expression
+    pub collection: Box<dyn Expression>,
+    // Even though the type allows any Expression here, this should only be an integer (for array
+    // and string indexing) or a hash key (for hash indexing)
+    pub index: Box<dyn Expression>,
+}
+
+impl IndexExpression {
+    pub fn new(token: Token, collection: Box<dyn Expression>, index: Box<dyn Expression>) -> Self {
+        Self {
+            span: collection.span().to(index.span()),
+            token,
+            collection,
+            index,
+        }
+    }
+}
+
+impl Node for IndexExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn string(&self) -> String {
+        let array_string = self.collection.string();
+        let index_string = self.index.string();
+        format!("{array_string}[{index_string}]")
+    }
+}
+
+impl Expression for IndexExpression {}
+
+// ========== Index expression End ==========
+
+// ========== Slice expression Start ==========
+
+/// `collection[start:end]`, where either bound may be left out.
+#[derive(Clone)]
+pub struct SliceExpression {
+    pub token: Token,
+    pub span: Span,
+    pub collection: Box<dyn Expression>,
+    pub start: Option<Box<dyn Expression>>,
+    pub end: Option<Box<dyn Expression>>,
+}
+
+impl SliceExpression {
+    pub fn new(
+        token: Token,
+        collection: Box<dyn Expression>,
+        start: Option<Box<dyn Expression>>,
+        end: Option<Box<dyn Expression>>,
+    ) -> Self {
+        Self {
+            span: collection.span().to(token.span),
+            token,
+            collection,
+            start,
+            end,
+        }
+    }
+}
+
+impl Node for SliceExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
 
-// ========== Call expression End ==========
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 
-// ========== Index expression Start ==========
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn string(&self) -> String {
+        let bound_string = |bound: &Option<Box<dyn Expression>>| {
+            bound
+                .as_ref()
+                .map(|bound| bound.string())
+                .unwrap_or_default()
+        };
+        format!(
+            "{}[{}:{}]",
+            self.collection.string(),
+            bound_string(&self.start),
+            bound_string(&self.end)
+        )
+    }
+}
+
+impl Expression for SliceExpression {}
+
+// ========== Slice expression End ==========
 
+// ========== Member expression Start ==========
+
+/// `record.field`
 #[derive(Clone)]
-pub struct IndexExpression {
+pub struct MemberExpression {
     pub token: Token,
-    // Even though the type allows any Expression here, this should only be an array expression or
-    // a map expression (if I ever add map expressions)
-    pub collection: Box<dyn Expression>,
-    // Even though the type allows any Expression here, this should only be an integer (for array
-    // indexing) or a string (for map indexing)
-    pub index: Box<dyn Expression>,
+    pub span: Span,
+    pub record: Box<dyn Expression>,
+    pub field: Identifier,
 }
 
-impl IndexExpression {
-    pub fn new(token: Token, collection: Box<dyn Expression>, index: Box<dyn Expression>) -> Self {
+impl MemberExpression {
+    pub fn new(token: Token, record: Box<dyn Expression>, field: Identifier) -> Self {
         Self {
+            span: record.span().to(field.span),
             token,
-            collection,
-            index,
+            record,
+            field,
         }
     }
 }
 
-impl Node for IndexExpression {
+impl Node for MemberExpression {
     fn token_literal(&self) -> String {
         self.token.literal.clone()
     }
@@ -599,28 +1311,90 @@ impl Node for IndexExpression {
         self
     }
 
+    fn span(&self) -> Span {
+        self.span
+    }
+
     fn string(&self) -> String {
-        let array_string = self.collection.string();
-        let index_string = self.index.string();
-        format!("{array_string}[{index_string}]")
+        format!("{}.{}", self.record.string(), self.field.string())
     }
 }
 
-impl Expression for IndexExpression {}
+impl Expression for MemberExpression {}
 
-// ========== Index expression End ==========
+// ========== Member expression End ==========
+
+// ========== With expression Start ==========
+
+/// `record with { field: value, ... }` creates a copy of a record with some of its fields
+/// replaced
+#[derive(Clone)]
+pub struct WithExpression {
+    pub token: Token,
+    pub span: Span,
+    pub record: Box<dyn Expression>,
+    pub updates: Vec<(Identifier, Box<dyn Expression>)>,
+}
+
+impl WithExpression {
+    pub fn new(
+        token: Token,
+        record: Box<dyn Expression>,
+        updates: Vec<(Identifier, Box<dyn Expression>)>,
+    ) -> Self {
+        Self {
+            span: record.span().to(token.span),
+            token,
+            record,
+            updates,
+        }
+    }
+}
+
+impl Node for WithExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn string(&self) -> String {
+        let updates = self
+            .updates
+            .iter()
+            .map(|(field, value)| format!("{}: {}", field.string(), value.string()))
+            .collect::<Vec<String>>()
+            .join(", ");
+        format!("({} with {{{updates}}})", self.record.string())
+    }
+}
+
+impl Expression for WithExpression {}
+
+// ========== With expression End ==========
 
 // ========== Array expression Start ==========
 
 #[derive(Clone)]
 pub struct ArrayExpression {
     pub token: Token,
+    pub span: Span,
     pub items: Vec<Box<dyn Expression>>,
 }
 
 impl ArrayExpression {
     pub fn new(token: Token, items: Vec<Box<dyn Expression>>) -> Self {
-        Self { token, items }
+        Self {
+            span: token.span,
+            token,
+            items,
+        }
     }
 }
 
@@ -633,6 +1407,10 @@ impl Node for ArrayExpression {
         self
     }
 
+    fn span(&self) -> Span {
+        self.span
+    }
+
     fn string(&self) -> String {
         let items = self
             .items
@@ -648,17 +1426,70 @@ impl Expression for ArrayExpression {}
 
 // ========== Array expression End ==========
 
+// ========== Hash expression Start ==========
+
+#[derive(Clone)]
+pub struct HashExpression {
+    pub token: Token,
+    pub span: Span,
+    // Key and value pairs, in the order they were written
+    pub pairs: Vec<(Box<dyn Expression>, Box<dyn Expression>)>,
+}
+
+impl HashExpression {
+    pub fn new(token: Token, pairs: Vec<(Box<dyn Expression>, Box<dyn Expression>)>) -> Self {
+        Self {
+            span: token.span,
+            token,
+            pairs,
+        }
+    }
+}
+
+impl Node for HashExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn string(&self) -> String {
+        let pairs = self
+            .pairs
+            .iter()
+            .map(|(key, value)| format!("{}: {}", key.string(), value.string()))
+            .collect::<Vec<String>>()
+            .join(", ");
+        format!("{{{pairs}}}")
+    }
+}
+
+impl Expression for HashExpression {}
+
+// ========== Hash expression End ==========
+
 // ========== String expression Start ==========
 
 #[derive(Clone)]
 pub struct StringExpression {
     pub token: Token,
+    pub span: Span,
     pub value: String,
 }
 
 impl StringExpression {
     pub fn new(token: Token, value: String) -> Self {
-        Self { token, value }
+        Self {
+            span: token.span,
+            token,
+            value,
+        }
     }
 }
 
@@ -671,11 +1502,262 @@ impl Node for StringExpression {
         self
     }
 
+    fn span(&self) -> Span {
+        self.span
+    }
+
     fn string(&self) -> String {
-        format!("\"{}\"", self.value)
+        format!("\"{}\"", escape_string(&self.value))
     }
 }
 
 impl Expression for StringExpression {}
 
 // ========== String expression End ==========
+
+// ========== Interpolated string expression Start ==========
+
+// A string literal containing `${...}` segments, eg. "Hello ${name}!". The parts are evaluated in
+// order and joined together. Literal text between the segments is stored as StringExpressions.
+#[derive(Clone)]
+pub struct InterpolatedStringExpression {
+    pub token: Token, // The opening " token
+    pub span: Span,
+    pub parts: Vec<Box<dyn Expression>>,
+}
+
+impl InterpolatedStringExpression {
+    pub fn new(token: Token, parts: Vec<Box<dyn Expression>>) -> Self {
+        Self {
+            span: token.span,
+            token,
+            parts,
+        }
+    }
+}
+
+impl Node for InterpolatedStringExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn string(&self) -> String {
+        let parts = self
+            .parts
+            .iter()
+            .map(
+                |part| match part.as_any().downcast_ref::<StringExpression>() {
+                    Some(string_expression) => escape_string(&string_expression.value),
+                    None => format!("${{{}}}", part.string()),
+                },
+            )
+            .collect::<String>();
+        format!("\"{parts}\"")
+    }
+}
+
+impl Expression for InterpolatedStringExpression {}
+
+// ========== Interpolated string expression End ==========
+
+// ========== Array pattern Start ==========
+
+/// `[first, second, ...rest]` matches an array with an item for each pattern, and binds the
+/// items after them to `rest`. Without a rest binding, the array can't have any other items.
+#[derive(Clone)]
+pub struct ArrayPattern {
+    pub token: Token,
+    pub span: Span,
+    pub items: Vec<Box<dyn Pattern>>,
+    pub rest: Option<Identifier>,
+}
+
+impl ArrayPattern {
+    pub fn new(token: Token, items: Vec<Box<dyn Pattern>>, rest: Option<Identifier>) -> Self {
+        Self {
+            span: token.span,
+            token,
+            items,
+            rest,
+        }
+    }
+}
+
+impl Node for ArrayPattern {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn string(&self) -> String {
+        let mut items = self
+            .items
+            .iter()
+            .map(|item| item.string())
+            .collect::<Vec<String>>();
+        if let Some(rest) = &self.rest {
+            items.push(format!("...{}", rest.string()));
+        }
+        format!("[{}]", items.join(", "))
+    }
+}
+
+impl Pattern for ArrayPattern {}
+
+// ========== Array pattern End ==========
+
+// ========== Hash pattern Start ==========
+
+/// `{name, age: years}` matches a hash with a value for each key, and matches each value against
+/// the pattern after its key. A key on its own binds the value to a name the same as the key. The
+/// hash can have other keys too.
+#[derive(Clone)]
+pub struct HashPattern {
+    pub token: Token,
+    pub span: Span,
+    // The keys are identifiers, which stand for string keys with the same name
+    pub pairs: Vec<(Identifier, Box<dyn Pattern>)>,
+}
+
+impl HashPattern {
+    pub fn new(token: Token, pairs: Vec<(Identifier, Box<dyn Pattern>)>) -> Self {
+        Self {
+            span: token.span,
+            token,
+            pairs,
+        }
+    }
+}
+
+impl Node for HashPattern {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn string(&self) -> String {
+        let pairs = self
+            .pairs
+            .iter()
+            .map(|(key, pattern)| {
+                let value = pattern.string();
+                if value == key.value {
+                    value
+                } else {
+                    format!("{}: {value}", key.string())
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(", ");
+        format!("{{{pairs}}}")
+    }
+}
+
+impl Pattern for HashPattern {}
+
+// ========== Hash pattern End ==========
+
+// ========== Wildcard pattern Start ==========
+
+/// `_` matches any value without binding it
+#[derive(Clone)]
+pub struct WildcardPattern {
+    pub token: Token,
+    pub span: Span,
+}
+
+impl WildcardPattern {
+    pub fn new(token: Token) -> Self {
+        Self {
+            span: token.span,
+            token,
+        }
+    }
+}
+
+impl Node for WildcardPattern {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn string(&self) -> String {
+        "_".to_string()
+    }
+}
+
+impl Pattern for WildcardPattern {}
+
+// ========== Wildcard pattern End ==========
+
+// ========== Literal pattern Start ==========
+
+/// A number, string, boolean or null literal, which matches values equal to it
+#[derive(Clone)]
+pub struct LiteralPattern {
+    pub token: Token,
+    pub span: Span,
+    // A literal expression, or a negated number literal
+    pub value: Box<dyn Expression>,
+}
+
+impl LiteralPattern {
+    pub fn new(token: Token, value: Box<dyn Expression>) -> Self {
+        Self {
+            span: value.span(),
+            token,
+            value,
+        }
+    }
+}
+
+impl Node for LiteralPattern {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn string(&self) -> String {
+        self.value.string()
+    }
+}
+
+impl Pattern for LiteralPattern {}
+
+// ========== Literal pattern End ==========
@@ -7,24 +7,38 @@ use crate::token::{Token, TokenType};
 fn test_let_statement() {
     let let_statement = LetStatement::new(
         Token::new(TokenType::Let, "let"),
-        Identifier::new(Token::new(TokenType::Ident, "bob"), "bob"),
+        Box::new(Identifier::new(Token::new(TokenType::Ident, "bob"), "bob")),
         Box::new(IntegerLiteral::new(Token::new(TokenType::Int, "10"), 10)),
         false,
+        false,
     );
     assert_eq!(let_statement.string(), "let bob = 10;")
 }
 
+#[test]
+fn test_let_mut_statement() {
+    let let_statement = LetStatement::new(
+        Token::new(TokenType::Let, "let"),
+        Box::new(Identifier::new(Token::new(TokenType::Ident, "bob"), "bob")),
+        Box::new(IntegerLiteral::new(Token::new(TokenType::Int, "10"), 10)),
+        false,
+        true,
+    );
+    assert_eq!(let_statement.string(), "let mut bob = 10;")
+}
+
 #[test]
 fn test_let_rec_statement() {
     let let_statement = LetStatement::new(
         Token::new(TokenType::Let, "let"),
-        Identifier::new(Token::new(TokenType::Ident, "bob"), "bob"),
+        Box::new(Identifier::new(Token::new(TokenType::Ident, "bob"), "bob")),
         Box::new(FunctionLiteral::new(
             Token::new(TokenType::Function, "fun"),
             Vec::new(),
             BlockStatement::new(Token::new(TokenType::LBrace, "{"), Vec::new()),
         )),
         true,
+        false,
     );
     assert_eq!(let_statement.string(), "let rec bob = fun() {  };")
 }
@@ -1,5 +1,3 @@
-use std::fmt::Display;
-
 #[repr(u8)]
 pub enum OpCode {
     OpConstant,
@@ -23,15 +21,12 @@ pub fn make_instruction(opcode: OpCode, operands: Vec<u32>) -> Vec<u8> {
         instruction.push(opcode as u8);
 
         for i in 0..operands.len() {
-            match operand_widths[i] {
-                2 => {
-                    let operand: u16 = operands[i]
-                        .try_into()
-                        .expect("Operand too large for 2 byte width");
-                    instruction.extend_from_slice(&operand.to_be_bytes());
-                }
-                _ => {}
-            };
+            if operand_widths[i] == 2 {
+                let operand: u16 = operands[i]
+                    .try_into()
+                    .expect("Operand too large for 2 byte width");
+                instruction.extend_from_slice(&operand.to_be_bytes());
+            }
         }
 
         instruction
@@ -1,12 +1,11 @@
-use std::rc::Rc;
-
 use crate::ast::{ExpressionStatement, InfixExpression, IntegerLiteral, Node, Program};
 use crate::code::{OpCode, make_instruction};
-use crate::object::{Integer, Object};
+use crate::object::Value;
+use crate::token::Span;
 
 struct Compiler {
     instructions: Vec<u8>,
-    constants: Vec<Rc<dyn Object>>,
+    constants: Vec<Value>,
 }
 
 impl Compiler {
@@ -31,17 +30,17 @@ impl Compiler {
             self.compile(infix_expression.right.as_ref())?;
         } else if let Some(integer_literal) = node.as_any().downcast_ref::<IntegerLiteral>() {
             let value = integer_literal.value;
-            let index = self.add_constant(Rc::new(Integer::new(value)));
+            let index = self.add_constant(Value::Integer(value));
             self.add_instruction(make_instruction(OpCode::OpConstant, vec![index]));
         } else {
-            return Err(CompilerError::new("Unknown node type"));
+            return Err(CompilerError::new("Unknown node type").with_span(node.span()));
         }
 
         Ok(())
     }
 
-    fn add_constant(&mut self, object: Rc<dyn Object>) -> u32 {
-        self.constants.push(Rc::clone(&object));
+    fn add_constant(&mut self, value: Value) -> u32 {
+        self.constants.push(value);
         (self.constants.len() - 1) as u32
     }
 
@@ -52,16 +51,23 @@ impl Compiler {
 }
 
 #[derive(Debug)]
-struct CompilerError {
-    error_message: String,
+pub struct CompilerError {
+    pub error_message: String,
+    pub span: Option<Span>,
 }
 
 impl CompilerError {
     fn new(error_message: &str) -> Self {
         Self {
             error_message: error_message.to_string(),
+            span: None,
         }
     }
+
+    fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
 }
 
 #[cfg(test)]
@@ -70,12 +76,11 @@ mod tests {
     use crate::ast::Program;
     use crate::code::{OpCode, make_instruction};
     use crate::lexer::Lexer;
-    use crate::object::Integer;
     use crate::parser::Parser;
 
     struct CompilerTestCase {
         input: String,
-        expected_consts: Vec<Rc<dyn Object>>,
+        expected_consts: Vec<Value>,
         expected_instructions: Vec<Vec<u8>>,
     }
 
@@ -83,7 +88,7 @@ mod tests {
     fn integer_arithmetric() {
         let tests = vec![CompilerTestCase {
             input: "1 + 2;".to_string(),
-            expected_consts: vec![Rc::new(Integer::new(1)), Rc::new(Integer::new(2))],
+            expected_consts: vec![Value::Integer(1), Value::Integer(2)],
             expected_instructions: vec![
                 make_instruction(OpCode::OpConstant, vec![0]),
                 make_instruction(OpCode::OpConstant, vec![1]),
@@ -121,7 +126,7 @@ mod tests {
         }
     }
 
-    fn test_constants(expected_constants: Vec<Rc<dyn Object>>, constants: Vec<Rc<dyn Object>>) {
+    fn test_constants(expected_constants: Vec<Value>, constants: Vec<Value>) {
         assert_eq!(
             expected_constants.len(),
             constants.len(),
@@ -130,21 +135,20 @@ mod tests {
             constants.len()
         );
         for (i, c) in expected_constants.into_iter().enumerate() {
-            if let Some(integer) = c.as_any().downcast_ref::<Integer>() {
-                test_integer_object(integer.value, constants[i].clone());
+            if let Value::Integer(integer) = c {
+                test_integer_object(integer, &constants[i]);
             }
         }
     }
 
-    fn test_integer_object(expected: i64, actual: Rc<dyn Object>) {
-        let actual = actual
-            .as_any()
-            .downcast_ref::<Integer>()
-            .expect("Expected an integer object");
+    fn test_integer_object(expected: i64, actual: &Value) {
+        let Value::Integer(actual) = actual else {
+            panic!("Expected an integer object");
+        };
         assert_eq!(
-            expected, actual.value,
+            expected, *actual,
             "object has the wrong value. Wanted {}, got {}",
-            expected, actual.value
+            expected, actual
         );
     }
 
@@ -0,0 +1,314 @@
+mod tests;
+
+use std::env;
+use std::io::{self, IsTerminal};
+
+use crate::compiler::CompilerError;
+use crate::evaluator::{EvaluatorError, EvaluatorErrorKind};
+use crate::parser::{ParseError, radix_name};
+use crate::token::{Span, TokenType};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const BOLD_RED: &str = "\x1b[1;31m";
+const BOLD_BLUE: &str = "\x1b[1;34m";
+const BOLD_CYAN: &str = "\x1b[1;36m";
+
+// Tabs are expanded to this many spaces when printing source lines, so carets line up
+const TAB_WIDTH: usize = 4;
+
+/// A user facing error, rendered in the style of rustc. Runtime errors are headed by the name
+/// of their kind:
+///
+/// ```text
+/// AssignmentError: Cannot assign to immutable binding `x`
+///  --> sample.donk:2:1
+///   |
+/// 2 | x = 2;
+///   | ^
+///   = help: declare it with `let mut x` to allow assigning to it
+/// ```
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub kind: String,
+    pub message: String,
+    pub span: Option<Span>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(kind: &str, message: &str) -> Self {
+        Self {
+            kind: kind.to_string(),
+            message: message.to_string(),
+            span: None,
+            notes: Vec::new(),
+            help: Vec::new(),
+        }
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    pub fn with_help(mut self, help: &str) -> Self {
+        self.help.push(help.to_string());
+        self
+    }
+
+    /// Renders the diagnostic as a string. `source` should be the full contents of `filename`,
+    /// since the span is used to find the line to print. When `color` is true, the output
+    /// contains ANSI escape codes.
+    pub fn render(&self, filename: &str, source: &str, color: bool) -> String {
+        let paint = |style: &str, text: &str| {
+            if color {
+                format!("{style}{text}{RESET}")
+            } else {
+                text.to_string()
+            }
+        };
+
+        let mut ret = format!(
+            "{}{}\n",
+            paint(BOLD_RED, &format!("{}:", self.kind)),
+            paint(BOLD, &format!(" {}", self.message))
+        );
+
+        let gutter_width = self
+            .span
+            .map(|span| span.line.to_string().len())
+            .unwrap_or(1);
+        let gutter = " ".repeat(gutter_width);
+
+        match self.span {
+            Some(span) => {
+                ret.push_str(&format!(
+                    "{gutter}{} {filename}:{}:{}\n",
+                    paint(BOLD_BLUE, "-->"),
+                    span.line,
+                    span.column
+                ));
+                let line = source
+                    .lines()
+                    .nth(span.line.saturating_sub(1))
+                    .unwrap_or("");
+                let (padding, underline) = Self::underline(line, source, span);
+                ret.push_str(&format!("{gutter} {}\n", paint(BOLD_BLUE, "|")));
+                ret.push_str(&format!(
+                    "{} {}\n",
+                    paint(BOLD_BLUE, &format!("{} |", span.line)),
+                    line.replace('\t', &" ".repeat(TAB_WIDTH))
+                ));
+                ret.push_str(&format!(
+                    "{gutter} {} {}{}\n",
+                    paint(BOLD_BLUE, "|"),
+                    " ".repeat(padding),
+                    paint(BOLD_RED, &"^".repeat(underline))
+                ));
+            }
+            None => {
+                ret.push_str(&format!("{gutter}{} {filename}\n", paint(BOLD_BLUE, "-->")));
+            }
+        }
+
+        for note in &self.notes {
+            ret.push_str(&format!(
+                "{gutter} {} {note}\n",
+                paint(BOLD_BLUE, "= note:")
+            ));
+        }
+        for help in &self.help {
+            ret.push_str(&format!(
+                "{gutter} {} {help}\n",
+                paint(BOLD_CYAN, "= help:")
+            ));
+        }
+        ret
+    }
+
+    /// Returns how many columns to indent the underline by and how many carets to print under
+    /// `line`. Spans that continue onto later lines are underlined up to the end of `line`.
+    fn underline(line: &str, source: &str, span: Span) -> (usize, usize) {
+        let width = |c: char| if c == '\t' { TAB_WIDTH } else { 1 };
+        let before: usize = line
+            .chars()
+            .take(span.column.saturating_sub(1))
+            .map(width)
+            .sum();
+        let highlighted = source
+            .get(span.start..span.end.max(span.start))
+            .unwrap_or("")
+            .lines()
+            .next()
+            .unwrap_or("");
+        let underline: usize = highlighted.chars().map(width).sum();
+        (before, underline.max(1))
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Self {
+        let diagnostic = Diagnostic::new("parse error", &error.message()).with_span(error.span());
+        match error {
+            ParseError::UnexpectedToken {
+                expected: TokenType::RBrace,
+                found: TokenType::Eof,
+                ..
+            } => diagnostic.with_note("the input ended before this block was closed"),
+            ParseError::UnexpectedToken {
+                expected: TokenType::Semicolon,
+                ..
+            } => {
+                diagnostic.with_help("let, return, break and continue statements must end with `;`")
+            }
+            ParseError::InvalidDigit { radix, .. } => {
+                let digits = match radix {
+                    2 => "0 and 1",
+                    8 => "0 to 7",
+                    16 => "0 to 9 and a to f",
+                    _ => "0 to 9",
+                };
+                diagnostic.with_note(&format!("{} digits are {digits}", radix_name(*radix)))
+            }
+            ParseError::MisplacedSeparator { .. } => {
+                diagnostic.with_help("remove the `_`, or move it between two digits")
+            }
+            ParseError::InvalidFloatLiteral { .. } => {
+                diagnostic.with_note("float literals must fit in a 64 bit float")
+            }
+            ParseError::InvalidCallTarget { .. } => {
+                diagnostic.with_help("bind the function to a name with `let`, then call it by name")
+            }
+            ParseError::UnterminatedString { .. } => {
+                diagnostic.with_help("add a closing `\"` to the end of the string")
+            }
+            ParseError::InvalidAssignmentTarget { .. } => diagnostic
+                .with_note("arrays and hashes can't be modified in place")
+                .with_help("use `put` or `push` to build an updated copy, and assign that instead"),
+            ParseError::OutsideLoop { .. } => diagnostic.with_note(
+                "`break` and `continue` only apply to loops in the same function as them",
+            ),
+            ParseError::RecNonFunction { .. } => {
+                diagnostic.with_help("use a plain `let` to bind other values")
+            }
+            ParseError::InvalidEscape { .. } => diagnostic
+                .with_note("supported escapes are \\n, \\t, \\r, \\0, \\\", \\\\, \\$ and \\u{...}")
+                .with_help("use a raw string like r\"...\" to write backslashes literally"),
+            _ => diagnostic,
+        }
+    }
+}
+
+impl From<&EvaluatorError> for Diagnostic {
+    fn from(error: &EvaluatorError) -> Self {
+        let diagnostic = Diagnostic::new(error.kind.name(), &error.kind.message());
+        let diagnostic = match error.span {
+            Some(span) => diagnostic.with_span(span),
+            None => diagnostic,
+        };
+        match &error.kind {
+            EvaluatorErrorKind::InternalError { .. } => {
+                diagnostic.with_note("this is a bug in the interpreter, please report it")
+            }
+            EvaluatorErrorKind::ImmutableAssignment { name } => diagnostic.with_help(&format!(
+                "declare it with `let mut {name}` to allow assigning to it"
+            )),
+            EvaluatorErrorKind::StackOverflow { .. } => diagnostic.with_help(
+                "calls in tail position don't count towards the limit, so recursion can often be \
+                 rewritten to avoid it",
+            ),
+            EvaluatorErrorKind::NoMatch { .. } => {
+                diagnostic.with_help("add a `_ => ...` arm to handle any other value")
+            }
+            _ => diagnostic,
+        }
+    }
+}
+
+impl From<&CompilerError> for Diagnostic {
+    fn from(error: &CompilerError) -> Self {
+        let diagnostic = Diagnostic::new("compile error", &error.error_message);
+        match error.span {
+            Some(span) => diagnostic.with_span(span),
+            None => diagnostic,
+        }
+    }
+}
+
+/// Renders the call stack of a runtime error in the style of a Python traceback, with the most
+/// recent call last. Each entry shows where a call was made and which function made it. The
+/// final entry is the location of the error itself. Returns None if the error occurred outside of
+/// any function call.
+///
+/// ```text
+/// Traceback (most recent call last):
+///   File "sample.donk", line 6, in <program>
+///     apply(half, 0);
+///   File "sample.donk", line 2, in apply
+///     f(x)
+///   File "sample.donk", line 4, in half
+///     1 / x
+/// ```
+///
+/// Deep recursion repeats the same entry many times in a row, so after a few repeats the rest
+/// are summarized in a single line.
+pub fn render_traceback(error: &EvaluatorError, filename: &str, source: &str) -> Option<String> {
+    // How many times an entry is shown in a row before its repeats are summarized
+    const MAX_REPEATS: usize = 3;
+
+    if error.backtrace.is_empty() {
+        return None;
+    }
+
+    let mut entries = Vec::new();
+    for frame in &error.backtrace {
+        entries.push((frame.call_site, frame.caller_name.as_str()));
+    }
+    if let (Some(span), Some(frame)) = (error.span, error.backtrace.last()) {
+        entries.push((span, frame.function_name.as_str()));
+    }
+
+    let mut ret = "Traceback (most recent call last):\n".to_string();
+    let mut index = 0;
+    while index < entries.len() {
+        let (span, function_name) = entries[index];
+        let repeats = entries[index..]
+            .iter()
+            .take_while(|entry| **entry == entries[index])
+            .count();
+        let line = source
+            .lines()
+            .nth(span.line.saturating_sub(1))
+            .unwrap_or("");
+        let entry = format!(
+            "  File \"{filename}\", line {}, in {function_name}\n    {}\n",
+            span.line,
+            line.trim()
+        );
+        for _ in 0..repeats.min(MAX_REPEATS) {
+            ret.push_str(&entry);
+        }
+        if repeats > MAX_REPEATS {
+            let hidden = repeats - MAX_REPEATS;
+            let plural = if hidden == 1 { "" } else { "s" };
+            ret.push_str(&format!(
+                "  [Previous line repeated {hidden} more time{plural}]\n"
+            ));
+        }
+        index += repeats;
+    }
+    Some(ret)
+}
+
+/// Returns true if diagnostics printed to stderr should be coloured. Colour is used when stderr
+/// is a terminal, unless the `NO_COLOR` environment variable is set.
+pub fn use_color() -> bool {
+    io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none()
+}
@@ -0,0 +1,174 @@
+#[cfg(test)]
+use crate::diagnostics::{Diagnostic, render_traceback};
+#[cfg(test)]
+use crate::evaluator::environment::Environment;
+#[cfg(test)]
+use crate::evaluator::{Evaluator, EvaluatorError, EvaluatorErrorKind, Frame};
+#[cfg(test)]
+use crate::lexer::Lexer;
+#[cfg(test)]
+use crate::parser::Parser;
+#[cfg(test)]
+use crate::token::Span;
+
+#[test]
+fn render_with_span() {
+    let source = "let x = 1;\nlet y = x / 0;\n";
+    let diagnostic =
+        Diagnostic::new("runtime error", "Division by zero").with_span(Span::new(19, 24, 2, 9));
+    let expected = "\
+runtime error: Division by zero
+ --> test.donk:2:9
+  |
+2 | let y = x / 0;
+  |         ^^^^^
+";
+    assert_eq!(diagnostic.render("test.donk", source, false), expected);
+}
+
+#[test]
+fn render_with_notes_and_help() {
+    let source = "foo(1);";
+    let diagnostic = Diagnostic::new("runtime error", "Unknown identifier found: foo")
+        .with_span(Span::new(0, 3, 1, 1))
+        .with_note("identifiers must be defined before they are used")
+        .with_help("did you mean `for`?");
+    let expected = "\
+runtime error: Unknown identifier found: foo
+ --> test.donk:1:1
+  |
+1 | foo(1);
+  | ^^^
+  = note: identifiers must be defined before they are used
+  = help: did you mean `for`?
+";
+    assert_eq!(diagnostic.render("test.donk", source, false), expected);
+}
+
+#[test]
+fn render_expands_tabs() {
+    let source = "if true {\n\t\tx\n}";
+    let diagnostic = Diagnostic::new("runtime error", "Unknown identifier found: x")
+        .with_span(Span::new(12, 13, 2, 3));
+    let expected = "\
+runtime error: Unknown identifier found: x
+ --> test.donk:2:3
+  |
+2 |         x
+  |         ^
+";
+    assert_eq!(diagnostic.render("test.donk", source, false), expected);
+}
+
+#[test]
+fn render_multiline_span_underlines_first_line() {
+    let source = "let f = fun(x) {\n  x\n};";
+    let diagnostic =
+        Diagnostic::new("parse error", "Something went wrong").with_span(Span::new(8, 22, 1, 9));
+    let expected = "\
+parse error: Something went wrong
+ --> test.donk:1:9
+  |
+1 | let f = fun(x) {
+  |         ^^^^^^^^
+";
+    assert_eq!(diagnostic.render("test.donk", source, false), expected);
+}
+
+#[test]
+fn render_without_span() {
+    let diagnostic = Diagnostic::new("compile error", "Unknown node type");
+    let expected = "\
+compile error: Unknown node type
+ --> test.donk
+";
+    assert_eq!(diagnostic.render("test.donk", "", false), expected);
+}
+
+#[test]
+fn render_with_color() {
+    let diagnostic =
+        Diagnostic::new("parse error", "Unexpected token").with_span(Span::new(0, 1, 1, 1));
+    let rendered = diagnostic.render("test.donk", "}", true);
+    assert!(rendered.starts_with("\x1b[1;31mparse error:\x1b[0m"));
+    assert!(rendered.contains("\x1b[1;31m^\x1b[0m"));
+}
+
+#[test]
+fn render_traceback_most_recent_call_last() {
+    let source = "let f = fun(x) {\n  x / 0\n};\nlet g = fun() { f(1) };\ng();";
+    let mut error =
+        EvaluatorError::new(EvaluatorErrorKind::ZeroDivision).with_span(Span::new(19, 24, 2, 3));
+    error.backtrace = vec![
+        Frame::new("g", "<program>", Span::new(50, 53, 5, 1)),
+        Frame::new("f", "g", Span::new(43, 47, 4, 17)),
+    ];
+    let expected = "\
+Traceback (most recent call last):
+  File \"test.donk\", line 5, in <program>
+    g();
+  File \"test.donk\", line 4, in g
+    let g = fun() { f(1) };
+  File \"test.donk\", line 2, in f
+    x / 0
+";
+    assert_eq!(
+        render_traceback(&error, "test.donk", source).as_deref(),
+        Some(expected)
+    );
+
+    error.backtrace.clear();
+    assert_eq!(render_traceback(&error, "test.donk", source), None);
+}
+
+#[test]
+fn render_traceback_collapses_repeated_entries() {
+    let source = "let rec f = fun(n) {\n  1 + f(n - 1)\n};\nf(5);";
+    let mut error = EvaluatorError::new(EvaluatorErrorKind::StackOverflow { limit: 5 })
+        .with_span(Span::new(27, 35, 2, 7));
+    error.backtrace = vec![Frame::new("f", "<program>", Span::new(38, 42, 4, 1))];
+    error
+        .backtrace
+        .extend(vec![Frame::new("f", "f", Span::new(27, 35, 2, 7)); 4]);
+    let expected = "\
+Traceback (most recent call last):
+  File \"test.donk\", line 4, in <program>
+    f(5);
+  File \"test.donk\", line 2, in f
+    1 + f(n - 1)
+  File \"test.donk\", line 2, in f
+    1 + f(n - 1)
+  File \"test.donk\", line 2, in f
+    1 + f(n - 1)
+  [Previous line repeated 2 more times]
+";
+    assert_eq!(
+        render_traceback(&error, "test.donk", source).as_deref(),
+        Some(expected)
+    );
+}
+
+#[test]
+fn render_traceback_through_tail_calls() {
+    // g's call to f is in tail position, so f replaces g's frame and h's call to g isn't shown,
+    // but the call to f is still shown as being in g
+    let source = "let f = fun(x) {\n  x / 0\n};\nlet g = fun(y) { f(y) };\nlet h = fun() { g(3) + 0 };\nh();";
+    let program = Parser::new(Lexer::new(source)).parse_program();
+    let error = match Evaluator::new().eval(&program, &mut Environment::new()) {
+        Ok(value) => panic!("Expected error, got {}", value.inspect()),
+        Err(error) => error,
+    };
+    let expected = "\
+Traceback (most recent call last):
+  File \"test.donk\", line 6, in <program>
+    h();
+  File \"test.donk\", line 4, in g
+    let g = fun(y) { f(y) };
+  File \"test.donk\", line 2, in f
+    x / 0
+";
+    assert_eq!(
+        render_traceback(&error, "test.donk", source).as_deref(),
+        Some(expected)
+    );
+}
@@ -0,0 +1,156 @@
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+use im_rc::vector::Iter;
+
+use crate::evaluator::EvaluatorError;
+use crate::evaluator::numeric::Number;
+use crate::object::Value;
+
+/// Returns true if two values are structurally equal. Numbers are compared by value, so `1 == 1.0`.
+/// Arrays are equal if they have the same length and their items are equal pairwise, and hashes
+/// are equal if they have the same keys with equal values. Records are equal if they were created
+/// from the same struct and their fields are equal, and a struct is only equal to itself. Values
+/// of different types are never equal. Functions have no identity, so they are never equal to
+/// anything, including themselves.
+pub fn values_equal(left: &Value, right: &Value) -> bool {
+    // Nested values are compared with an explicit stack of the pairs left to compare, rather than
+    // recursively, so deeply nested values don't overflow the Rust stack
+    let mut pairs = Vec::new();
+    let (mut left, mut right) = (left, right);
+    loop {
+        if !shallow_equal(left, right, &mut pairs) {
+            return false;
+        }
+        match pairs.pop() {
+            Some(pair) => (left, right) = pair,
+            None => return true,
+        }
+    }
+}
+
+/// Compares two values without looking inside them. The pairs of values nested in them that also
+/// need to be equal are pushed onto `pairs`.
+fn shallow_equal<'a>(
+    left: &'a Value,
+    right: &'a Value,
+    pairs: &mut Vec<(&'a Value, &'a Value)>,
+) -> bool {
+    if let (Some(left), Some(right)) = (Number::from_value(left), Number::from_value(right)) {
+        return left.compare(&right) == Some(Ordering::Equal);
+    }
+
+    match (left, right) {
+        (Value::Boolean(left), Value::Boolean(right)) => left == right,
+        (Value::String(left), Value::String(right)) => left == right,
+        (Value::Array(left), Value::Array(right)) => {
+            if left.len() != right.len() {
+                return false;
+            }
+            pairs.extend(left.iter().zip(right.iter()));
+            true
+        }
+        (Value::Hash(left), Value::Hash(right)) => {
+            if left.len() != right.len() {
+                return false;
+            }
+            // Insertion order doesn't affect equality
+            for (key, left) in left.iter() {
+                let Some(right) = right.get(key) else {
+                    return false;
+                };
+                pairs.push((left, right));
+            }
+            true
+        }
+        (
+            Value::Range {
+                start: left_start,
+                end: left_end,
+            },
+            Value::Range {
+                start: right_start,
+                end: right_end,
+            },
+        ) => left_start == right_start && left_end == right_end,
+        (Value::Struct(left), Value::Struct(right)) => Rc::ptr_eq(left, right),
+        (Value::Record(left), Value::Record(right)) => {
+            if !Rc::ptr_eq(&left.struct_type, &right.struct_type) {
+                return false;
+            }
+            pairs.extend(left.values.iter().zip(&right.values));
+            true
+        }
+        (Value::Null, Value::Null) => true,
+        _ => false,
+    }
+}
+
+/// Orders two values. Numbers are ordered by value, strings by their characters and arrays
+/// lexicographically by their items. Returns None if the values are unordered, which happens when
+/// a NaN is involved. Fails if the values can't be ordered against each other.
+pub fn compare_values(left: &Value, right: &Value) -> Result<Option<Ordering>, EvaluatorError> {
+    // Nested arrays are compared with an explicit stack of the arrays being compared, along with
+    // how their lengths compare, rather than recursively, so deeply nested arrays don't overflow
+    // the Rust stack
+    let mut arrays: Vec<(Iter<Value>, Iter<Value>, Ordering)> = Vec::new();
+    let (mut left, mut right) = (left, right);
+    loop {
+        match (left, right) {
+            (Value::Array(left), Value::Array(right)) => {
+                arrays.push((left.iter(), right.iter(), left.len().cmp(&right.len())));
+            }
+            _ => match compare_scalars(left, right)? {
+                Some(Ordering::Equal) => {}
+                ordering => return Ok(ordering),
+            },
+        }
+        // Move on to the next pair of items, finishing the arrays that have run out of them
+        loop {
+            let Some((left_items, right_items, lengths)) = arrays.last_mut() else {
+                return Ok(Some(Ordering::Equal));
+            };
+            if let (Some(left_item), Some(right_item)) = (left_items.next(), right_items.next()) {
+                (left, right) = (left_item, right_item);
+                break;
+            }
+            let lengths = *lengths;
+            arrays.pop();
+            if lengths != Ordering::Equal {
+                return Ok(Some(lengths));
+            }
+        }
+    }
+}
+
+/// Orders two values that aren't both arrays
+fn compare_scalars(left: &Value, right: &Value) -> Result<Option<Ordering>, EvaluatorError> {
+    if let (Some(left), Some(right)) = (Number::from_value(left), Number::from_value(right)) {
+        return Ok(left.compare(&right));
+    }
+
+    let Some(expected) = orderable_kind(left) else {
+        return Err(EvaluatorError::type_error("number, string or array", left));
+    };
+    if orderable_kind(right) != Some(expected) {
+        return Err(EvaluatorError::type_error(expected, right));
+    }
+
+    match (left, right) {
+        // Comparing UTF-8 bytes orders strings by Unicode scalar value
+        (Value::String(left), Value::String(right)) => Ok(Some(left.cmp(right))),
+        _ => Err(EvaluatorError::internal_error(
+            "Orderable values of the same kind should be strings",
+        )),
+    }
+}
+
+/// The kind of value used in type errors for comparisons, or None if the value can't be ordered.
+pub fn orderable_kind(value: &Value) -> Option<&'static str> {
+    match value {
+        Value::Integer(_) | Value::BigInteger(_) | Value::Float(_) => Some("number"),
+        Value::String(_) => Some("string"),
+        Value::Array(_) => Some("array"),
+        _ => None,
+    }
+}
@@ -1,37 +1,262 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::rc::{Rc, Weak};
 
-use crate::{ast::Identifier, object::Object};
+use crate::evaluator::{EvaluatorError, EvaluatorErrorKind};
+use crate::{
+    ast::Identifier,
+    object::{Function, Value},
+};
 
+struct Binding {
+    value: Value,
+    mutable: bool,
+    // The number of bindings made in the scope before this one
+    index: usize,
+    // The binding of the same name that this one shadows, kept for functions that captured the
+    // scope before it was shadowed
+    shadowed: Option<Box<Binding>>,
+}
+
+struct Scope {
+    mapping: HashMap<String, Binding>,
+    outer: Option<Environment>,
+    length: usize,
+}
+
+impl Scope {
+    /// Finds the binding of `id` that a handle which captured the scope's first `captured`
+    /// bindings sees
+    fn find(&self, id: &str, captured: Option<usize>) -> Option<&Binding> {
+        let mut binding = self.mapping.get(id)?;
+        for _ in 0..visible_depth(binding, captured) {
+            binding = binding.shadowed.as_deref()?;
+        }
+        Some(binding)
+    }
+
+    fn find_mut(&mut self, id: &str, captured: Option<usize>) -> Option<&mut Binding> {
+        let mut binding = self.mapping.get_mut(id)?;
+        for _ in 0..visible_depth(binding, captured) {
+            binding = binding.shadowed.as_deref_mut()?;
+        }
+        Some(binding)
+    }
+}
+
+/// How many shadowed bindings back from `newest` the binding a handle which captured the first
+/// `captured` bindings of the scope sees is. That's the newest binding made before the handle was
+/// captured, or the newest binding if the name was only bound afterwards, so functions can refer
+/// to functions defined after them.
+fn visible_depth(newest: &Binding, captured: Option<usize>) -> usize {
+    let Some(captured) = captured else {
+        return 0;
+    };
+    let mut binding = newest;
+    let mut depth = 0;
+    while binding.index >= captured {
+        match &binding.shadowed {
+            Some(shadowed) => binding = shadowed,
+            None => return 0,
+        }
+        depth += 1;
+    }
+    depth
+}
+
+#[derive(Clone)]
+enum ScopeRef {
+    Strong(Rc<RefCell<Scope>>),
+    // Only used by functions bound in the scope they captured. The scope is alive whenever they're
+    // called, since they're only reachable through it.
+    Weak(Weak<RefCell<Scope>>),
+}
+
+/// A handle to a scope and the chain of scopes enclosing it. Cloning an environment is cheap and
+/// produces another handle to the same scope, so a function that captures its defining
+/// environment sees bindings that are assigned to after it was created. A `let` of a name that's
+/// already bound in the scope shadows the earlier binding, and functions that captured the scope
+/// before then keep seeing the earlier one.
+///
+/// A function bound in the scope it captured only holds that scope weakly, so the two don't keep
+/// each other alive. Other cycles, such as a function stored in an array in the scope it captured,
+/// are never freed.
 #[derive(Clone)]
 pub struct Environment {
-    mapping: HashMap<String, Box<dyn Object>>,
-    outer: Option<Box<Environment>>,
+    scope: ScopeRef,
+    // The number of bindings the scope had when this handle was captured by a function. None for
+    // handles that see every binding.
+    captured: Option<usize>,
 }
 
 impl Environment {
     pub fn new() -> Self {
+        Self::with_outer(None)
+    }
+
+    /// Creates a new, empty scope nested inside `outer`.
+    pub fn new_wrapped(outer: &Environment) -> Self {
+        Self::with_outer(Some(outer.upgrade()))
+    }
+
+    fn with_outer(outer: Option<Environment>) -> Self {
         Self {
-            mapping: HashMap::new(),
-            outer: None,
+            scope: ScopeRef::Strong(Rc::new(RefCell::new(Scope {
+                mapping: HashMap::new(),
+                outer,
+                length: 0,
+            }))),
+            captured: None,
         }
     }
 
-    pub fn new_wrapped(outer: &Environment) -> Self {
+    /// A handle for a function to capture, which doesn't see bindings that later shadow the ones
+    /// currently in the scope
+    pub fn capture(&self) -> Environment {
+        self.capture_with_next_bindings(0)
+    }
+
+    /// Like `capture`, but also sees the next `count` bindings made in the scope. Functions bound
+    /// to names with `let` capture their own bindings this way, so they can refer to themselves by
+    /// those names.
+    pub fn capture_with_next_bindings(&self, count: usize) -> Environment {
+        let scope = self.scope();
+        let length = scope.borrow().length;
         Self {
-            mapping: HashMap::new(),
-            outer: Some(Box::new(outer.clone())),
+            scope: ScopeRef::Strong(scope),
+            captured: Some(self.captured.unwrap_or(length + count)),
         }
     }
 
-    pub fn insert(&mut self, id: &Identifier, value: Box<dyn Object>) {
-        self.mapping.insert(id.value.clone(), value);
+    fn scope(&self) -> Rc<RefCell<Scope>> {
+        match &self.scope {
+            ScopeRef::Strong(scope) => scope.clone(),
+            ScopeRef::Weak(scope) => scope
+                .upgrade()
+                .expect("a function bound in a scope should only be called while it's alive"),
+        }
     }
 
-    pub fn get(&self, id: &str) -> Option<&Box<dyn Object>> {
-        self.mapping.get(id).or_else(|| {
-            self.outer
+    fn upgrade(&self) -> Environment {
+        Self {
+            scope: ScopeRef::Strong(self.scope()),
+            captured: self.captured,
+        }
+    }
+
+    /// Whether this handle and `scope` refer to the same scope
+    fn is_scope(&self, scope: &Rc<RefCell<Scope>>) -> bool {
+        match &self.scope {
+            ScopeRef::Strong(own) => Rc::ptr_eq(own, scope),
+            ScopeRef::Weak(own) => std::ptr::eq(own.as_ptr(), Rc::as_ptr(scope)),
+        }
+    }
+
+    /// Binds `id` to `value` in this scope. The binding can't be assigned to.
+    pub fn insert(&mut self, id: &Identifier, value: Value) {
+        self.define(id, value, false);
+    }
+
+    /// Binds `id` to `value` in this scope, allowing later assignments to it.
+    pub fn insert_mutable(&mut self, id: &Identifier, value: Value) {
+        self.define(id, value, true);
+    }
+
+    fn define(&mut self, id: &Identifier, value: Value, mutable: bool) {
+        let scope = self.scope();
+        let value = self.detach(&scope, value);
+        let mut scope = scope.borrow_mut();
+        let index = scope.length;
+        scope.length += 1;
+        // A new binding shadows any previous binding of the same name, rather than updating it
+        let shadowed = scope.mapping.remove(&id.value).map(Box::new);
+        scope.mapping.insert(
+            id.value.clone(),
+            Binding {
+                value,
+                mutable,
+                index,
+                shadowed,
+            },
+        );
+    }
+
+    /// Makes a function that captured `scope` hold it weakly before it's stored there
+    fn detach(&self, scope: &Rc<RefCell<Scope>>, value: Value) -> Value {
+        match &value {
+            Value::Function(function) if function.env.is_scope(scope) => {
+                let env = Environment {
+                    scope: ScopeRef::Weak(Rc::downgrade(scope)),
+                    captured: function.env.captured,
+                };
+                Value::Function(Rc::new(Function::new(
+                    function.name.clone(),
+                    function.parameters.clone(),
+                    function.body.clone(),
+                    env,
+                )))
+            }
+            _ => value,
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<Value> {
+        let value = self.get_callee(id)?;
+        match &value {
+            // The function may outlive the scope once it's taken out of it
+            Value::Function(function) if matches!(function.env.scope, ScopeRef::Weak(_)) => {
+                Some(Value::Function(Rc::new(Function::new(
+                    function.name.clone(),
+                    function.parameters.clone(),
+                    function.body.clone(),
+                    function.env.upgrade(),
+                ))))
+            }
+            _ => Some(value),
+        }
+    }
+
+    /// Like `get`, but a function bound in the scope it captured may still only hold it weakly.
+    /// Cheaper than `get` for values that are called straight away.
+    pub fn get_callee(&self, id: &str) -> Option<Value> {
+        let scope = self.scope();
+        let scope = scope.borrow();
+        match scope.find(id, self.captured) {
+            Some(binding) => Some(binding.value.clone()),
+            None => scope
+                .outer
                 .as_ref()
-                .and_then(|environment| environment.get(id))
-        })
+                .and_then(|environment| environment.get_callee(id)),
+        }
+    }
+
+    /// Updates the nearest binding of `id`. Fails if there is no such binding, or if it isn't
+    /// mutable.
+    pub fn assign(&mut self, id: &str, value: Value) -> Result<(), EvaluatorError> {
+        let scope_ref = self.scope();
+        let mut scope = scope_ref.borrow_mut();
+        match scope.find_mut(id, self.captured) {
+            Some(binding) if binding.mutable => {
+                binding.value = self.detach(&scope_ref, value);
+                Ok(())
+            }
+            Some(_) => Err(EvaluatorError::new(
+                EvaluatorErrorKind::ImmutableAssignment {
+                    name: id.to_string(),
+                },
+            )),
+            None => match scope.outer.as_mut() {
+                Some(environment) => environment.assign(id, value),
+                None => Err(EvaluatorError::new(EvaluatorErrorKind::NameError {
+                    name: id.to_string(),
+                })),
+            },
+        }
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
     }
 }
@@ -0,0 +1,204 @@
+use std::fmt::{self, Display};
+
+use crate::object::Value;
+use crate::token::Span;
+
+/// The category of a runtime error, along with the details needed to describe it. Host code can
+/// match on this to tell errors apart without inspecting the message.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EvaluatorErrorKind {
+    /// A value had a different type than the operation required
+    TypeError {
+        expected: String,
+        found: String,
+    },
+    /// An identifier was used that isn't bound to anything
+    NameError {
+        name: String,
+    },
+    /// A function was called with the wrong number of arguments. `min` and `max` are the same
+    /// unless the function takes optional arguments.
+    ArityError {
+        min: usize,
+        max: usize,
+        found: usize,
+    },
+    /// A collection was indexed outside of its bounds
+    IndexError {
+        index: i64,
+        length: usize,
+    },
+    /// A binding that wasn't declared with `let mut` was assigned to
+    ImmutableAssignment {
+        name: String,
+    },
+    /// A hash was indexed with a key it doesn't contain. `key` is the inspected key.
+    KeyError {
+        key: String,
+    },
+    /// A record was accessed or updated with a field its struct doesn't declare
+    FieldError {
+        struct_name: String,
+        field: String,
+    },
+    ZeroDivision,
+    /// Calls to user defined functions nested more deeply than the evaluator's depth limit
+    StackOverflow {
+        limit: usize,
+    },
+    /// None of the arms of a match expression matched its subject. `value` is the inspected
+    /// subject.
+    NoMatch {
+        value: String,
+    },
+    /// A value had the right type, but its value isn't supported by the operation
+    ValueError {
+        message: String,
+    },
+    /// An invariant of the evaluator didn't hold. This indicates a bug in the interpreter rather
+    /// than in the program being evaluated.
+    InternalError {
+        message: String,
+    },
+}
+
+impl EvaluatorErrorKind {
+    /// The name of the error category, as shown to users
+    pub fn name(&self) -> &'static str {
+        match self {
+            EvaluatorErrorKind::TypeError { .. } => "TypeError",
+            EvaluatorErrorKind::NameError { .. } => "NameError",
+            EvaluatorErrorKind::ArityError { .. } => "ArityError",
+            EvaluatorErrorKind::IndexError { .. } => "IndexError",
+            EvaluatorErrorKind::KeyError { .. } => "KeyError",
+            EvaluatorErrorKind::FieldError { .. } => "FieldError",
+            EvaluatorErrorKind::ImmutableAssignment { .. } => "AssignmentError",
+            EvaluatorErrorKind::ZeroDivision => "ZeroDivisionError",
+            EvaluatorErrorKind::StackOverflow { .. } => "StackOverflowError",
+            EvaluatorErrorKind::NoMatch { .. } => "MatchError",
+            EvaluatorErrorKind::ValueError { .. } => "ValueError",
+            EvaluatorErrorKind::InternalError { .. } => "InternalError",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            EvaluatorErrorKind::TypeError { expected, found } => {
+                format!("Expected {expected}, found {found}")
+            }
+            EvaluatorErrorKind::NameError { name } => format!("Unknown identifier found: {name}"),
+            EvaluatorErrorKind::ArityError { min, max, found } => {
+                let plural = if *max == 1 { "" } else { "s" };
+                if min == max {
+                    format!("Expected {max} argument{plural}, found {found}")
+                } else if *max == min + 1 {
+                    format!("Expected {min} or {max} argument{plural}, found {found}")
+                } else {
+                    format!("Expected {min} to {max} argument{plural}, found {found}")
+                }
+            }
+            EvaluatorErrorKind::IndexError { index, length } => {
+                format!("Index {index} is out of bounds for length {length}")
+            }
+            EvaluatorErrorKind::KeyError { key } => format!("Key {key} not found"),
+            EvaluatorErrorKind::FieldError { struct_name, field } => {
+                format!("{struct_name} has no field `{field}`")
+            }
+            EvaluatorErrorKind::ImmutableAssignment { name } => {
+                format!("Cannot assign to immutable binding `{name}`")
+            }
+            EvaluatorErrorKind::ZeroDivision => "Division by zero".to_string(),
+            EvaluatorErrorKind::StackOverflow { limit } => {
+                format!("Maximum call depth of {limit} exceeded")
+            }
+            EvaluatorErrorKind::NoMatch { value } => format!("No pattern matched {value}"),
+            EvaluatorErrorKind::ValueError { message }
+            | EvaluatorErrorKind::InternalError { message } => message.clone(),
+        }
+    }
+}
+
+/// A function call that was in progress when an error occurred
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    // The name the function was bound to, or `<anonymous>`
+    pub function_name: String,
+    // The name of the function the call expression is in, or `<program>`. That's usually the
+    // previous frame's function, but not for a tail call, which replaces the frame of the function
+    // that made it.
+    pub caller_name: String,
+    // The span of the call expression that entered this frame
+    pub call_site: Span,
+}
+
+impl Frame {
+    pub fn new(function_name: &str, caller_name: &str, call_site: Span) -> Self {
+        Self {
+            function_name: function_name.to_string(),
+            caller_name: caller_name.to_string(),
+            call_site,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct EvaluatorError {
+    pub kind: EvaluatorErrorKind,
+    // The span of the innermost AST node whose evaluation produced this error. Set by `eval` as
+    // the error propagates, so code creating an error doesn't need to know where it is.
+    pub span: Option<Span>,
+    // The function calls that were in progress when the error occurred, outermost first. Empty
+    // if the error occurred at the top level of the program.
+    pub backtrace: Vec<Frame>,
+}
+
+impl EvaluatorError {
+    pub fn new(kind: EvaluatorErrorKind) -> Self {
+        Self {
+            kind,
+            span: None,
+            backtrace: Vec::new(),
+        }
+    }
+
+    pub fn type_error(expected: &str, found: &Value) -> Self {
+        Self::new(EvaluatorErrorKind::TypeError {
+            expected: expected.to_string(),
+            found: found.type_name().to_string(),
+        })
+    }
+
+    pub fn arity_error(expected: usize, found: usize) -> Self {
+        Self::arity_range_error(expected, expected, found)
+    }
+
+    /// An arity error for a function that takes between `min` and `max` arguments
+    pub fn arity_range_error(min: usize, max: usize, found: usize) -> Self {
+        Self::new(EvaluatorErrorKind::ArityError { min, max, found })
+    }
+
+    pub fn value_error(message: &str) -> Self {
+        Self::new(EvaluatorErrorKind::ValueError {
+            message: message.to_string(),
+        })
+    }
+
+    pub fn internal_error(message: &str) -> Self {
+        Self::new(EvaluatorErrorKind::InternalError {
+            message: message.to_string(),
+        })
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        if self.span.is_none() {
+            self.span = Some(span);
+        }
+        self
+    }
+}
+
+impl Display for EvaluatorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.kind.name(), self.kind.message())
+    }
+}
@@ -0,0 +1,956 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use im_rc::Vector;
+use indexmap::IndexMap;
+use typed_arena::Arena;
+
+use crate::ast::{
+    ArrayExpression, AssignExpression, BigIntegerLiteral, BlockStatement, BooleanLiteral,
+    BreakStatement, CallExpression, ContinueStatement, Expression, ExpressionStatement,
+    FloatLiteral, ForStatement, FunctionLiteral, HashExpression, Identifier, IfExpression,
+    IndexExpression, InfixExpression, IntegerLiteral, InterpolatedStringExpression, LetStatement,
+    MatchExpression, MemberExpression, Node, NullLiteral, PrefixExpression, Program,
+    ReturnStatement, SliceExpression, Statement, StringExpression, StructStatement, WhileStatement,
+    WithExpression,
+};
+use crate::evaluator::environment::Environment;
+use crate::evaluator::pattern::{bind_pattern, match_pattern};
+use crate::evaluator::{
+    EvaluatorError, EvaluatorErrorKind, Frame, apply_infix, apply_prefix, get_field, hash_key,
+    index_collection, is_truthy, iterate, slice_collection, update_record,
+};
+use crate::object::{BuiltinFunction, Function, Record, Struct, Value};
+use crate::token::Span;
+
+/// A unit of work for the machine. Evaluating a node pushes tasks for its children, followed by
+/// a task that combines their values once the children have left them on the value stack, so
+/// nesting in the program grows the task stack rather than the native stack.
+enum Task<'a> {
+    /// Evaluates a node, leaving its value on the value stack
+    Eval(&'a dyn Node, Environment),
+    /// Evaluates the statement at `index`, after discarding the value of the statement before
+    /// it. The value of the last statement is left as the value of the block.
+    Block {
+        statements: &'a [Box<dyn Statement>],
+        index: usize,
+        env: Environment,
+    },
+    Prefix(&'a PrefixExpression),
+    Infix(&'a InfixExpression),
+    // Decides whether the right operand of `&&` or `||` needs to be evaluated
+    Logical(&'a InfixExpression, Environment),
+    // Replaces the value on top of the stack with its truthiness
+    Truthy,
+    Index(&'a IndexExpression),
+    Slice(&'a SliceExpression),
+    Member(&'a MemberExpression),
+    With(&'a WithExpression),
+    // Checks that the value on top of the stack can be used as a hash key
+    HashKey(&'a dyn Expression),
+    Hash(&'a HashExpression),
+    Array(&'a ArrayExpression),
+    Interpolate(&'a InterpolatedStringExpression),
+    Call(&'a CallExpression, Environment),
+    // Picks the branch of an if expression once the condition at `index` has been evaluated
+    IfBranch {
+        if_expression: &'a IfExpression,
+        index: usize,
+        env: Environment,
+    },
+    // Picks the arm of a match expression once its subject has been evaluated
+    Match(&'a MatchExpression, Environment),
+    // Decides whether to take the arm of a match expression at `index` once its guard has been
+    // evaluated, or to try the arms after it
+    MatchGuard {
+        match_expression: &'a MatchExpression,
+        index: usize,
+        subject: Value,
+        env: Environment,
+        arm_env: Environment,
+    },
+    Let(&'a LetStatement, Environment),
+    Assign(&'a AssignExpression, Environment),
+    WhileCondition(&'a WhileStatement, Environment),
+    ForIterable(&'a ForStatement, Environment),
+    // Loop markers, which run after each evaluation of a loop's body. `break` and `continue`
+    // unwind the task stack to the nearest one, and drop any values above `values_height`.
+    WhileBody {
+        while_statement: &'a WhileStatement,
+        env: Environment,
+        values_height: usize,
+    },
+    ForBody {
+        for_statement: &'a ForStatement,
+        env: Environment,
+        items: Box<dyn Iterator<Item = Value>>,
+        values_height: usize,
+    },
+    // Marks the end of a call to a user defined function, and records the call for backtraces.
+    // `return` unwinds the task stack to the nearest one. A call made while this is on top of the
+    // task stack is a tail call, and replaces the call recorded here rather than pushing a new
+    // marker. `tail_caller` is the function whose call was replaced, which the call site is in.
+    FinishCall {
+        function: Rc<Function>,
+        call_site: Span,
+        tail_caller: Option<Rc<Function>>,
+        values_height: usize,
+    },
+    // Marks the end of the program, for `return` statements outside of any function
+    FinishProgram,
+}
+
+impl<'a> Task<'a> {
+    /// The node whose evaluation this task is part of. Errors raised by the task point at it.
+    fn node(&self) -> Option<&'a dyn Node> {
+        match self {
+            Task::Eval(node, _) => Some(*node),
+            Task::Prefix(node) => Some(*node),
+            Task::Infix(node) | Task::Logical(node, _) => Some(*node),
+            Task::Index(node) => Some(*node),
+            Task::Slice(node) => Some(*node),
+            Task::Member(node) => Some(*node),
+            Task::With(node) => Some(*node),
+            Task::HashKey(node) => Some(*node as &dyn Node),
+            Task::Hash(node) => Some(*node),
+            Task::Array(node) => Some(*node),
+            Task::Interpolate(node) => Some(*node),
+            Task::Call(node, _) => Some(*node),
+            Task::IfBranch { if_expression, .. } => Some(*if_expression),
+            Task::Match(node, _) => Some(*node),
+            Task::MatchGuard {
+                match_expression, ..
+            } => Some(*match_expression),
+            Task::Let(node, _) => Some(*node),
+            Task::Assign(node, _) => Some(*node),
+            Task::WhileCondition(node, _) => Some(*node),
+            Task::ForIterable(node, _) => Some(*node),
+            Task::Block { .. }
+            | Task::Truthy
+            | Task::WhileBody { .. }
+            | Task::ForBody { .. }
+            | Task::FinishCall { .. }
+            | Task::FinishProgram => None,
+        }
+    }
+}
+
+/// Evaluates a program with explicit, heap allocated stacks of tasks and values, so the depth of
+/// recursion in the program is limited by `max_depth` and memory rather than by the native stack.
+pub struct Machine<'a> {
+    builtin_fns: &'a HashMap<String, BuiltinFunction>,
+    max_depth: usize,
+    tasks: Vec<Task<'a>>,
+    values: Vec<Value>,
+    // The number of calls to user defined functions in progress
+    depth: usize,
+    // Tasks borrow the statements of the functions they're evaluating, so each body that gets
+    // called is kept alive here until the machine is done, even if its function is dropped
+    // partway through a call. Bodies are looked up by address so each is only kept once.
+    bodies: &'a Arena<Rc<BlockStatement>>,
+    retained_bodies: HashMap<*const BlockStatement, &'a BlockStatement>,
+}
+
+impl<'a> Machine<'a> {
+    pub fn new(
+        builtin_fns: &'a HashMap<String, BuiltinFunction>,
+        max_depth: usize,
+        bodies: &'a Arena<Rc<BlockStatement>>,
+    ) -> Self {
+        Self {
+            builtin_fns,
+            max_depth,
+            tasks: Vec::new(),
+            values: Vec::new(),
+            depth: 0,
+            bodies,
+            retained_bodies: HashMap::new(),
+        }
+    }
+
+    pub fn run(&mut self, node: &'a dyn Node, env: Environment) -> Result<Value, EvaluatorError> {
+        self.tasks.push(Task::FinishProgram);
+        self.tasks.push(Task::Eval(node, env));
+        while let Some(task) = self.tasks.pop() {
+            let node = task.node();
+            if let Err(mut error) = self.step(task) {
+                if let Some(node) = node {
+                    error = error.with_span(node.span());
+                }
+                error.backtrace = self.backtrace();
+                return Err(error);
+            }
+        }
+        self.values
+            .pop()
+            .ok_or_else(|| EvaluatorError::internal_error("Program left no value on the stack"))
+    }
+
+    fn step(&mut self, task: Task<'a>) -> Result<(), EvaluatorError> {
+        match task {
+            Task::Eval(node, env) => return self.eval_node(node, env),
+            Task::Block {
+                statements,
+                index,
+                env,
+            } => {
+                if index > 0 {
+                    self.values.pop();
+                }
+                match statements.get(index) {
+                    Some(statement) => {
+                        if index + 1 < statements.len() {
+                            self.tasks.push(Task::Block {
+                                statements,
+                                index: index + 1,
+                                env: env.clone(),
+                            });
+                        }
+                        self.tasks.push(Task::Eval(statement.as_ref(), env));
+                    }
+                    // An empty block evaluates to null
+                    None => self.values.push(Value::Null),
+                }
+            }
+            Task::Prefix(prefix_expression) => {
+                let right = self.pop_value()?;
+                self.values.push(apply_prefix(prefix_expression, right)?);
+            }
+            Task::Infix(infix_expression) => {
+                let right = self.pop_value()?;
+                let left = self.pop_value()?;
+                self.values
+                    .push(apply_infix(infix_expression, left, right)?);
+            }
+            Task::Logical(infix_expression, env) => {
+                // `&&` and `||` only evaluate their right operand if the left operand doesn't
+                // already decide the result. Both operators return a boolean.
+                let left = is_truthy(&self.pop_value()?);
+                match (infix_expression.operator.as_ref(), left) {
+                    ("&&", false) => self.values.push(Value::Boolean(false)),
+                    ("||", true) => self.values.push(Value::Boolean(true)),
+                    _ => {
+                        self.tasks.push(Task::Truthy);
+                        self.tasks
+                            .push(Task::Eval(infix_expression.right.as_ref(), env));
+                    }
+                }
+            }
+            Task::Truthy => {
+                let value = self.pop_value()?;
+                self.values.push(Value::Boolean(is_truthy(&value)));
+            }
+            Task::Index(index_expression) => {
+                let collection = self.pop_value()?;
+                let index = self.pop_value()?;
+                self.values
+                    .push(index_collection(index_expression, collection, index)?);
+            }
+            Task::Slice(slice_expression) => {
+                let end = match slice_expression.end {
+                    Some(_) => Some(self.pop_value()?),
+                    None => None,
+                };
+                let start = match slice_expression.start {
+                    Some(_) => Some(self.pop_value()?),
+                    None => None,
+                };
+                let collection = self.pop_value()?;
+                self.values
+                    .push(slice_collection(slice_expression, collection, start, end)?);
+            }
+            Task::HashKey(_) => {
+                if let Some(key) = self.values.last() {
+                    hash_key(key)?;
+                }
+            }
+            Task::Hash(hash_expression) => {
+                let values = self.pop_values(hash_expression.pairs.len() * 2)?;
+                let mut pairs = IndexMap::new();
+                let mut values = values.into_iter();
+                while let (Some(key), Some(value)) = (values.next(), values.next()) {
+                    pairs.insert(hash_key(&key)?, value);
+                }
+                self.values.push(Value::hash(pairs));
+            }
+            Task::Member(member_expression) => {
+                let record = self.pop_value()?;
+                self.values.push(get_field(member_expression, record)?);
+            }
+            Task::With(with_expression) => {
+                let values = self.pop_values(with_expression.updates.len())?;
+                let record = self.pop_value()?;
+                self.values
+                    .push(update_record(with_expression, record, values)?);
+            }
+            Task::Array(array_expression) => {
+                let items = self.pop_values(array_expression.items.len())?;
+                self.values.push(Value::array(Vector::from(items)));
+            }
+            Task::Interpolate(interpolated_string_expression) => {
+                let parts = self.pop_values(interpolated_string_expression.parts.len())?;
+                let value: String = parts.iter().map(Value::display).collect();
+                self.values.push(Value::string(value));
+            }
+            Task::Call(call_expression, env) => return self.apply_call(call_expression, env),
+            Task::IfBranch {
+                if_expression,
+                index,
+                env,
+            } => {
+                let condition = self.pop_value()?;
+                if is_truthy(&condition) {
+                    let branch = &if_expression.consequences[index].1;
+                    self.tasks.push(Task::Eval(branch, env));
+                } else {
+                    self.next_branch(if_expression, index + 1, env);
+                }
+            }
+            Task::Match(match_expression, env) => {
+                let subject = self.pop_value()?;
+                self.next_arm(match_expression, 0, subject, env)?;
+            }
+            Task::MatchGuard {
+                match_expression,
+                index,
+                subject,
+                env,
+                arm_env,
+            } => {
+                let condition = self.pop_value()?;
+                if is_truthy(&condition) {
+                    let value = &match_expression.arms[index].value;
+                    self.tasks.push(Task::Eval(value.as_ref(), arm_env));
+                } else {
+                    self.next_arm(match_expression, index + 1, subject, env)?;
+                }
+            }
+            Task::Let(let_statement, mut env) => {
+                let value = self.pop_value()?;
+                bind_pattern(
+                    let_statement.pattern.as_ref(),
+                    value,
+                    &mut env,
+                    let_statement.mutable,
+                )?;
+                self.values.push(Value::Null);
+            }
+            Task::Assign(assign_expression, mut env) => {
+                let value = self.pop_value()?;
+                env.assign(&assign_expression.name.value, value.clone())
+                    .map_err(|error| error.with_span(assign_expression.name.span))?;
+                self.values.push(value);
+            }
+            Task::WhileCondition(while_statement, env) => {
+                let condition = self.pop_value()?;
+                if is_truthy(&condition) {
+                    self.tasks.push(Task::WhileBody {
+                        while_statement,
+                        env: env.clone(),
+                        values_height: self.values.len(),
+                    });
+                    self.tasks.push(Task::Eval(&while_statement.body, env));
+                } else {
+                    // Loops are statements, so like let statements they evaluate to null
+                    self.values.push(Value::Null);
+                }
+            }
+            Task::WhileBody {
+                while_statement,
+                env,
+                ..
+            } => {
+                self.values.pop();
+                self.tasks
+                    .push(Task::WhileCondition(while_statement, env.clone()));
+                self.tasks
+                    .push(Task::Eval(while_statement.condition.as_ref(), env));
+            }
+            Task::ForIterable(for_statement, env) => {
+                let iterable = self.pop_value()?;
+                let items = iterate(for_statement, iterable)?;
+                self.next_iteration(for_statement, env, items);
+            }
+            Task::ForBody {
+                for_statement,
+                env,
+                items,
+                ..
+            } => {
+                self.values.pop();
+                self.next_iteration(for_statement, env, items);
+            }
+            Task::FinishCall { .. } => {
+                // The call's value is left on the stack as the value of the call expression
+                self.depth -= 1;
+            }
+            Task::FinishProgram => {}
+        }
+        Ok(())
+    }
+
+    fn eval_node(
+        &mut self,
+        node: &'a dyn Node,
+        mut env: Environment,
+    ) -> Result<(), EvaluatorError> {
+        let any = node.as_any();
+        let value = if let Some(program) = any.downcast_ref::<Program>() {
+            self.tasks.push(Task::Block {
+                statements: &program.statements,
+                index: 0,
+                env,
+            });
+            return Ok(());
+        } else if let Some(statement) = any.downcast_ref::<ExpressionStatement>() {
+            self.tasks
+                .push(Task::Eval(statement.expression.as_ref(), env));
+            return Ok(());
+        } else if let Some(integer_literal) = any.downcast_ref::<IntegerLiteral>() {
+            Value::Integer(integer_literal.value)
+        } else if let Some(big_integer_literal) = any.downcast_ref::<BigIntegerLiteral>() {
+            Value::BigInteger(Rc::new(big_integer_literal.value.clone()))
+        } else if let Some(float_literal) = any.downcast_ref::<FloatLiteral>() {
+            Value::Float(float_literal.value)
+        } else if let Some(boolean_literal) = any.downcast_ref::<BooleanLiteral>() {
+            Value::Boolean(boolean_literal.value)
+        } else if any.is::<NullLiteral>() {
+            Value::Null
+        } else if let Some(function_literal) = any.downcast_ref::<FunctionLiteral>() {
+            Value::Function(Rc::new(new_function(function_literal, None, env.capture())))
+        } else if let Some(identifier) = any.downcast_ref::<Identifier>() {
+            match env.get(&identifier.value) {
+                Some(value) => value,
+                None => match self.builtin_fns.get(&identifier.value) {
+                    Some(builtin_fn) => Value::Builtin(builtin_fn.clone()),
+                    None => {
+                        return Err(EvaluatorError::new(EvaluatorErrorKind::NameError {
+                            name: identifier.value.clone(),
+                        }));
+                    }
+                },
+            }
+        } else if let Some(member_expression) = any.downcast_ref::<MemberExpression>() {
+            self.tasks.push(Task::Member(member_expression));
+            self.tasks
+                .push(Task::Eval(member_expression.record.as_ref(), env));
+            return Ok(());
+        } else if let Some(with_expression) = any.downcast_ref::<WithExpression>() {
+            // The record is evaluated before the new values of its fields
+            self.tasks.push(Task::With(with_expression));
+            for (_, value) in with_expression.updates.iter().rev() {
+                self.tasks.push(Task::Eval(value.as_ref(), env.clone()));
+            }
+            self.tasks
+                .push(Task::Eval(with_expression.record.as_ref(), env));
+            return Ok(());
+        } else if let Some(index_expression) = any.downcast_ref::<IndexExpression>() {
+            // The index is evaluated before the collection
+            self.tasks.push(Task::Index(index_expression));
+            self.tasks.push(Task::Eval(
+                index_expression.collection.as_ref(),
+                env.clone(),
+            ));
+            self.tasks
+                .push(Task::Eval(index_expression.index.as_ref(), env));
+            return Ok(());
+        } else if let Some(slice_expression) = any.downcast_ref::<SliceExpression>() {
+            self.tasks.push(Task::Slice(slice_expression));
+            for bound in [&slice_expression.end, &slice_expression.start]
+                .into_iter()
+                .flatten()
+            {
+                self.tasks.push(Task::Eval(bound.as_ref(), env.clone()));
+            }
+            self.tasks
+                .push(Task::Eval(slice_expression.collection.as_ref(), env));
+            return Ok(());
+        } else if let Some(hash_expression) = any.downcast_ref::<HashExpression>() {
+            self.tasks.push(Task::Hash(hash_expression));
+            for (key_expression, value_expression) in hash_expression.pairs.iter().rev() {
+                self.tasks
+                    .push(Task::Eval(value_expression.as_ref(), env.clone()));
+                self.tasks.push(Task::HashKey(key_expression.as_ref()));
+                self.tasks
+                    .push(Task::Eval(key_expression.as_ref(), env.clone()));
+            }
+            return Ok(());
+        } else if let Some(call_expression) = any.downcast_ref::<CallExpression>() {
+            // The arguments are evaluated before the function. Functions that are called by name
+            // or defined in place are looked up when the call is applied, and any other function
+            // is evaluated onto the value stack above the arguments.
+            self.tasks.push(Task::Call(call_expression, env.clone()));
+            let callee = call_expression.function.as_ref();
+            if !(callee.as_any().is::<Identifier>() || callee.as_any().is::<FunctionLiteral>()) {
+                self.tasks.push(Task::Eval(callee, env.clone()));
+            }
+            for argument in call_expression.arguments.iter().rev() {
+                self.tasks.push(Task::Eval(argument.as_ref(), env.clone()));
+            }
+            return Ok(());
+        } else if let Some(prefix_expression) = any.downcast_ref::<PrefixExpression>() {
+            self.tasks.push(Task::Prefix(prefix_expression));
+            self.tasks
+                .push(Task::Eval(prefix_expression.right.as_ref(), env));
+            return Ok(());
+        } else if let Some(infix_expression) = any.downcast_ref::<InfixExpression>() {
+            if matches!(infix_expression.operator.as_ref(), "&&" | "||") {
+                self.tasks
+                    .push(Task::Logical(infix_expression, env.clone()));
+            } else {
+                self.tasks.push(Task::Infix(infix_expression));
+                self.tasks
+                    .push(Task::Eval(infix_expression.right.as_ref(), env.clone()));
+            }
+            self.tasks
+                .push(Task::Eval(infix_expression.left.as_ref(), env));
+            return Ok(());
+        } else if let Some(if_expression) = any.downcast_ref::<IfExpression>() {
+            self.next_branch(if_expression, 0, env);
+            return Ok(());
+        } else if let Some(match_expression) = any.downcast_ref::<MatchExpression>() {
+            self.tasks.push(Task::Match(match_expression, env.clone()));
+            self.tasks
+                .push(Task::Eval(match_expression.subject.as_ref(), env));
+            return Ok(());
+        } else if let Some(array_expression) = any.downcast_ref::<ArrayExpression>() {
+            self.tasks.push(Task::Array(array_expression));
+            for item in array_expression.items.iter().rev() {
+                self.tasks.push(Task::Eval(item.as_ref(), env.clone()));
+            }
+            return Ok(());
+        } else if let Some(string_expression) = any.downcast_ref::<StringExpression>() {
+            Value::string(string_expression.value.as_str())
+        } else if let Some(interpolated_string_expression) =
+            any.downcast_ref::<InterpolatedStringExpression>()
+        {
+            self.tasks
+                .push(Task::Interpolate(interpolated_string_expression));
+            for part in interpolated_string_expression.parts.iter().rev() {
+                self.tasks.push(Task::Eval(part.as_ref(), env.clone()));
+            }
+            return Ok(());
+        } else if let Some(block_statement) = any.downcast_ref::<BlockStatement>() {
+            self.tasks.push(Task::Block {
+                statements: &block_statement.statements,
+                index: 0,
+                env: Environment::new_wrapped(&env),
+            });
+            return Ok(());
+        } else if let Some(return_statement) = any.downcast_ref::<ReturnStatement>() {
+            // Drop everything the function was doing, so the return value is evaluated in tail
+            // position
+            let values_height = loop {
+                match self.tasks.last() {
+                    Some(Task::FinishCall { values_height, .. }) => break *values_height,
+                    Some(Task::FinishProgram) | None => break 0,
+                    Some(_) => {
+                        self.tasks.pop();
+                    }
+                }
+            };
+            self.values.truncate(values_height);
+            self.tasks
+                .push(Task::Eval(return_statement.return_value.as_ref(), env));
+            return Ok(());
+        } else if let Some(let_statement) = any.downcast_ref::<LetStatement>() {
+            if let_statement.rec {
+                bind_recursive_functions(let_statement, env)?;
+                self.values.push(Value::Null);
+                return Ok(());
+            }
+            // Functions bound directly to a name with let are named after the binding, so they
+            // can be identified in backtraces
+            let function_binding = let_statement
+                .pattern
+                .as_any()
+                .downcast_ref::<Identifier>()
+                .zip(
+                    let_statement
+                        .value
+                        .as_any()
+                        .downcast_ref::<FunctionLiteral>(),
+                );
+            let Some((name, function_literal)) = function_binding else {
+                self.tasks.push(Task::Let(let_statement, env.clone()));
+                self.tasks
+                    .push(Task::Eval(let_statement.value.as_ref(), env));
+                return Ok(());
+            };
+            let function = Value::Function(Rc::new(new_function(
+                function_literal,
+                Some(name.value.clone()),
+                env.capture_with_next_bindings(1),
+            )));
+            if let_statement.mutable {
+                env.insert_mutable(name, function);
+            } else {
+                env.insert(name, function);
+            }
+            Value::Null
+        } else if let Some(assign_expression) = any.downcast_ref::<AssignExpression>() {
+            self.tasks
+                .push(Task::Assign(assign_expression, env.clone()));
+            self.tasks
+                .push(Task::Eval(assign_expression.value.as_ref(), env));
+            return Ok(());
+        } else if let Some(struct_statement) = any.downcast_ref::<StructStatement>() {
+            let fields = struct_statement
+                .fields
+                .iter()
+                .map(|field| field.value.clone())
+                .collect();
+            let struct_type = Struct::new(struct_statement.name.value.clone(), fields);
+            env.insert(&struct_statement.name, Value::Struct(Rc::new(struct_type)));
+            Value::Null
+        } else if let Some(while_statement) = any.downcast_ref::<WhileStatement>() {
+            self.tasks
+                .push(Task::WhileCondition(while_statement, env.clone()));
+            self.tasks
+                .push(Task::Eval(while_statement.condition.as_ref(), env));
+            return Ok(());
+        } else if let Some(for_statement) = any.downcast_ref::<ForStatement>() {
+            self.tasks
+                .push(Task::ForIterable(for_statement, env.clone()));
+            self.tasks
+                .push(Task::Eval(for_statement.iterable.as_ref(), env));
+            return Ok(());
+        } else if any.is::<BreakStatement>() {
+            let values_height = self.unwind_to_loop()?;
+            self.tasks.pop();
+            self.values.truncate(values_height);
+            self.values.push(Value::Null);
+            return Ok(());
+        } else if any.is::<ContinueStatement>() {
+            // The loop marker is left in place, and runs the next iteration once it has
+            // discarded the value standing in for the body's
+            let values_height = self.unwind_to_loop()?;
+            self.values.truncate(values_height);
+            self.values.push(Value::Null);
+            return Ok(());
+        } else {
+            return Err(EvaluatorError::internal_error(
+                "Evaluator encountered unknown AST type",
+            ));
+        };
+        self.values.push(value);
+        Ok(())
+    }
+
+    /// Applies a call once its arguments are on the value stack.
+    fn apply_call(
+        &mut self,
+        call_expression: &'a CallExpression,
+        env: Environment,
+    ) -> Result<(), EvaluatorError> {
+        let callee = call_expression.function.as_ref();
+        if let Some(function_literal) = callee.as_any().downcast_ref::<FunctionLiteral>() {
+            let arguments = self.pop_values(call_expression.arguments.len())?;
+            let function = Rc::new(new_function(function_literal, None, env.capture()));
+            self.call_function(function, arguments, call_expression.span)
+        } else if let Some(identifier) = callee.as_any().downcast_ref::<Identifier>() {
+            let arguments = self.pop_values(call_expression.arguments.len())?;
+            match &env.get_callee(&identifier.value) {
+                Some(value) => self.call_value(value, arguments, call_expression.span),
+                // Check for builtin functions here
+                None => match self.builtin_fns.get(&identifier.value) {
+                    Some(builtin_fn) => {
+                        self.values.push(builtin_fn(arguments)?);
+                        Ok(())
+                    }
+                    None => Err(EvaluatorError::new(EvaluatorErrorKind::NameError {
+                        name: identifier.value.clone(),
+                    })),
+                },
+            }
+        } else {
+            // The function was evaluated after the arguments, so it's on top of them
+            let function = self.pop_value()?;
+            let arguments = self.pop_values(call_expression.arguments.len())?;
+            self.call_value(&function, arguments, call_expression.span)
+        }
+    }
+
+    /// Calls `value`, which should be a function or a struct
+    fn call_value(
+        &mut self,
+        value: &Value,
+        arguments: Vec<Value>,
+        call_site: Span,
+    ) -> Result<(), EvaluatorError> {
+        match value {
+            Value::Function(function) => self.call_function(function.clone(), arguments, call_site),
+            // Calling a struct creates a record with the arguments as its fields
+            Value::Struct(struct_type) => {
+                if struct_type.fields.len() != arguments.len() {
+                    return Err(EvaluatorError::arity_error(
+                        struct_type.fields.len(),
+                        arguments.len(),
+                    ));
+                }
+                let record = Record::new(struct_type.clone(), arguments);
+                self.values.push(Value::Record(Rc::new(record)));
+                Ok(())
+            }
+            value => Err(EvaluatorError::type_error("function", value)),
+        }
+    }
+
+    /// Starts a call to a user defined function. A call in tail position replaces the frame of
+    /// the call that made it, so tail calls don't count towards the depth limit or grow the
+    /// backtrace.
+    fn call_function(
+        &mut self,
+        function: Rc<Function>,
+        arguments: Vec<Value>,
+        call_site: Span,
+    ) -> Result<(), EvaluatorError> {
+        if function.parameters.len() != arguments.len() {
+            return Err(EvaluatorError::arity_error(
+                function.parameters.len(),
+                arguments.len(),
+            ));
+        }
+        if let Some(Task::FinishCall {
+            function: current_function,
+            call_site: current_call_site,
+            tail_caller,
+            ..
+        }) = self.tasks.last_mut()
+        {
+            *tail_caller = Some(std::mem::replace(current_function, function.clone()));
+            *current_call_site = call_site;
+        } else {
+            if self.depth >= self.max_depth {
+                return Err(EvaluatorError::new(EvaluatorErrorKind::StackOverflow {
+                    limit: self.max_depth,
+                }));
+            }
+            self.depth += 1;
+            self.tasks.push(Task::FinishCall {
+                function: function.clone(),
+                call_site,
+                tail_caller: None,
+                values_height: self.values.len(),
+            });
+        }
+
+        // Each call gets its own scope for its parameters, nested in the scope the function was
+        // defined in
+        let mut call_env = Environment::new_wrapped(&function.env);
+        for (parameter, argument) in function.parameters.iter().zip(arguments) {
+            bind_pattern(parameter.as_ref(), argument, &mut call_env, false)?;
+        }
+        let body = self.retain_body(&function.body);
+        self.tasks.push(Task::Block {
+            statements: &body.statements,
+            index: 0,
+            env: call_env,
+        });
+        Ok(())
+    }
+
+    fn retain_body(&mut self, body: &Rc<BlockStatement>) -> &'a BlockStatement {
+        let key = Rc::as_ptr(body);
+        if let Some(retained) = self.retained_bodies.get(&key) {
+            return retained;
+        }
+        let retained: &'a Rc<BlockStatement> = self.bodies.alloc(body.clone());
+        self.retained_bodies.insert(key, retained);
+        retained
+    }
+
+    /// Evaluates the condition of the if expression's branch at `index`, or the alternative if
+    /// there are no branches left.
+    fn next_branch(&mut self, if_expression: &'a IfExpression, index: usize, env: Environment) {
+        if let Some((condition, _)) = if_expression.consequences.get(index) {
+            self.tasks.push(Task::IfBranch {
+                if_expression,
+                index,
+                env: env.clone(),
+            });
+            self.tasks.push(Task::Eval(condition.as_ref(), env));
+        } else if let Some(alternative) = &if_expression.alternative {
+            self.tasks.push(Task::Eval(alternative, env));
+        } else {
+            // If there's no else branch and all the conditions are falsey, the if expression
+            // evaluates to null
+            self.values.push(Value::Null);
+        }
+    }
+
+    /// Takes the first arm of the match expression from `index` onwards whose pattern matches
+    /// `subject`, evaluating its guard first if it has one. Fails if none of them match.
+    fn next_arm(
+        &mut self,
+        match_expression: &'a MatchExpression,
+        index: usize,
+        subject: Value,
+        env: Environment,
+    ) -> Result<(), EvaluatorError> {
+        // Each arm gets its own scope for the names its pattern binds. Patterns only bind names
+        // if they match, so one scope can be shared by the arms that are tried here.
+        let mut arm_env = Environment::new_wrapped(&env);
+        for (index, arm) in match_expression.arms.iter().enumerate().skip(index) {
+            if !match_pattern(arm.pattern.as_ref(), subject.clone(), &mut arm_env)? {
+                continue;
+            }
+            match &arm.guard {
+                Some(guard) => {
+                    self.tasks.push(Task::MatchGuard {
+                        match_expression,
+                        index,
+                        subject,
+                        env,
+                        arm_env: arm_env.clone(),
+                    });
+                    self.tasks.push(Task::Eval(guard.as_ref(), arm_env));
+                }
+                None => self.tasks.push(Task::Eval(arm.value.as_ref(), arm_env)),
+            }
+            return Ok(());
+        }
+        Err(EvaluatorError::new(EvaluatorErrorKind::NoMatch {
+            value: subject.inspect(),
+        }))
+    }
+
+    fn next_iteration(
+        &mut self,
+        for_statement: &'a ForStatement,
+        env: Environment,
+        mut items: Box<dyn Iterator<Item = Value>>,
+    ) {
+        let Some(item) = items.next() else {
+            self.values.push(Value::Null);
+            return;
+        };
+        // Each iteration gets a fresh scope, so the loop variable and anything bound in the body
+        // don't leak out of the loop
+        let mut loop_env = Environment::new_wrapped(&env);
+        loop_env.insert(&for_statement.variable, item);
+        self.tasks.push(Task::ForBody {
+            for_statement,
+            env,
+            items,
+            values_height: self.values.len(),
+        });
+        self.tasks.push(Task::Block {
+            statements: &for_statement.body.statements,
+            index: 0,
+            env: loop_env,
+        });
+    }
+
+    /// Pops tasks until the innermost loop's marker is on top of the task stack, and returns the
+    /// height of the value stack when the loop's body started.
+    fn unwind_to_loop(&mut self) -> Result<usize, EvaluatorError> {
+        loop {
+            match self.tasks.last() {
+                Some(
+                    Task::WhileBody { values_height, .. } | Task::ForBody { values_height, .. },
+                ) => return Ok(*values_height),
+                // The parser rejects break and continue outside of loops
+                Some(Task::FinishCall { .. } | Task::FinishProgram) | None => {
+                    return Err(EvaluatorError::internal_error(
+                        "Found break or continue outside of a loop",
+                    ));
+                }
+                Some(_) => {
+                    self.tasks.pop();
+                }
+            }
+        }
+    }
+
+    fn pop_value(&mut self) -> Result<Value, EvaluatorError> {
+        self.values
+            .pop()
+            .ok_or_else(|| EvaluatorError::internal_error("Value stack is empty"))
+    }
+
+    /// Pops the top `count` values, in the order they were pushed.
+    fn pop_values(&mut self, count: usize) -> Result<Vec<Value>, EvaluatorError> {
+        match self.values.len().checked_sub(count) {
+            Some(start) => Ok(self.values.split_off(start)),
+            None => Err(EvaluatorError::internal_error("Value stack is empty")),
+        }
+    }
+
+    /// The calls to user defined functions in progress, outermost first
+    fn backtrace(&self) -> Vec<Frame> {
+        let mut frames = Vec::new();
+        let mut caller_name = "<program>";
+        for task in &self.tasks {
+            if let Task::FinishCall {
+                function,
+                call_site,
+                tail_caller,
+                ..
+            } = task
+            {
+                if let Some(tail_caller) = tail_caller {
+                    caller_name = function_name(tail_caller);
+                }
+                frames.push(Frame::new(function_name(function), caller_name, *call_site));
+                caller_name = function_name(function);
+            }
+        }
+        frames
+    }
+}
+
+fn function_name(function: &Function) -> &str {
+    function.name.as_deref().unwrap_or("<anonymous>")
+}
+
+fn new_function(
+    function_literal: &FunctionLiteral,
+    name: Option<String>,
+    env: Environment,
+) -> Function {
+    // Functions capture a handle to the scope they're defined in, rather than a copy of it
+    Function::new(
+        name,
+        function_literal.parameters.clone(),
+        function_literal.body.clone(),
+        env,
+    )
+}
+
+/// Binds the functions defined by a `let rec` statement. Each can call itself and the others wherever
+/// it's passed, even if the names they're bound to in `env` are later shadowed or assigned to, since
+/// they capture immutable bindings of their own. With `let rec mut`, those are shadowed by the
+/// mutable bindings the rest of the program sees.
+fn bind_recursive_functions(
+    let_statement: &LetStatement,
+    mut env: Environment,
+) -> Result<(), EvaluatorError> {
+    let captured = env.capture_with_next_bindings(let_statement.bindings().count());
+    let mut functions = Vec::new();
+    for (pattern, value) in let_statement.bindings() {
+        let (Some(name), Some(function_literal)) = (
+            pattern.as_any().downcast_ref::<Identifier>(),
+            value.as_any().downcast_ref::<FunctionLiteral>(),
+        ) else {
+            return Err(EvaluatorError::internal_error(
+                "let rec should only bind function literals to names",
+            ));
+        };
+        let function = Value::Function(Rc::new(new_function(
+            function_literal,
+            Some(name.value.clone()),
+            captured.clone(),
+        )));
+        functions.push((name, function));
+    }
+    for (name, function) in &functions {
+        env.insert(name, function.clone());
+    }
+    if let_statement.mutable {
+        for (name, function) in functions {
+            env.insert_mutable(name, function);
+        }
+    }
+    Ok(())
+}
@@ -1,43 +1,52 @@
+mod compare;
 pub mod environment;
+mod error;
+mod machine;
+mod numeric;
+mod pattern;
+mod sequence;
 
 mod tests;
 
-use std::any::Any;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::rc::Rc;
 
+use num_bigint::BigInt;
+use typed_arena::Arena;
+
 use crate::ast::{
-    ArrayExpression, BlockStatement, BooleanLiteral, CallExpression, Expression,
-    ExpressionStatement, FunctionLiteral, Identifier, IfExpression, IndexExpression,
-    InfixExpression, IntegerLiteral, LetStatement, Node, NullLiteral, PrefixExpression, Program,
-    ReturnStatement, Statement, StringExpression,
+    ForStatement, IndexExpression, InfixExpression, MemberExpression, Node, PrefixExpression,
+    SliceExpression, WithExpression,
 };
 use crate::evaluator::environment::Environment;
-use crate::object::{
-    Array, Boolean, BuiltinFn, Function, Integer, Null, Object, ReturnValue, SelfRef, StringObject,
-};
+use crate::object::{BuiltinFunction, HashKey, Record, Value};
 
-#[derive(Debug)]
-pub struct EvaluatorError {
-    pub error_message: String,
-}
+use compare::{compare_values, orderable_kind, values_equal};
+pub use error::{EvaluatorError, EvaluatorErrorKind, Frame};
+use machine::Machine;
+use numeric::{Number, eval_bitwise_infix, eval_numeric_infix, float_to_integer};
+use sequence::{index_array, index_string, index_value, repeat_string, slice_range};
 
-impl EvaluatorError {
-    fn new(error_message: &str) -> Self {
-        Self {
-            error_message: error_message.to_string(),
-        }
-    }
-}
+/// The default limit on how deeply calls to user defined functions can nest. Calls in tail
+/// position don't count towards it.
+pub const DEFAULT_MAX_DEPTH: usize = 100_000;
 
 pub struct Evaluator {
-    builtin_fns: HashMap<String, Box<dyn Object>>,
-    self_fn: Option<Box<Function>>,
+    builtin_fns: HashMap<String, BuiltinFunction>,
+    // Calls nested more deeply than this raise a StackOverflow error
+    max_depth: usize,
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Evaluator {
     pub fn new() -> Self {
-        let mut builtin_fns: HashMap<String, Box<dyn Object>> = HashMap::new();
+        let mut builtin_fns: HashMap<String, BuiltinFunction> = HashMap::new();
 
         // Define builtin functions here.
         // Monkey Lang supports the following builtin functions:
@@ -46,505 +55,550 @@ impl Evaluator {
         // - println: Prints the input argument to stdout, then prints newline character
         // - push: Expects an array and an object. Returns a new array with the object appended to
         // the end
+        // - tail: Returns a new array with every item of the input array except the first
+        // - int: Converts a number or numeric string to an integer, truncating floats towards zero
+        // - float: Converts a number or numeric string to a float
+        // - floor, ceil, round: Rounds a number down, up, or to the nearest integer (halfway cases
+        // away from zero), returning an integer
         builtin_fns.insert(
             "len".to_string(),
-            Box::new(BuiltinFn::new(Rc::new(|args| {
+            Rc::new(|args| {
                 if args.len() != 1 {
-                    Err(EvaluatorError::new("len expects exactly one argument"))
-                } else if let Some(array_expression) = args[0].as_any().downcast_ref::<Array>() {
-                    Ok(Box::new(Integer::new(array_expression.items.len() as i64)))
+                    Err(EvaluatorError::arity_error(1, args.len()))
                 } else {
-                    Err(EvaluatorError::new(
-                        "Builtin function len expects array argument",
-                    ))
+                    match &args[0] {
+                        Value::Array(items) => Ok(Value::Integer(items.len() as i64)),
+                        // Strings are measured in Unicode scalar values, to agree with indexing
+                        Value::String(value) => Ok(Value::Integer(value.chars().count() as i64)),
+                        value => Err(EvaluatorError::type_error("array or string", value)),
+                    }
                 }
-            }))),
+            }),
         );
         builtin_fns.insert(
             "print".to_string(),
-            Box::new(BuiltinFn::new(Rc::new(|args| {
+            Rc::new(|args| {
                 if args.len() != 1 {
-                    Err(EvaluatorError::new("print expects exactly one argument"))
+                    Err(EvaluatorError::arity_error(1, args.len()))
                 } else {
-                    print!("{}", args[0].inspect());
-                    Ok(Box::new(Null::new()))
+                    print!("{}", args[0].display());
+                    Ok(Value::Null)
                 }
-            }))),
+            }),
         );
         builtin_fns.insert(
             "println".to_string(),
-            Box::new(BuiltinFn::new(Rc::new(|args| {
+            Rc::new(|args| {
                 if args.len() != 1 {
-                    Err(EvaluatorError::new("print expects exactly one argument"))
+                    Err(EvaluatorError::arity_error(1, args.len()))
                 } else {
-                    println!("{}", args[0].inspect());
-                    Ok(Box::new(Null::new()))
+                    println!("{}", args[0].display());
+                    Ok(Value::Null)
                 }
-            }))),
+            }),
         );
         builtin_fns.insert(
             "push".to_string(),
-            Box::new(BuiltinFn::new(Rc::new(|args| {
+            Rc::new(|args| {
                 if args.len() != 2 {
-                    Err(EvaluatorError::new("push expects exactly two arguments"))
-                } else if let Some(array_expression) = args[0].as_any().downcast_ref::<Array>() {
-                    let mut new_array_items = array_expression.items.clone();
-                    new_array_items.push(args[1].clone());
-                    Ok(Box::new(Array::new(new_array_items)))
+                    Err(EvaluatorError::arity_error(2, args.len()))
+                } else if let Value::Array(items) = &args[0] {
+                    let mut new_array_items = items.as_ref().clone();
+                    new_array_items.push_back(args[1].clone());
+                    Ok(Value::array(new_array_items))
                 } else {
-                    Err(EvaluatorError::new(
-                        "push expects the first argument to be an array",
-                    ))
+                    Err(EvaluatorError::type_error("array", &args[0]))
                 }
-            }))),
+            }),
         );
         builtin_fns.insert(
             "tail".to_string(),
-            Box::new(BuiltinFn::new(Rc::new(|args| {
+            Rc::new(|args| {
                 if args.len() != 1 {
-                    Err(EvaluatorError::new("tail expects exactly one arguments"))
-                } else if let Some(array_expression) = args[0].as_any().downcast_ref::<Array>() {
-                    // Very inefficient since removing first element from Vec is O(n)
-                    let mut new_array_items = array_expression.items.clone();
-                    if new_array_items.is_empty() {
-                        Err(EvaluatorError::new("tail expects on a non-empty list"))
+                    Err(EvaluatorError::arity_error(1, args.len()))
+                } else if let Value::Array(items) = &args[0] {
+                    if items.is_empty() {
+                        Err(EvaluatorError::value_error(
+                            "tail expects a non-empty array",
+                        ))
                     } else {
-                        new_array_items.remove(0);
-                        Ok(Box::new(Array::new(new_array_items)))
+                        Ok(Value::array(items.skip(1)))
                     }
                 } else {
-                    Err(EvaluatorError::new(
-                        "tail expects the argument to be an array",
-                    ))
+                    Err(EvaluatorError::type_error("array", &args[0]))
                 }
-            }))),
+            }),
         );
-        Self {
-            builtin_fns,
-            self_fn: None,
-        }
-    }
-
-    pub fn eval<T: Node + ?Sized>(
-        &mut self,
-        node: &T,
-        env: &mut Environment,
-    ) -> Result<Box<dyn Object>, EvaluatorError> {
-        if let Some(program) = node.as_any().downcast_ref::<Program>() {
-            self.eval_block_statement(&program.statements, env, true)
-        } else if let Some(statement) = node.as_any().downcast_ref::<ExpressionStatement>() {
-            self.eval(statement.expression.as_ref(), env)
-        } else if let Some(integer_literal) = node.as_any().downcast_ref::<IntegerLiteral>() {
-            Ok(Box::new(Integer::new(integer_literal.value)))
-        } else if let Some(boolean_literal) = node.as_any().downcast_ref::<BooleanLiteral>() {
-            Ok(Box::new(Boolean::new(boolean_literal.value)))
-        } else if node.as_any().is::<NullLiteral>() {
-            Ok(Box::new(Null::new()))
-        } else if let Some(function_literal) = node.as_any().downcast_ref::<FunctionLiteral>() {
-            let function_env = Environment::new_wrapped(env);
-            Ok(Box::new(Function::new(
-                &function_literal.parameters,
-                function_literal.body.clone(),
-                function_env,
-            )))
-        } else if let Some(identifier) = node.as_any().downcast_ref::<Identifier>() {
-            match env.get(&identifier.value) {
-                Some(value) => Ok(value.clone()),
-                None => match self.builtin_fns.get(&identifier.value) {
-                    Some(value) => Ok(value.clone()),
-                    None => Err(EvaluatorError::new(&format!(
-                        "Unknown identifier found: {}",
-                        identifier.value
-                    ))),
-                },
-            }
-        } else if let Some(index_expression) = node.as_any().downcast_ref::<IndexExpression>() {
-            self.eval_index_expression(index_expression, env)
-        } else if let Some(call_expression) = node.as_any().downcast_ref::<CallExpression>() {
-            self.eval_call_expression(call_expression, env)
-        } else if let Some(prefix_expression) = node.as_any().downcast_ref::<PrefixExpression>() {
-            self.eval_prefix_expression(prefix_expression, env)
-        } else if let Some(infix_expression) = node.as_any().downcast_ref::<InfixExpression>() {
-            self.eval_infix_expression(infix_expression, env)
-        } else if let Some(if_expression) = node.as_any().downcast_ref::<IfExpression>() {
-            self.eval_if_expression(if_expression, env)
-        } else if let Some(array_expression) = node.as_any().downcast_ref::<ArrayExpression>() {
-            self.eval_array_expression(array_expression, env)
-        } else if let Some(string_expression) = node.as_any().downcast_ref::<StringExpression>() {
-            Ok(Box::new(StringObject::new(string_expression.value.clone())))
-        } else if let Some(block_statement) = node.as_any().downcast_ref::<BlockStatement>() {
-            let mut wrapped_env = Environment::new_wrapped(env);
-            self.eval_block_statement(&block_statement.statements, &mut wrapped_env, false)
-        } else if let Some(return_statement) = node.as_any().downcast_ref::<ReturnStatement>() {
-            self.eval_return_statement(return_statement, env)
-        } else if let Some(let_statement) = node.as_any().downcast_ref::<LetStatement>() {
-            self.eval_let_statement(let_statement, env)
-        } else {
-            Err(EvaluatorError::new(
-                "Evaluator encountered unknown AST type",
-            ))
-        }
-    }
-
-    fn eval_block_statement(
-        &mut self,
-        statements: &[Box<dyn Statement>],
-        env: &mut Environment,
-        unwrap_return_value: bool,
-    ) -> Result<Box<dyn Object>, EvaluatorError> {
-        let mut ret: Box<dyn Object> = Box::new(Integer::new(69));
-        for statement in statements {
-            ret = self.eval(statement.as_ref(), env)?;
-            if ret.as_any().is::<ReturnValue>() {
-                if unwrap_return_value {
-                    let ret: Box<dyn Any> = ret;
-                    let return_value = ret
-                        .downcast::<ReturnValue>()
-                        .expect("Object should be ReturnValue");
-                    let value = return_value.value;
-                    return Ok(value);
+        builtin_fns.insert(
+            "int".to_string(),
+            Rc::new(|args| {
+                if args.len() != 1 {
+                    Err(EvaluatorError::arity_error(1, args.len()))
+                } else if let Some(number) = Number::from_value(&args[0]) {
+                    match number {
+                        Number::Float(value) => Ok(float_to_integer(value)?.into_value()),
+                        integer => Ok(integer.into_value()),
+                    }
+                } else if let Value::String(string) = &args[0] {
+                    match string.trim().parse::<BigInt>() {
+                        Ok(value) => Ok(Number::from_bigint(value).into_value()),
+                        Err(_) => Err(EvaluatorError::value_error(&format!(
+                            "Cannot convert {} to an integer",
+                            args[0].inspect()
+                        ))),
+                    }
                 } else {
-                    return Ok(ret);
+                    Err(EvaluatorError::type_error("number or string", &args[0]))
                 }
-            }
-        }
-        Ok(ret)
-    }
-
-    fn eval_index_expression(
-        &mut self,
-        index_expression: &IndexExpression,
-        env: &mut Environment,
-    ) -> Result<Box<dyn Object>, EvaluatorError> {
-        let index = self.eval(index_expression.index.as_ref(), env)?;
-        if let Some(index) = index.as_any().downcast_ref::<Integer>() {
-            let collection = self.eval(index_expression.collection.as_ref(), env)?;
-            if let Some(collection) = collection.as_any().downcast_ref::<Array>() {
-                let index = index.value as usize;
-                if index >= collection.items.len() {
-                    Err(EvaluatorError::new(&format!(
-                        "Out of bounds array access. Index is {} but array length is {}",
-                        index,
-                        collection.items.len()
-                    )))
+            }),
+        );
+        builtin_fns.insert(
+            "float".to_string(),
+            Rc::new(|args| {
+                if args.len() != 1 {
+                    Err(EvaluatorError::arity_error(1, args.len()))
+                } else if let Some(number) = Number::from_value(&args[0]) {
+                    Ok(Value::Float(number.as_f64()))
+                } else if let Value::String(string) = &args[0] {
+                    match string.trim().parse::<f64>() {
+                        Ok(value) => Ok(Value::Float(value)),
+                        Err(_) => Err(EvaluatorError::value_error(&format!(
+                            "Cannot convert {} to a float",
+                            args[0].inspect()
+                        ))),
+                    }
                 } else {
-                    Ok(collection.items[index].clone())
+                    Err(EvaluatorError::type_error("number or string", &args[0]))
                 }
-            } else {
-                Err(EvaluatorError::new(
-                    "Expected collection to be an array when the index is an integer literal",
-                ))
-            }
-        } else {
-            Err(EvaluatorError::new(
-                "Expected index to be an integer literal",
-            ))
-        }
-    }
-
-    fn eval_call_expression(
-        &mut self,
-        call_expression: &CallExpression,
-        env: &mut Environment,
-    ) -> Result<Box<dyn Object>, EvaluatorError> {
-        let mut arguments = Vec::new();
-        // Evaluate the call expression arguments. If any of them return an error, immediately
-        // return from this function with that error.
-        for arg in &call_expression.arguments {
-            match self.eval(arg.as_ref(), env) {
-                Ok(arg) => arguments.push(arg),
-                Err(e) => return Err(e),
-            }
-        }
-        if let Some(function_literal) = call_expression
-            .function
-            .as_any()
-            .downcast_ref::<FunctionLiteral>()
-        {
-            let function: Box<dyn Any> = self.eval(function_literal, env)?;
-            if let Ok(function) = function.downcast::<Function>() {
-                self.apply_function(function, arguments)
-            } else {
-                Err(EvaluatorError::new(
-                    "Expected function literal to evaluate to function",
-                ))
-            }
-        } else if let Some(identifier) = call_expression
-            .function
-            .as_any()
-            .downcast_ref::<Identifier>()
-        {
-            if let Some(value) = env.get(&identifier.value) {
-                let value: Box<dyn Any> = value.clone();
-                if value.is::<SelfRef>() {
-                    if let Some(self_fn) = self.self_fn.clone() {
-                        self.apply_function(self_fn, arguments)
-                    } else {
-                        Err(EvaluatorError::new(
-                            "Expected self_fn to be Some when evaluating recursive function",
-                        ))
-                    }
-                } else if let Ok(function) = value.downcast::<Function>() {
-                    let temp = self.self_fn.clone();
-                    self.self_fn = Some(function.clone());
-                    let ret = self.apply_function(function, arguments);
-                    self.self_fn = temp;
-                    ret
+            }),
+        );
+        builtin_fns.insert(
+            "keys".to_string(),
+            Rc::new(|args| {
+                if args.len() != 1 {
+                    Err(EvaluatorError::arity_error(1, args.len()))
+                } else if let Value::Hash(pairs) = &args[0] {
+                    Ok(Value::array(pairs.keys().map(HashKey::to_value).collect()))
+                } else {
+                    Err(EvaluatorError::type_error("hash", &args[0]))
+                }
+            }),
+        );
+        builtin_fns.insert(
+            "values".to_string(),
+            Rc::new(|args| {
+                if args.len() != 1 {
+                    Err(EvaluatorError::arity_error(1, args.len()))
+                } else if let Value::Hash(pairs) = &args[0] {
+                    Ok(Value::array(pairs.values().cloned().collect()))
                 } else {
-                    Err(EvaluatorError::new(&format!(
-                        "Expected function literal in call expression. {} is not a function literal",
-                        identifier.value
-                    )))
+                    Err(EvaluatorError::type_error("hash", &args[0]))
                 }
-            } else if let Some(value) = self.builtin_fns.get(&identifier.value) {
-                // Check for builtin functions here
-                let value: Box<dyn Any> = value.clone();
-                if let Ok(builtin_fn) = value.downcast::<BuiltinFn>() {
-                    let builtin_fn = builtin_fn.builtin_fn;
-                    builtin_fn(arguments)
+            }),
+        );
+        builtin_fns.insert(
+            "has".to_string(),
+            Rc::new(|args| {
+                if args.len() != 2 {
+                    Err(EvaluatorError::arity_error(2, args.len()))
+                } else if let Value::Hash(pairs) = &args[0] {
+                    let key = hash_key(&args[1])?;
+                    Ok(Value::Boolean(pairs.contains_key(&key)))
+                } else {
+                    Err(EvaluatorError::type_error("hash", &args[0]))
+                }
+            }),
+        );
+        // put and remove return a new hash, leaving the original unchanged
+        builtin_fns.insert(
+            "put".to_string(),
+            Rc::new(|args| {
+                if args.len() != 3 {
+                    Err(EvaluatorError::arity_error(3, args.len()))
+                } else if let Value::Hash(pairs) = &args[0] {
+                    let key = hash_key(&args[1])?;
+                    let mut new_pairs = pairs.as_ref().clone();
+                    new_pairs.insert(key, args[2].clone());
+                    Ok(Value::hash(new_pairs))
                 } else {
-                    Err(EvaluatorError::new(
-                        "Unable to downcast to builtin function",
-                    ))
+                    Err(EvaluatorError::type_error("hash", &args[0]))
+                }
+            }),
+        );
+        builtin_fns.insert(
+            "remove".to_string(),
+            Rc::new(|args| {
+                if args.len() != 2 {
+                    Err(EvaluatorError::arity_error(2, args.len()))
+                } else if let Value::Hash(pairs) = &args[0] {
+                    let key = hash_key(&args[1])?;
+                    let mut new_pairs = pairs.as_ref().clone();
+                    // Shifting keeps the remaining keys in insertion order
+                    new_pairs.shift_remove(&key);
+                    Ok(Value::hash(new_pairs))
+                } else {
+                    Err(EvaluatorError::type_error("hash", &args[0]))
+                }
+            }),
+        );
+        builtin_fns.insert(
+            "range".to_string(),
+            Rc::new(|args| {
+                // range(end) counts from 0, and range(start, end) counts from start
+                if args.is_empty() || args.len() > 2 {
+                    return Err(EvaluatorError::arity_range_error(1, 2, args.len()));
                 }
-            } else {
-                Err(EvaluatorError::new(&format!(
-                    "Unknown identifier: {}",
-                    identifier.value
-                )))
-            }
-        } else {
-            Err(EvaluatorError::new(
-                "Expected function literal or identifier in call expression",
-            ))
+                let mut bounds = Vec::new();
+                for arg in &args {
+                    match arg {
+                        Value::Integer(value) => bounds.push(*value),
+                        Value::BigInteger(value) => {
+                            return Err(EvaluatorError::value_error(&format!(
+                                "Range bound {value} is too large"
+                            )));
+                        }
+                        _ => return Err(EvaluatorError::type_error("integer", arg)),
+                    }
+                }
+                let (start, end) = if bounds.len() == 1 {
+                    (0, bounds[0])
+                } else {
+                    (bounds[0], bounds[1])
+                };
+                Ok(Value::Range { start, end })
+            }),
+        );
+        let rounding_fns = [
+            ("floor", f64::floor as fn(f64) -> f64),
+            ("ceil", f64::ceil),
+            ("round", f64::round),
+        ];
+        for (name, rounding_fn) in rounding_fns {
+            builtin_fns.insert(
+                name.to_string(),
+                Rc::new(move |args| {
+                    if args.len() != 1 {
+                        return Err(EvaluatorError::arity_error(1, args.len()));
+                    }
+                    match Number::from_value(&args[0]) {
+                        Some(Number::Float(value)) => {
+                            Ok(float_to_integer(rounding_fn(value))?.into_value())
+                        }
+                        Some(integer) => Ok(integer.into_value()),
+                        None => Err(EvaluatorError::type_error("number", &args[0])),
+                    }
+                }),
+            );
+        }
+        Self {
+            builtin_fns,
+            max_depth: DEFAULT_MAX_DEPTH,
         }
     }
 
-    fn apply_function(
-        &mut self,
-        mut function: Box<Function>,
-        arguments: Vec<Box<dyn Object>>,
-    ) -> Result<Box<dyn Object>, EvaluatorError> {
-        if function.parameters.len() != arguments.len() {
-            return Err(EvaluatorError::new(&format!(
-                "Mismatched number of parameters in call expression: {} != {}",
-                function.parameters.len(),
-                arguments.len()
-            )));
-        }
-        function
-            .parameters
-            .iter()
-            .zip(arguments)
-            .for_each(|(param, arg)| {
-                function.env.insert(param, arg);
-            });
-        self.eval_block_statement(&function.body.statements, &mut function.env, true)
+    /// Sets the limit on how deeply calls to user defined functions can nest.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
     }
 
-    fn eval_prefix_expression(
+    /// Evaluates a node in `env`. Evaluation doesn't recurse on the native stack, so deeply
+    /// nested calls fail with a StackOverflow error once they pass the depth limit, rather than
+    /// crashing the process.
+    pub fn eval(
         &mut self,
-        prefix_expression: &PrefixExpression,
+        node: &dyn Node,
         env: &mut Environment,
-    ) -> Result<Box<dyn Object>, EvaluatorError> {
-        match prefix_expression.operator.as_ref() {
-            "!" => self.eval_bang_expression(prefix_expression.right.as_ref(), env),
-            "-" => self.eval_minus_expression(prefix_expression.right.as_ref(), env),
-            _ => Err(EvaluatorError::new("Unknown operator in prefix expression")),
-        }
+    ) -> Result<Value, EvaluatorError> {
+        let bodies = Arena::new();
+        Machine::new(&self.builtin_fns, self.max_depth, &bodies).run(node, env.clone())
     }
+}
 
-    fn eval_infix_expression(
-        &mut self,
-        infix_expression: &InfixExpression,
-        env: &mut Environment,
-    ) -> Result<Box<dyn Object>, EvaluatorError> {
-        match infix_expression.operator.as_ref() {
-            "+" | "-" | "*" | "/" | ">=" | "<=" | ">" | "<" => {
-                self.eval_integer_infix_expression(infix_expression, env)
-            }
-            "==" | "!=" => self.eval_equality_infix_expression(infix_expression, env),
-            _ => Err(EvaluatorError::new("Unknown operator in infix expression")),
-        }
+fn apply_prefix(
+    prefix_expression: &PrefixExpression,
+    right: Value,
+) -> Result<Value, EvaluatorError> {
+    match prefix_expression.operator.as_ref() {
+        "!" => match right {
+            Value::Boolean(value) => Ok(Value::Boolean(!value)),
+            right => Err(EvaluatorError::type_error("boolean", &right)),
+        },
+        "-" => match Number::from_value(&right) {
+            Some(number) => Ok(number.negate().into_value()),
+            None => Err(EvaluatorError::type_error("number", &right)),
+        },
+        _ => Err(EvaluatorError::internal_error(
+            "Unknown operator in prefix expression",
+        )),
     }
+}
 
-    fn eval_integer_infix_expression(
-        &mut self,
-        infix_expression: &InfixExpression,
-        env: &mut Environment,
-    ) -> Result<Box<dyn Object>, EvaluatorError> {
-        let left = self.eval(infix_expression.left.as_ref(), env)?;
-        let right = self.eval(infix_expression.right.as_ref(), env)?;
-        if let Some(left) = left.as_any().downcast_ref::<Integer>()
-            && let Some(right) = right.as_any().downcast_ref::<Integer>()
-        {
-            match infix_expression.operator.as_ref() {
-                "+" => Ok(Box::new(Integer::new(left.value + right.value))),
-                "-" => Ok(Box::new(Integer::new(left.value - right.value))),
-                "*" => Ok(Box::new(Integer::new(left.value * right.value))),
-                "/" => {
-                    if right.value == 0 {
-                        Err(EvaluatorError::new("Division by zero"))
-                    } else {
-                        Ok(Box::new(Integer::new(left.value / right.value)))
-                    }
-                }
-                ">" => Ok(Box::new(Boolean::new(left.value > right.value))),
-                "<" => Ok(Box::new(Boolean::new(left.value < right.value))),
-                ">=" => Ok(Box::new(Boolean::new(left.value >= right.value))),
-                "<=" => Ok(Box::new(Boolean::new(left.value <= right.value))),
-                _ => Err(EvaluatorError::new("Unknown integer infix operator")),
-            }
-        } else {
-            Err(EvaluatorError::new(
-                "Expected integer expressions in infix expression",
-            ))
-        }
+/// Applies any infix operator other than `&&` and `||`, which the machine evaluates itself so
+/// they can short circuit.
+fn apply_infix(
+    infix_expression: &InfixExpression,
+    left: Value,
+    right: Value,
+) -> Result<Value, EvaluatorError> {
+    match infix_expression.operator.as_ref() {
+        "+" | "-" | "*" | "/" | "%" | "**" => apply_numeric_infix(infix_expression, left, right),
+        ">=" | "<=" | ">" | "<" => apply_comparison_infix(infix_expression, left, right),
+        "&" | "|" | "^" | "<<" | ">>" => apply_bitwise_infix(infix_expression, left, right),
+        "==" | "!=" => apply_equality_infix(infix_expression, left, right),
+        _ => Err(EvaluatorError::internal_error(
+            "Unknown operator in infix expression",
+        )),
     }
+}
 
-    // Note: It is valid in the Monkey language to compare two expressions of different types. Two expressions of different types are
-    // always considered to be not equal.
-    fn eval_equality_infix_expression(
-        &mut self,
-        infix_expression: &InfixExpression,
-        env: &mut Environment,
-    ) -> Result<Box<dyn Object>, EvaluatorError> {
-        let left = self.eval(infix_expression.left.as_ref(), env)?;
-        let right = self.eval(infix_expression.right.as_ref(), env)?;
-        match infix_expression.operator.as_ref() {
-            "==" => {
-                if let (Some(left_int), Some(right_int)) = (
-                    left.as_any().downcast_ref::<Integer>(),
-                    right.as_any().downcast_ref::<Integer>(),
-                ) {
-                    Ok(Box::new(Boolean::new(left_int.value == right_int.value)))
-                } else if let (Some(left_bool), Some(right_bool)) = (
-                    left.as_any().downcast_ref::<Boolean>(),
-                    right.as_any().downcast_ref::<Boolean>(),
-                ) {
-                    Ok(Box::new(Boolean::new(left_bool.value == right_bool.value)))
-                } else if left.as_any().is::<Null>() && right.as_any().is::<Null>() {
-                    Ok(Box::new(Boolean::new(true)))
-                } else {
-                    Ok(Box::new(Boolean::new(false)))
-                }
-            }
-            "!=" => {
-                if let (Some(left_int), Some(right_int)) = (
-                    left.as_any().downcast_ref::<Integer>(),
-                    right.as_any().downcast_ref::<Integer>(),
-                ) {
-                    Ok(Box::new(Boolean::new(left_int.value != right_int.value)))
-                } else if let (Some(left_bool), Some(right_bool)) = (
-                    left.as_any().downcast_ref::<Boolean>(),
-                    right.as_any().downcast_ref::<Boolean>(),
-                ) {
-                    Ok(Box::new(Boolean::new(left_bool.value != right_bool.value)))
-                } else if left.as_any().is::<Null>() && right.as_any().is::<Null>() {
-                    Ok(Box::new(Boolean::new(false)))
-                } else {
-                    Ok(Box::new(Boolean::new(true)))
-                }
-            }
-            _ => Err(EvaluatorError::new("Unknown boolean infix operator")),
+fn apply_numeric_infix(
+    infix_expression: &InfixExpression,
+    left: Value,
+    right: Value,
+) -> Result<Value, EvaluatorError> {
+    match (infix_expression.operator.as_ref(), &left, &right) {
+        ("+", Value::String(left), Value::String(right)) => {
+            return Ok(Value::string(format!("{left}{right}")));
+        }
+        ("+", Value::String(_), _) => {
+            return Err(EvaluatorError::type_error("string", &right)
+                .with_span(infix_expression.right.span()));
+        }
+        ("+", Value::Array(left), Value::Array(right)) => {
+            let mut items = left.as_ref().clone();
+            items.append(right.as_ref().clone());
+            return Ok(Value::array(items));
+        }
+        ("+", Value::Array(_), _) => {
+            return Err(EvaluatorError::type_error("array", &right)
+                .with_span(infix_expression.right.span()));
+        }
+        ("*", Value::String(string), right) if !matches!(right, Value::String(_)) => {
+            return repeat_string(string, right)
+                .map_err(|error| error.with_span(infix_expression.right.span()));
+        }
+        ("*", left, Value::String(string)) if !matches!(left, Value::String(_)) => {
+            return repeat_string(string, left)
+                .map_err(|error| error.with_span(infix_expression.left.span()));
         }
+        _ => {}
     }
+    let Some(left) = Number::from_value(&left) else {
+        return Err(
+            EvaluatorError::type_error("number", &left).with_span(infix_expression.left.span())
+        );
+    };
+    let Some(right) = Number::from_value(&right) else {
+        return Err(
+            EvaluatorError::type_error("number", &right).with_span(infix_expression.right.span())
+        );
+    };
+    eval_numeric_infix(&infix_expression.operator, left, right)
+}
 
-    fn eval_return_statement(
-        &mut self,
-        return_statement: &ReturnStatement,
-        env: &mut Environment,
-    ) -> Result<Box<dyn Object>, EvaluatorError> {
-        let expression = self.eval(return_statement.return_value.as_ref(), env)?;
-        Ok(Box::new(ReturnValue::new(expression)))
+fn apply_bitwise_infix(
+    infix_expression: &InfixExpression,
+    left: Value,
+    right: Value,
+) -> Result<Value, EvaluatorError> {
+    let as_integer = |value: &Value, span| match Number::from_value(value) {
+        Some(Number::Float(_)) | None => {
+            Err(EvaluatorError::type_error("integer", value).with_span(span))
+        }
+        Some(number) => Ok(number),
+    };
+    let left = as_integer(&left, infix_expression.left.span())?;
+    let right = as_integer(&right, infix_expression.right.span())?;
+    eval_bitwise_infix(&infix_expression.operator, left, right)
+}
+
+// Note: It is valid in the Monkey language to compare two expressions of different types. Two expressions of different types are
+// always considered to be not equal, except for integers and floats, which are compared by value. Arrays are compared item by item.
+fn apply_equality_infix(
+    infix_expression: &InfixExpression,
+    left: Value,
+    right: Value,
+) -> Result<Value, EvaluatorError> {
+    let equal = values_equal(&left, &right);
+    match infix_expression.operator.as_ref() {
+        "==" => Ok(Value::Boolean(equal)),
+        "!=" => Ok(Value::Boolean(!equal)),
+        _ => Err(EvaluatorError::internal_error(
+            "Unknown boolean infix operator",
+        )),
     }
+}
 
-    fn eval_let_statement(
-        &mut self,
-        let_statement: &LetStatement,
-        env: &mut Environment,
-    ) -> Result<Box<dyn Object>, EvaluatorError> {
-        if let_statement.rec {
-            env.insert(&let_statement.name, Box::new(SelfRef::new()));
-            let value = self.eval(let_statement.value.as_ref(), env)?;
-            env.insert(&let_statement.name, value);
-            Ok(Box::new(Null::new()))
-        } else {
-            let value = self.eval(let_statement.value.as_ref(), env)?;
-            env.insert(&let_statement.name, value);
-            Ok(Box::new(Null::new()))
+fn apply_comparison_infix(
+    infix_expression: &InfixExpression,
+    left: Value,
+    right: Value,
+) -> Result<Value, EvaluatorError> {
+    // Point type errors at the operand that can't be ordered
+    let span = match orderable_kind(&left) {
+        Some(_) => infix_expression.right.span(),
+        None => infix_expression.left.span(),
+    };
+    let ordering = compare_values(&left, &right).map_err(|error| error.with_span(span))?;
+    let result = match infix_expression.operator.as_ref() {
+        ">" => ordering.is_some_and(Ordering::is_gt),
+        "<" => ordering.is_some_and(Ordering::is_lt),
+        ">=" => ordering.is_some_and(Ordering::is_ge),
+        "<=" => ordering.is_some_and(Ordering::is_le),
+        _ => {
+            return Err(EvaluatorError::internal_error(
+                "Unknown comparison infix operator",
+            ));
         }
+    };
+    Ok(Value::Boolean(result))
+}
+
+fn index_collection(
+    index_expression: &IndexExpression,
+    collection: Value,
+    index: Value,
+) -> Result<Value, EvaluatorError> {
+    if let Value::Hash(pairs) = &collection {
+        let key =
+            hash_key(&index).map_err(|error| error.with_span(index_expression.index.span()))?;
+        return match pairs.get(&key) {
+            Some(value) => Ok(value.clone()),
+            None => Err(EvaluatorError::new(EvaluatorErrorKind::KeyError {
+                key: index.inspect(),
+            })),
+        };
     }
+    let index =
+        index_value(&index).map_err(|error| error.with_span(index_expression.index.span()))?;
+    match &collection {
+        Value::Array(items) => index_array(items, index),
+        Value::String(value) => index_string(value, index),
+        _ => Err(EvaluatorError::type_error(
+            "array, string or hash",
+            &collection,
+        )),
+    }
+}
 
-    fn eval_if_expression(
-        &mut self,
-        if_expression: &IfExpression,
-        env: &mut Environment,
-    ) -> Result<Box<dyn Object>, EvaluatorError> {
-        for cons in &if_expression.consequences {
-            let condition = self.eval(cons.0.as_ref(), env)?;
-            if is_truthy(condition.as_ref()) {
-                return self.eval(&cons.1, env);
-            }
-        }
-        if let Some(alternative) = if_expression.alternative.as_ref() {
-            self.eval(alternative, env)
-        } else {
-            // If the if_expression has no else branch and all the conditions are falsey, then it evaluates to null
-            Ok(Box::new(Null::new()))
+fn slice_collection(
+    slice_expression: &SliceExpression,
+    collection: Value,
+    start: Option<Value>,
+    end: Option<Value>,
+) -> Result<Value, EvaluatorError> {
+    let mut bounds = [None, None];
+    for ((bound, value), expression) in bounds
+        .iter_mut()
+        .zip([start, end])
+        .zip([&slice_expression.start, &slice_expression.end])
+    {
+        if let (Some(value), Some(expression)) = (value, expression) {
+            *bound = Some(index_value(&value).map_err(|error| error.with_span(expression.span()))?);
         }
     }
+    let [start, end] = bounds;
 
-    fn eval_array_expression(
-        &mut self,
-        array_expression: &ArrayExpression,
-        env: &mut Environment,
-    ) -> Result<Box<dyn Object>, EvaluatorError> {
-        let mut items = Vec::new();
-        for item in &array_expression.items {
-            let item_object = self.eval(item.as_ref(), env)?;
-            items.push(item_object);
+    match &collection {
+        Value::Array(items) => {
+            let (start, end) = slice_range(start, end, items.len());
+            Ok(Value::array(items.skip(start).take(end - start)))
         }
-        Ok(Box::new(Array::new(items)))
+        Value::String(value) => {
+            let (start, end) = slice_range(start, end, value.chars().count());
+            let value: String = value.chars().skip(start).take(end - start).collect();
+            Ok(Value::string(value))
+        }
+        _ => Err(EvaluatorError::type_error("array or string", &collection)
+            .with_span(slice_expression.collection.span())),
     }
+}
 
-    fn eval_bang_expression(
-        &mut self,
-        right: &dyn Expression,
-        env: &mut Environment,
-    ) -> Result<Box<dyn Object>, EvaluatorError> {
-        let right = self.eval(right, env)?;
-        if let Some(boolean) = right.as_any().downcast_ref::<Boolean>() {
-            Ok(Box::new(Boolean::new(!boolean.value)))
-        } else {
-            Err(EvaluatorError::new(
-                "Expected boolean expression after bang operator",
-            ))
-        }
+fn get_field(member_expression: &MemberExpression, record: Value) -> Result<Value, EvaluatorError> {
+    let Value::Record(record) = &record else {
+        return Err(EvaluatorError::type_error("record", &record)
+            .with_span(member_expression.record.span()));
+    };
+    let field = &member_expression.field;
+    record.get(&field.value).cloned().ok_or_else(|| {
+        EvaluatorError::new(EvaluatorErrorKind::FieldError {
+            struct_name: record.struct_type.name.clone(),
+            field: field.value.clone(),
+        })
+        .with_span(field.span)
+    })
+}
+
+/// Copies `record`, replacing the fields updated by the with expression with `values`
+fn update_record(
+    with_expression: &WithExpression,
+    record: Value,
+    values: Vec<Value>,
+) -> Result<Value, EvaluatorError> {
+    let Value::Record(record) = &record else {
+        return Err(
+            EvaluatorError::type_error("record", &record).with_span(with_expression.record.span())
+        );
+    };
+    let mut fields = record.values.clone();
+    for ((field, _), value) in with_expression.updates.iter().zip(values) {
+        let Some(index) = record.struct_type.field_index(&field.value) else {
+            return Err(EvaluatorError::new(EvaluatorErrorKind::FieldError {
+                struct_name: record.struct_type.name.clone(),
+                field: field.value.clone(),
+            })
+            .with_span(field.span));
+        };
+        fields[index] = value;
     }
+    Ok(Value::Record(Rc::new(Record::new(
+        record.struct_type.clone(),
+        fields,
+    ))))
+}
 
-    fn eval_minus_expression(
-        &mut self,
-        right: &dyn Expression,
-        env: &mut Environment,
-    ) -> Result<Box<dyn Object>, EvaluatorError> {
-        let right = self.eval(right, env)?;
-        if let Some(integer) = right.as_any().downcast_ref::<Integer>() {
-            Ok(Box::new(Integer::new(-integer.value)))
-        } else {
-            Err(EvaluatorError::new(
-                "Expected integer expression after minus operator",
-            ))
+/// The items a for loop visits when iterating over `iterable`.
+fn iterate(
+    for_statement: &ForStatement,
+    iterable: Value,
+) -> Result<Box<dyn Iterator<Item = Value>>, EvaluatorError> {
+    match &iterable {
+        // Cloning the persistent vector shares its structure rather than copying the items
+        Value::Array(items) => Ok(Box::new(items.as_ref().clone().into_iter())),
+        Value::String(value) => {
+            let characters: Vec<Value> = value
+                .chars()
+                .map(|c| Value::string(c.to_string()))
+                .collect();
+            Ok(Box::new(characters.into_iter()))
+        }
+        Value::Range { start, end } => Ok(Box::new((*start..*end).map(Value::Integer))),
+        Value::Hash(pairs) => {
+            // Iterating over a hash visits its keys in insertion order
+            let keys: Vec<Value> = pairs.keys().map(HashKey::to_value).collect();
+            Ok(Box::new(keys.into_iter()))
         }
+        _ => Err(
+            EvaluatorError::type_error("array, string, range or hash", &iterable)
+                .with_span(for_statement.iterable.span()),
+        ),
     }
 }
 
-fn is_truthy(expression: &dyn Object) -> bool {
-    if let Some(boolean) = expression.as_any().downcast_ref::<Boolean>() {
-        boolean.value
-    } else if let Some(integer) = expression.as_any().downcast_ref::<Integer>() {
-        integer.value != 0
-    } else {
-        false
+/// Converts a value to a hash key. Fails if the value can't be used as a key.
+fn hash_key(value: &Value) -> Result<HashKey, EvaluatorError> {
+    HashKey::from_value(value)
+        .ok_or_else(|| EvaluatorError::type_error("integer, string, boolean or null", value))
+}
+
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Boolean(value) => *value,
+        Value::Integer(value) => *value != 0,
+        // BigIntegers are never zero, since they only hold values outside the range of i64
+        Value::BigInteger(_) => true,
+        Value::Float(value) => *value != 0.0,
+        _ => false,
     }
 }
@@ -0,0 +1,269 @@
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+use num_bigint::BigInt;
+use num_traits::{FromPrimitive, ToPrimitive, Zero};
+
+use crate::evaluator::{EvaluatorError, EvaluatorErrorKind};
+use crate::object::Value;
+
+// The most bits the result of `<<` or `**` can have. Larger integers would take minutes to compute,
+// or fail to allocate and abort the interpreter.
+const MAX_INTEGER_BITS: u64 = 1 << 20;
+
+/// A numeric operand. Arithmetic between integers stays in integers, while arithmetic involving
+/// a float promotes both sides to floats. Integers are stored as i64 when they fit, and promoted
+/// to BigInt when an operation overflows.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Number {
+    Integer(i64),
+    // Only ever holds values outside the range of i64
+    BigInt(BigInt),
+    Float(f64),
+}
+
+impl Number {
+    /// Returns None if `value` isn't an integer or float.
+    pub fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Integer(value) => Some(Number::Integer(*value)),
+            Value::BigInteger(value) => Some(Number::BigInt(value.as_ref().clone())),
+            Value::Float(value) => Some(Number::Float(*value)),
+            _ => None,
+        }
+    }
+
+    /// Demotes `value` to an i64 if it fits.
+    pub fn from_bigint(value: BigInt) -> Self {
+        match value.to_i64() {
+            Some(value) => Number::Integer(value),
+            None => Number::BigInt(value),
+        }
+    }
+
+    pub fn into_value(self) -> Value {
+        match self {
+            Number::Integer(value) => Value::Integer(value),
+            Number::BigInt(value) => Value::BigInteger(Rc::new(value)),
+            Number::Float(value) => Value::Float(value),
+        }
+    }
+
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            Number::Integer(value) => *value as f64,
+            Number::BigInt(value) => value.to_f64().unwrap_or(f64::NAN),
+            Number::Float(value) => *value,
+        }
+    }
+
+    /// Returns None for floats.
+    fn to_bigint(&self) -> Option<BigInt> {
+        match self {
+            Number::Integer(value) => Some(BigInt::from(*value)),
+            Number::BigInt(value) => Some(value.clone()),
+            Number::Float(_) => None,
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        match self {
+            Number::Integer(value) => *value == 0,
+            Number::BigInt(value) => value.is_zero(),
+            Number::Float(value) => *value == 0.0,
+        }
+    }
+
+    /// Compares two numbers by value, so `1 == 1.0`. Returns None if either side is NaN.
+    pub fn compare(&self, other: &Number) -> Option<Ordering> {
+        match (self, other) {
+            (Number::Integer(left), Number::Integer(right)) => Some(left.cmp(right)),
+            (left, right) => match (left.to_bigint(), right.to_bigint()) {
+                (Some(left), Some(right)) => Some(left.cmp(&right)),
+                _ => left.as_f64().partial_cmp(&right.as_f64()),
+            },
+        }
+    }
+
+    pub fn negate(self) -> Number {
+        match self {
+            Number::Integer(value) => match value.checked_neg() {
+                Some(value) => Number::Integer(value),
+                None => Number::from_bigint(-BigInt::from(value)),
+            },
+            Number::BigInt(value) => Number::from_bigint(-value),
+            Number::Float(value) => Number::Float(-value),
+        }
+    }
+}
+
+/// Evaluates an arithmetic operator on two numbers. Dividing an integer by zero fails, while
+/// float arithmetic follows IEEE 754, so `1.0 / 0` is `inf` and `0.0 / 0` is `NaN`.
+pub fn eval_numeric_infix(
+    operator: &str,
+    left: Number,
+    right: Number,
+) -> Result<Value, EvaluatorError> {
+    let is_float = matches!(left, Number::Float(_)) || matches!(right, Number::Float(_));
+    if matches!(operator, "/" | "%") && right.is_zero() && !is_float {
+        return Err(EvaluatorError::new(EvaluatorErrorKind::ZeroDivision));
+    }
+    if operator == "**" {
+        return Ok(eval_power(left, right)?.into_value());
+    }
+    let result = match (&left, &right) {
+        (Number::Integer(left), Number::Integer(right)) => {
+            let checked = match operator {
+                "+" => left.checked_add(*right),
+                "-" => left.checked_sub(*right),
+                "*" => left.checked_mul(*right),
+                "/" => left.checked_div(*right),
+                "%" => left.checked_rem(*right),
+                _ => return Err(unknown_operator()),
+            };
+            match checked {
+                Some(value) => Number::Integer(value),
+                None => eval_bigint_infix(operator, BigInt::from(*left), BigInt::from(*right))?,
+            }
+        }
+        (Number::Float(_), _) | (_, Number::Float(_)) => {
+            let (left, right) = (left.as_f64(), right.as_f64());
+            match operator {
+                "+" => Number::Float(left + right),
+                "-" => Number::Float(left - right),
+                "*" => Number::Float(left * right),
+                "/" => Number::Float(left / right),
+                "%" => Number::Float(left % right),
+                _ => return Err(unknown_operator()),
+            }
+        }
+        (left, right) => match (left.to_bigint(), right.to_bigint()) {
+            (Some(left), Some(right)) => eval_bigint_infix(operator, left, right)?,
+            _ => return Err(unknown_operator()),
+        },
+    };
+    Ok(result.into_value())
+}
+
+fn eval_bigint_infix(
+    operator: &str,
+    left: BigInt,
+    right: BigInt,
+) -> Result<Number, EvaluatorError> {
+    let result = match operator {
+        "+" => left + right,
+        "-" => left - right,
+        "*" => left * right,
+        "/" => left / right,
+        "%" => left % right,
+        _ => return Err(unknown_operator()),
+    };
+    Ok(Number::from_bigint(result))
+}
+
+/// Raises `base` to the power of `exponent`. Integer powers stay integers unless the exponent is
+/// negative, in which case the result is a float. Raising integer zero to a negative power fails,
+/// while a float zero gives `inf`. Fails if an integer result would have more than
+/// `MAX_INTEGER_BITS` bits.
+fn eval_power(base: Number, exponent: Number) -> Result<Number, EvaluatorError> {
+    let (Some(base_int), Some(exponent_int)) = (base.to_bigint(), exponent.to_bigint()) else {
+        return Ok(Number::Float(base.as_f64().powf(exponent.as_f64())));
+    };
+    if exponent_int < BigInt::zero() {
+        if base_int.is_zero() {
+            return Err(EvaluatorError::new(EvaluatorErrorKind::ZeroDivision));
+        }
+        return Ok(Number::Float(base.as_f64().powf(exponent.as_f64())));
+    }
+    let Some(exponent) = exponent_int.to_u32() else {
+        return Err(EvaluatorError::value_error(&format!(
+            "Exponent {exponent_int} is too large"
+        )));
+    };
+    if let Number::Integer(base) = base
+        && let Some(value) = base.checked_pow(exponent)
+    {
+        return Ok(Number::Integer(value));
+    }
+    // The result has at most this many bits. Powers of 0, 1 and -1 stay small.
+    if base_int.bits() > 1 && base_int.bits() * u64::from(exponent) > MAX_INTEGER_BITS {
+        return Err(too_large("**"));
+    }
+    Ok(Number::from_bigint(base_int.pow(exponent)))
+}
+
+/// Evaluates a bitwise or shift operator. These are only defined for integers, so the caller
+/// checks that neither operand is a float. Fails if the result of `<<` would have more than
+/// `MAX_INTEGER_BITS` bits.
+pub fn eval_bitwise_infix(
+    operator: &str,
+    left: Number,
+    right: Number,
+) -> Result<Value, EvaluatorError> {
+    if let (Number::Integer(left), Number::Integer(right)) = (&left, &right) {
+        let result = match operator {
+            "&" => Some(left & right),
+            "|" => Some(left | right),
+            "^" => Some(left ^ right),
+            _ => None,
+        };
+        if let Some(result) = result {
+            return Ok(Number::Integer(result).into_value());
+        }
+    }
+
+    let (Some(left), Some(right)) = (left.to_bigint(), right.to_bigint()) else {
+        return Err(unknown_operator());
+    };
+    let result = match operator {
+        "&" => left & right,
+        "|" => left | right,
+        "^" => left ^ right,
+        "<<" | ">>" => {
+            if right < BigInt::zero() {
+                return Err(EvaluatorError::value_error(&format!(
+                    "Negative shift amount {right}"
+                )));
+            }
+            let Some(shift) = right.to_usize() else {
+                return Err(EvaluatorError::value_error(&format!(
+                    "Shift amount {right} is too large"
+                )));
+            };
+            if operator == "<<" {
+                if !left.is_zero() && left.bits().saturating_add(shift as u64) > MAX_INTEGER_BITS {
+                    return Err(too_large("<<"));
+                }
+                left << shift
+            } else {
+                left >> shift
+            }
+        }
+        _ => return Err(unknown_operator()),
+    };
+    Ok(Number::from_bigint(result).into_value())
+}
+
+/// Converts a float to an integer, truncating towards zero. Fails if the float is infinite or NaN.
+pub fn float_to_integer(value: f64) -> Result<Number, EvaluatorError> {
+    // i64::MAX as f64 rounds up to 2^63, which is out of range
+    if value >= i64::MIN as f64 && value < i64::MAX as f64 {
+        Ok(Number::Integer(value as i64))
+    } else {
+        BigInt::from_f64(value.trunc())
+            .map(Number::from_bigint)
+            .ok_or_else(|| {
+                EvaluatorError::value_error(&format!("Cannot convert {value:?} to an integer"))
+            })
+    }
+}
+
+fn too_large(operator: &str) -> EvaluatorError {
+    EvaluatorError::value_error(&format!(
+        "Result of `{operator}` would have more than {MAX_INTEGER_BITS} bits"
+    ))
+}
+
+fn unknown_operator() -> EvaluatorError {
+    EvaluatorError::internal_error("Unknown numeric infix operator")
+}
@@ -0,0 +1,216 @@
+use crate::ast::{
+    ArrayPattern, BigIntegerLiteral, BooleanLiteral, Expression, FloatLiteral, HashPattern,
+    Identifier, IntegerLiteral, LiteralPattern, NullLiteral, Pattern, PrefixExpression,
+    StringExpression, WildcardPattern,
+};
+use crate::evaluator::compare::values_equal;
+use crate::evaluator::environment::Environment;
+use crate::evaluator::{EvaluatorError, EvaluatorErrorKind, apply_prefix};
+use crate::object::{HashKey, Value};
+
+/// Why a value didn't match a pattern. Only turned into an error when the mismatch is reported,
+/// so match expressions can try one arm after another cheaply.
+enum Mismatch<'a> {
+    Type {
+        pattern: &'a dyn Pattern,
+        expected: &'static str,
+        found: Value,
+    },
+    Length {
+        pattern: &'a ArrayPattern,
+        found: usize,
+    },
+    MissingKey(&'a Identifier),
+    Literal {
+        pattern: &'a LiteralPattern,
+        expected: Value,
+        found: Value,
+    },
+    // Raised regardless of whether the value should have matched
+    Error(EvaluatorError),
+}
+
+impl Mismatch<'_> {
+    fn into_error(self) -> EvaluatorError {
+        match self {
+            Mismatch::Type {
+                pattern,
+                expected,
+                found,
+            } => EvaluatorError::type_error(expected, &found).with_span(pattern.span()),
+            Mismatch::Length { pattern, found } => {
+                let at_least = if pattern.rest.is_some() {
+                    "at least "
+                } else {
+                    ""
+                };
+                let expected = pattern.items.len();
+                let plural = |count: usize| if count == 1 { "" } else { "s" };
+                EvaluatorError::value_error(&format!(
+                    "Expected an array of {at_least}{expected} item{}, found {found} item{}",
+                    plural(expected),
+                    plural(found)
+                ))
+                .with_span(pattern.span)
+            }
+            Mismatch::MissingKey(key) => EvaluatorError::new(EvaluatorErrorKind::KeyError {
+                key: Value::string(key.value.as_str()).inspect(),
+            })
+            .with_span(key.span),
+            Mismatch::Literal {
+                pattern,
+                expected,
+                found,
+            } => EvaluatorError::value_error(&format!(
+                "Expected {}, found {}",
+                expected.inspect(),
+                found.inspect()
+            ))
+            .with_span(pattern.span),
+            Mismatch::Error(error) => error,
+        }
+    }
+}
+
+/// Binds the names in `pattern` to the matching parts of `value` in `env`. If the value doesn't
+/// have the shape the pattern expects, nothing is bound.
+pub fn bind_pattern(
+    pattern: &dyn Pattern,
+    value: Value,
+    env: &mut Environment,
+    mutable: bool,
+) -> Result<(), EvaluatorError> {
+    // A plain name always matches, so it's bound without collecting the bindings first
+    if let Some(identifier) = pattern.as_any().downcast_ref::<Identifier>() {
+        bind(env, identifier, value, mutable);
+        return Ok(());
+    }
+    let mut bindings = Vec::new();
+    destructure(pattern, value, &mut bindings).map_err(Mismatch::into_error)?;
+    for (name, value) in bindings {
+        bind(env, name, value, mutable);
+    }
+    Ok(())
+}
+
+fn bind(env: &mut Environment, name: &Identifier, value: Value, mutable: bool) {
+    if mutable {
+        env.insert_mutable(name, value);
+    } else {
+        env.insert(name, value);
+    }
+}
+
+/// Like `bind_pattern`, but returns false rather than an error if the value doesn't match
+pub fn match_pattern(
+    pattern: &dyn Pattern,
+    value: Value,
+    env: &mut Environment,
+) -> Result<bool, EvaluatorError> {
+    let mut bindings = Vec::new();
+    match destructure(pattern, value, &mut bindings) {
+        Ok(()) => {
+            for (name, value) in bindings {
+                bind(env, name, value, false);
+            }
+            Ok(true)
+        }
+        Err(Mismatch::Error(error)) => Err(error),
+        Err(_) => Ok(false),
+    }
+}
+
+/// Collects the names in `pattern` along with the values they should be bound to
+fn destructure<'a>(
+    pattern: &'a dyn Pattern,
+    value: Value,
+    bindings: &mut Vec<(&'a Identifier, Value)>,
+) -> Result<(), Mismatch<'a>> {
+    let any = pattern.as_any();
+    if let Some(identifier) = any.downcast_ref::<Identifier>() {
+        bindings.push((identifier, value));
+    } else if any.is::<WildcardPattern>() {
+        // Matches anything without binding it
+    } else if let Some(literal_pattern) = any.downcast_ref::<LiteralPattern>() {
+        let expected = literal_value(literal_pattern.value.as_ref()).map_err(Mismatch::Error)?;
+        if !values_equal(&expected, &value) {
+            return Err(Mismatch::Literal {
+                pattern: literal_pattern,
+                expected,
+                found: value,
+            });
+        }
+    } else if let Some(array_pattern) = any.downcast_ref::<ArrayPattern>() {
+        let Value::Array(items) = &value else {
+            return Err(Mismatch::Type {
+                pattern,
+                expected: "array",
+                found: value,
+            });
+        };
+        let expected = array_pattern.items.len();
+        let length_matches = match array_pattern.rest {
+            Some(_) => items.len() >= expected,
+            None => items.len() == expected,
+        };
+        if !length_matches {
+            return Err(Mismatch::Length {
+                pattern: array_pattern,
+                found: items.len(),
+            });
+        }
+        for (item_pattern, item) in array_pattern.items.iter().zip(items.iter()) {
+            destructure(item_pattern.as_ref(), item.clone(), bindings)?;
+        }
+        if let Some(rest) = &array_pattern.rest {
+            let rest_items = items.iter().skip(expected).cloned().collect();
+            bindings.push((rest, Value::array(rest_items)));
+        }
+    } else if let Some(hash_pattern) = any.downcast_ref::<HashPattern>() {
+        let Value::Hash(pairs) = &value else {
+            return Err(Mismatch::Type {
+                pattern,
+                expected: "hash",
+                found: value,
+            });
+        };
+        for (key, value_pattern) in &hash_pattern.pairs {
+            let Some(value) = pairs.get(&HashKey::String(key.value.as_str().into())) else {
+                return Err(Mismatch::MissingKey(key));
+            };
+            destructure(value_pattern.as_ref(), value.clone(), bindings)?;
+        }
+    } else {
+        return Err(Mismatch::Error(EvaluatorError::internal_error(&format!(
+            "Unknown pattern `{}`",
+            pattern.string()
+        ))));
+    }
+    Ok(())
+}
+
+/// The value of the literal in a literal pattern
+fn literal_value(literal: &dyn Expression) -> Result<Value, EvaluatorError> {
+    let any = literal.as_any();
+    if let Some(integer_literal) = any.downcast_ref::<IntegerLiteral>() {
+        Ok(Value::Integer(integer_literal.value))
+    } else if let Some(big_integer_literal) = any.downcast_ref::<BigIntegerLiteral>() {
+        Ok(Value::BigInteger(big_integer_literal.value.clone().into()))
+    } else if let Some(float_literal) = any.downcast_ref::<FloatLiteral>() {
+        Ok(Value::Float(float_literal.value))
+    } else if let Some(string_expression) = any.downcast_ref::<StringExpression>() {
+        Ok(Value::string(string_expression.value.as_str()))
+    } else if let Some(boolean_literal) = any.downcast_ref::<BooleanLiteral>() {
+        Ok(Value::Boolean(boolean_literal.value))
+    } else if any.is::<NullLiteral>() {
+        Ok(Value::Null)
+    } else if let Some(prefix_expression) = any.downcast_ref::<PrefixExpression>() {
+        let right = literal_value(prefix_expression.right.as_ref())?;
+        apply_prefix(prefix_expression, right)
+    } else {
+        Err(EvaluatorError::internal_error(&format!(
+            "Unknown literal `{}` in pattern",
+            literal.string()
+        )))
+    }
+}
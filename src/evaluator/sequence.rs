@@ -0,0 +1,98 @@
+use im_rc::Vector;
+
+use crate::evaluator::{EvaluatorError, EvaluatorErrorKind};
+use crate::object::Value;
+
+/// Converts an index or slice bound to an i64. Fails if it isn't an integer.
+pub fn index_value(index: &Value) -> Result<i64, EvaluatorError> {
+    match index {
+        Value::Integer(index) => Ok(*index),
+        Value::BigInteger(index) => Err(EvaluatorError::value_error(&format!(
+            "Index {index} is too large"
+        ))),
+        _ => Err(EvaluatorError::type_error("integer", index)),
+    }
+}
+
+/// Resolves an index into a collection with `length` items to a position in it. Negative indices
+/// count back from the end, so `-1` is the last item. Returns `None` if the index is out of bounds.
+pub fn resolve_index(index: i64, length: usize) -> Option<usize> {
+    let position = if index < 0 {
+        index.checked_add(length as i64)?
+    } else {
+        index
+    };
+    usize::try_from(position)
+        .ok()
+        .filter(|position| *position < length)
+}
+
+/// Returns the item at `index` in an array, counting back from the end for negative indices
+pub fn index_array(items: &Vector<Value>, index: i64) -> Result<Value, EvaluatorError> {
+    resolve_index(index, items.len())
+        .map(|position| items[position].clone())
+        .ok_or(EvaluatorError::new(EvaluatorErrorKind::IndexError {
+            index,
+            length: items.len(),
+        }))
+}
+
+/// Returns the character at `index` as a one character string. Strings are indexed by Unicode
+/// scalar value rather than by byte, so `"héllo"[1]` is `"é"`. Negative indices count back from
+/// the end of the string, so `-1` is the last character.
+pub fn index_string(value: &str, index: i64) -> Result<Value, EvaluatorError> {
+    let length = value.chars().count();
+    resolve_index(index, length)
+        .and_then(|position| value.chars().nth(position))
+        .map(|c| Value::string(c.to_string()))
+        .ok_or(EvaluatorError::new(EvaluatorErrorKind::IndexError {
+            index,
+            length,
+        }))
+}
+
+/// Resolves the bounds of a slice of a collection with `length` items into a range. A missing
+/// start or end defaults to the start or end of the collection, negative bounds count back from
+/// the end, and bounds outside the collection are clamped to it, so slicing never fails.
+pub fn slice_range(start: Option<i64>, end: Option<i64>, length: usize) -> (usize, usize) {
+    let resolve = |bound: i64| {
+        let bound = if bound < 0 {
+            bound.saturating_add(length as i64)
+        } else {
+            bound
+        };
+        bound.clamp(0, length as i64) as usize
+    };
+    let start = start.map(resolve).unwrap_or(0);
+    let end = end.map(resolve).unwrap_or(length);
+    (start, end.max(start))
+}
+
+// The longest string, in bytes, that repeating a string can create. Longer strings would take
+// minutes to build, or fail to allocate and abort the interpreter.
+const MAX_STRING_LENGTH: usize = 1 << 30;
+
+/// Repeats `value` `count` times, for `"ab" * 3`. Fails if the result would be longer than
+/// `MAX_STRING_LENGTH` bytes.
+pub fn repeat_string(value: &str, count: &Value) -> Result<Value, EvaluatorError> {
+    let count = match count {
+        Value::Integer(count) => *count,
+        Value::BigInteger(count) => {
+            return Err(EvaluatorError::value_error(&format!(
+                "Cannot repeat a string {count} times"
+            )));
+        }
+        _ => return Err(EvaluatorError::type_error("integer", count)),
+    };
+    let Ok(count) = usize::try_from(count) else {
+        return Err(EvaluatorError::value_error(&format!(
+            "Cannot repeat a string {count} times"
+        )));
+    };
+    match value.len().checked_mul(count) {
+        Some(length) if length <= MAX_STRING_LENGTH => Ok(Value::string(value.repeat(count))),
+        _ => Err(EvaluatorError::value_error(&format!(
+            "Repeating a string {count} times would make it longer than {MAX_STRING_LENGTH} bytes"
+        ))),
+    }
+}
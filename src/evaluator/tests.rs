@@ -1,13 +1,15 @@
 #[cfg(test)]
-use crate::evaluator::Evaluator;
-#[cfg(test)]
 use crate::evaluator::environment::Environment;
 #[cfg(test)]
+use crate::evaluator::{Evaluator, EvaluatorError, EvaluatorErrorKind, Frame};
+#[cfg(test)]
 use crate::lexer::Lexer;
 #[cfg(test)]
-use crate::object::{Array, Boolean, Integer, Null, Object};
+use crate::object::Value;
 #[cfg(test)]
 use crate::parser::Parser;
+#[cfg(test)]
+use crate::token::Span;
 
 #[test]
 fn integer_literal_evaluation() {
@@ -181,7 +183,8 @@ fn division_by_zero_error() {
     let result = evaluator.eval(&program, &mut env);
     assert!(result.is_err());
     if let Err(error) = result {
-        assert!(error.error_message.contains("Division by zero"));
+        assert_eq!(error.kind, EvaluatorErrorKind::ZeroDivision);
+        assert_eq!(error.to_string(), "ZeroDivisionError: Division by zero");
     }
 }
 
@@ -336,7 +339,7 @@ fn return_null_value() {
 // Helper functions
 
 #[cfg(test)]
-fn test_eval(input: &str) -> Box<dyn Object> {
+fn test_eval(input: &str) -> Value {
     let lexer = Lexer::new(input);
     let mut parser = Parser::new(lexer);
     let program = parser.parse_program();
@@ -349,47 +352,48 @@ fn test_eval(input: &str) -> Box<dyn Object> {
 }
 
 #[cfg(test)]
-fn expect_eval_error(input: &str) {
+fn expect_eval_error(input: &str) -> EvaluatorError {
     let lexer = Lexer::new(input);
     let mut parser = Parser::new(lexer);
     let program = parser.parse_program();
     let mut evaluator = Evaluator::new();
     let mut env = Environment::new();
 
-    if let Ok(val) = evaluator.eval(&program, &mut env) {
-        panic!("Expected error, got {}", val.inspect());
+    match evaluator.eval(&program, &mut env) {
+        Ok(val) => panic!("Expected error, got {}", val.inspect()),
+        Err(error) => error,
     }
 }
 
 #[cfg(test)]
-fn test_integer_object(obj: &Box<dyn Object>, expected: i64) {
-    if let Some(integer) = obj.as_any().downcast_ref::<Integer>() {
-        assert_eq!(integer.value, expected, "Integer value mismatch");
+fn test_integer_object(value: &Value, expected: i64) {
+    if let Value::Integer(integer) = value {
+        assert_eq!(*integer, expected, "Integer value mismatch");
     } else {
         panic!("Expected Integer object, got different type");
     }
 }
 
 #[cfg(test)]
-fn test_boolean_object(obj: &Box<dyn Object>, expected: bool) {
-    if let Some(boolean) = obj.as_any().downcast_ref::<Boolean>() {
-        assert_eq!(boolean.value, expected, "Boolean value mismatch");
+fn test_boolean_object(value: &Value, expected: bool) {
+    if let Value::Boolean(boolean) = value {
+        assert_eq!(*boolean, expected, "Boolean value mismatch");
     } else {
         panic!("Expected Boolean object, got different type");
     }
 }
 
 #[cfg(test)]
-fn test_null_object(obj: &Box<dyn Object>) {
-    if !obj.as_any().is::<Null>() {
+fn test_null_object(value: &Value) {
+    if !matches!(value, Value::Null) {
         panic!("Expected Null object, got different type");
     }
 }
 
 #[cfg(test)]
-fn test_array_object(obj: &Box<dyn Object>, expected: &str) {
-    if let Some(array) = obj.as_any().downcast_ref::<Array>() {
-        assert_eq!(array.inspect(), expected, "Array value mismatch");
+fn test_array_object(value: &Value, expected: &str) {
+    if let Value::Array(_) = value {
+        assert_eq!(value.inspect(), expected, "Array value mismatch");
     } else {
         panic!("Expected Array object, got different type");
     }
@@ -412,6 +416,9 @@ fn array_index_evaluation() {
         ("[5, 10, 15][1 + 1];", 15),
         ("[100, 200, 300][2 - 1];", 200),
         ("[7, 14, 21][3 / 3 - 1];", 7),
+        // Negative indices count back from the end
+        ("[1, 2, 3][-1];", 3),
+        ("[1, 2, 3][-3];", 1),
     ];
 
     for (input, expected) in tests {
@@ -455,8 +462,7 @@ fn array_index_error_cases() {
         "[1, 2, 3][5];",
         "[10][1];",
         "[][0];",
-        // Negative indices should error (since we use usize)
-        "[1, 2, 3][-1];",
+        "[1, 2, 3][-4];",
     ];
 
     for input in tests {
@@ -507,6 +513,379 @@ fn fibonacci_function() {
     test_integer_object(&evaluated, 21);
 }
 
+#[test]
+fn recursive_functions() {
+    let tests = vec![
+        (
+            "let rec is_even = fun(n) { if n == 0 { true } else { is_odd(n - 1) } } and is_odd = fun(n) { if n == 0 { false } else { is_even(n - 1) } }; [is_even(10), is_odd(7), is_even(20001)];",
+            "[true, true, false]",
+        ),
+        // A recursive function can be passed around as a value
+        (
+            "let rec fact = fun(n) { if n == 0 { 1 } else { n * fact(n - 1) } }; let apply = fun(f, x) { f(x) }; apply(fact, 5);",
+            "120",
+        ),
+        (
+            "let rec countdown = fun(n) { if n == 0 { [] } else { push(countdown(n - 1), n) } }; let fs = [countdown]; let f = fs[0]; f(3);",
+            "[1, 2, 3]",
+        ),
+        // The functions refer to each other rather than to whatever their names are bound to later
+        (
+            "let rec fact = fun(n) { if n == 0 { 1 } else { n * fact(n - 1) } }; let f = fact; let fact = 0; f(5);",
+            "120",
+        ),
+        (
+            "let rec mut ping = fun(n) { if n == 0 { \"ping\" } else { pong(n - 1) } } and pong = fun(n) { if n == 0 { \"pong\" } else { ping(n - 1) } }; let p = ping; ping = null; p(3);",
+            r#""pong""#,
+        ),
+        // Recursive functions defined inside a function can capture its parameters
+        (
+            "let count_to = fun(limit) { let rec up = fun(n) { if n == limit { [n] } else { [n] + up(n + 1) } }; up }; let up = count_to(3); up(1);",
+            "[1, 2, 3]",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        assert_eq!(evaluated.inspect(), expected, "Input: {input}");
+    }
+
+    // Functions defined together are named after their own bindings in backtraces
+    let input = "let rec f = fun(n) { g(n) + 0 } and g = fun(n) { n / 0 };\nf(1);";
+    let error = expect_eval_error(input);
+    assert_eq!(error.kind, EvaluatorErrorKind::ZeroDivision);
+    assert_eq!(
+        error.backtrace,
+        vec![
+            Frame::new("f", "<program>", Span::new(58, 62, 2, 1)),
+            Frame::new("g", "f", Span::new(21, 25, 1, 22)),
+        ]
+    );
+}
+
+#[test]
+fn destructuring() {
+    let tests = vec![
+        ("let [a, b] = [1, 2]; a + b;", "3"),
+        (
+            "let [head, ...rest] = [1, 2, 3]; [head, rest];",
+            "[1, [2, 3]]",
+        ),
+        ("let [x, ...rest] = [1]; rest;", "[]"),
+        ("let [...all] = []; all;", "[]"),
+        (
+            r#"let {name, age} = {"name": "Ada", "age": 36}; "${name} is ${age}";"#,
+            r#""Ada is 36""#,
+        ),
+        (
+            r#"let {point: {x, y: [first, ...others]}} = {"point": {"x": 1, "y": [2, 3]}, "z": 4}; x + first;"#,
+            "3",
+        ),
+        ("let [[a, b], [c]] = [[1, 2], [3]]; a + b + c;", "6"),
+        ("let mut [a, b] = [1, 2]; a = 10; a + b;", "12"),
+        // Parameters can be destructured too
+        ("let sum = fun([a, b]) { a + b }; sum([3, 4]);", "7"),
+        (
+            r#"let name_of = fun({name}) { name }; name_of({"name": "Grace"});"#,
+            r#""Grace""#,
+        ),
+        (
+            "let swap = fun([a, b]) { [b, a] }; let [x, y] = swap([1, 2]); [x, y];",
+            "[2, 1]",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        assert_eq!(evaluated.inspect(), expected, "Input: {input}");
+    }
+
+    let error_tests = vec![
+        (
+            "let [a, b] = 1;",
+            EvaluatorErrorKind::TypeError {
+                expected: "array".to_string(),
+                found: "integer".to_string(),
+            },
+            Span::new(4, 10, 1, 5),
+        ),
+        (
+            "let [a, b] = [1, 2, 3];",
+            EvaluatorErrorKind::ValueError {
+                message: "Expected an array of 2 items, found 3 items".to_string(),
+            },
+            Span::new(4, 10, 1, 5),
+        ),
+        (
+            "let [a, b, ...rest] = [1];",
+            EvaluatorErrorKind::ValueError {
+                message: "Expected an array of at least 2 items, found 1 item".to_string(),
+            },
+            Span::new(4, 19, 1, 5),
+        ),
+        (
+            r#"let {name, age} = {"name": "Ada"};"#,
+            EvaluatorErrorKind::KeyError {
+                key: r#""age""#.to_string(),
+            },
+            Span::new(11, 14, 1, 12),
+        ),
+        (
+            "let {name} = [1];",
+            EvaluatorErrorKind::TypeError {
+                expected: "hash".to_string(),
+                found: "array".to_string(),
+            },
+            Span::new(4, 10, 1, 5),
+        ),
+        (
+            "let first = fun([a, ...rest]) { a }; first([]);",
+            EvaluatorErrorKind::ValueError {
+                message: "Expected an array of at least 1 item, found 0 items".to_string(),
+            },
+            Span::new(16, 28, 1, 17),
+        ),
+    ];
+
+    for (input, expected_kind, expected_span) in error_tests {
+        let error = expect_eval_error(input);
+        assert_eq!(error.kind, expected_kind, "Input: {input}");
+        assert_eq!(error.span, Some(expected_span), "Input: {input}");
+    }
+}
+
+#[test]
+fn match_expressions() {
+    let describe = r#"let describe = fun(v) {
+        match v {
+            0 => "zero",
+            -1.5 => "negative",
+            "hi" => "greeting",
+            true => "yes",
+            null => "nothing",
+            [] => "empty",
+            [x] => "one ${x}",
+            [x, ...rest] if x > 10 => "big ${len(rest)}",
+            [_, ...rest] => "many ${rest}",
+            {name, age} if age >= 18 => "${name} is an adult",
+            {name} => name,
+            n if n > 100 => "large",
+            _ => "other",
+        }
+    };"#;
+    let tests = vec![
+        ("0", r#""zero""#),
+        ("0.0", r#""zero""#),
+        ("-1.5", r#""negative""#),
+        (r#""hi""#, r#""greeting""#),
+        ("true", r#""yes""#),
+        ("null", r#""nothing""#),
+        ("[]", r#""empty""#),
+        ("[3]", r#""one 3""#),
+        ("[11, 2, 3]", r#""big 2""#),
+        ("[1, 2, 3]", r#""many [2, 3]""#),
+        (r#"{"name": "Ada", "age": 36}"#, r#""Ada is an adult""#),
+        (r#"{"name": "Kid", "age": 3}"#, r#""Kid""#),
+        ("1000", r#""large""#),
+        ("5", r#""other""#),
+    ];
+    for (value, expected) in tests {
+        let input = format!("{describe} describe({value});");
+        let evaluated = test_eval(&input);
+        assert_eq!(evaluated.inspect(), expected, "Input: {value}");
+    }
+
+    let tests = vec![
+        // Names bound by an arm don't leak out of it
+        ("let x = 1; match 2 { x => x }; x;", "1"),
+        // An arm's value can be a block, which has its own scope
+        (
+            "let y = 1; match 1 { 1 => { let y = 2; y + 1 }, _ => 0 } + y;",
+            "4",
+        ),
+        ("match 2 { 1 => { 1 } _ => { 2 } };", "2"),
+        ("match 1 { _ => {} };", "null"),
+        (r#"match 1 { _ => ({"a": 1}) }["a"];"#, "1"),
+        ("let x = 1; match 2 { x if x > 5 => x, y => x + y };", "3"),
+        // Only the chosen arm's value is evaluated
+        ("match 1 { 1 => 10, _ => 1 / 0 };", "10"),
+        // Arm values are in tail position
+        (
+            "let rec count = fun(n) { match n { 0 => \"done\", _ => count(n - 1) } }; count(200000);",
+            r#""done""#,
+        ),
+        (
+            "let rec sum = fun(xs) { match xs { [] => 0, [x, ...rest] => x + sum(rest) } }; sum([1, 2, 3, 4]);",
+            "10",
+        ),
+    ];
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        assert_eq!(evaluated.inspect(), expected, "Input: {input}");
+    }
+
+    let input = "let x = [1, 2];\nmatch x { [a] => a, [a, b] if a > b => a };";
+    let error = expect_eval_error(input);
+    assert_eq!(
+        error.kind,
+        EvaluatorErrorKind::NoMatch {
+            value: "[1, 2]".to_string()
+        }
+    );
+    assert_eq!(error.span, Some(Span::new(16, 58, 2, 1)));
+    assert_eq!(error.to_string(), "MatchError: No pattern matched [1, 2]");
+
+    // Literal patterns in let statements must match too
+    let error = expect_eval_error("let [x, 0] = [1, 2];");
+    assert_eq!(
+        error.kind,
+        EvaluatorErrorKind::ValueError {
+            message: "Expected 0, found 2".to_string()
+        }
+    );
+    assert_eq!(error.span, Some(Span::new(8, 9, 1, 9)));
+}
+
+#[test]
+fn records() {
+    let tests = vec![
+        ("struct Point { x, y } Point(1, 2);", "Point { x: 1, y: 2 }"),
+        ("struct Point { x, y } Point;", "struct Point { x, y }"),
+        ("struct Unit {} Unit();", "Unit {}"),
+        ("struct Unit {} Unit;", "struct Unit {}"),
+        ("struct Point { x, y } let p = Point(1, 2); p.x + p.y;", "3"),
+        (
+            r#"struct Person { name, tags } let p = Person("Ada", ["math"]); p.tags[0];"#,
+            r#""math""#,
+        ),
+        (
+            "struct Point { x, y } struct Line { start, end } let l = Line(Point(0, 0), Point(3, 4)); l.end.y - l.start.y;",
+            "4",
+        ),
+        // Updating a record copies it rather than changing it
+        (
+            "struct Point { x, y } let p = Point(1, 2); let q = p with { x: 3 }; [p, q];",
+            "[Point { x: 1, y: 2 }, Point { x: 3, y: 2 }]",
+        ),
+        (
+            "struct Point { x, y } let p = Point(1, 2); (p with { y: p.x, x: p.y }).x;",
+            "2",
+        ),
+        (
+            "struct Point { x, y } Point(1, 2) with {};",
+            "Point { x: 1, y: 2 }",
+        ),
+        // Records can be passed to and returned from functions
+        (
+            "struct Point { x, y } let move = fun(p, dx) { p with { x: p.x + dx } }; move(Point(1, 2), 5).x;",
+            "6",
+        ),
+        (
+            r#"struct Point { x, y } "${Point(1, "a")}";"#,
+            r#""Point { x: 1, y: \"a\" }""#,
+        ), // Functions stored in fields can be called through them
+        (
+            "struct Shape { area } let s = Shape(fun(k) { k * 2 }); s.area(3);",
+            "6",
+        ),
+        (
+            "struct Box { item } let b = Box(Box(fun() { 1 })); b.item.item() + 1;",
+            "2",
+        ),
+        (
+            "struct Types { point } struct Point { x, y } Types(Point).point(1, 2);",
+            "Point { x: 1, y: 2 }",
+        ),
+        // The arguments are evaluated before the field
+        (
+            "struct F { f } let mut log = []; let g = fun(x) { log = push(log, x); F(fun(y) { y }) }; g(1).f(g(2)); log;",
+            "[2, 1]",
+        ),
+    ];
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        assert_eq!(evaluated.inspect(), expected, "Input: {input}");
+    }
+
+    let equality_tests = vec![
+        ("struct P { x } P(1) == P(1);", true),
+        ("struct P { x } P(1) == P(1.0);", true),
+        ("struct P { x } P(1) != P(2);", true),
+        ("struct P { x } P([1, 2]) == P([1, 2]);", true),
+        ("struct P { x } let p = P(1); p == p with { x: 1 };", true),
+        // Records of different structs are never equal, even if their fields are
+        (
+            "struct P { x } let a = P(1); struct P { x } a == P(1);",
+            false,
+        ),
+        ("struct P { x } struct Q { x } P(1) == Q(1);", false),
+        ("struct P { x } P == P;", true),
+        ("struct P { x } struct Q { x } P == Q;", false),
+        (r#"struct P { x } P(1) == {"x": 1};"#, false),
+    ];
+    for (input, expected) in equality_tests {
+        let evaluated = test_eval(input);
+        test_boolean_object(&evaluated, expected);
+    }
+
+    let error_tests = vec![
+        (
+            "struct Point { x, y } let p = Point(1, 2); p.z;",
+            EvaluatorErrorKind::FieldError {
+                struct_name: "Point".to_string(),
+                field: "z".to_string(),
+            },
+            Span::new(45, 46, 1, 46),
+        ),
+        (
+            "struct Point { x, y } let p = Point(1, 2); p with { x: 1, z: 2 };",
+            EvaluatorErrorKind::FieldError {
+                struct_name: "Point".to_string(),
+                field: "z".to_string(),
+            },
+            Span::new(58, 59, 1, 59),
+        ),
+        (
+            r#"let h = {"x": 1}; h.x;"#,
+            EvaluatorErrorKind::TypeError {
+                expected: "record".to_string(),
+                found: "hash".to_string(),
+            },
+            Span::new(18, 19, 1, 19),
+        ),
+        (
+            "[1] with { x: 2 };",
+            EvaluatorErrorKind::TypeError {
+                expected: "record".to_string(),
+                found: "array".to_string(),
+            },
+            Span::new(0, 3, 1, 1),
+        ),
+        (
+            "struct Shape { area } let s = Shape(1); s.area(2);",
+            EvaluatorErrorKind::TypeError {
+                expected: "function".to_string(),
+                found: "integer".to_string(),
+            },
+            Span::new(40, 49, 1, 41),
+        ),
+        (
+            "struct Point { x, y } Point(1);",
+            EvaluatorErrorKind::ArityError {
+                min: 2,
+                max: 2,
+                found: 1,
+            },
+            Span::new(22, 30, 1, 23),
+        ),
+    ];
+    for (input, expected_kind, expected_span) in error_tests {
+        let error = expect_eval_error(input);
+        assert_eq!(error.kind, expected_kind, "Input: {input}");
+        assert_eq!(error.span, Some(expected_span), "Input: {input}");
+    }
+}
+
 #[test]
 fn array_literal_evaluation() {
     let tests = vec![
@@ -569,3 +948,1341 @@ fn array_len() {
         test_integer_object(&evaluated, expected);
     }
 }
+
+#[test]
+fn runtime_error_spans() {
+    let tests = vec![
+        ("5 / 0;", 1, 1),
+        ("let x = 1;\nlet y = x + foo;", 2, 13),
+        ("let f = fun(x) {\n  x / 0\n};\nf(1);", 2, 3),
+        ("len(1, 2);", 1, 1),
+    ];
+
+    for (input, line, column) in tests {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program();
+        let mut evaluator = Evaluator::new();
+        let mut env = Environment::new();
+
+        let error = match evaluator.eval(&program, &mut env) {
+            Ok(value) => panic!("Expected error, got {}", value.inspect()),
+            Err(error) => error,
+        };
+        let span = error.span.expect("Expected error to have a span");
+        assert_eq!((span.line, span.column), (line, column), "Input: {input}");
+    }
+}
+
+#[test]
+fn runtime_error_kinds() {
+    let type_error = |expected: &str, found: &str| EvaluatorErrorKind::TypeError {
+        expected: expected.to_string(),
+        found: found.to_string(),
+    };
+    let tests = vec![
+        ("true + 1;", type_error("number", "boolean")),
+        ("1 - [1];", type_error("number", "array")),
+        ("1.5 * null;", type_error("number", "null")),
+        ("-true;", type_error("number", "boolean")),
+        ("!5;", type_error("boolean", "integer")),
+        ("len(5);", type_error("array or string", "integer")),
+        ("let x = 5; x(1);", type_error("function", "integer")),
+        ("[1, 2][true];", type_error("integer", "boolean")),
+        (
+            "foo + 1;",
+            EvaluatorErrorKind::NameError {
+                name: "foo".to_string(),
+            },
+        ),
+        (
+            "bar(1);",
+            EvaluatorErrorKind::NameError {
+                name: "bar".to_string(),
+            },
+        ),
+        (
+            "let f = fun(x, y) { x + y }; f(1);",
+            EvaluatorErrorKind::ArityError {
+                min: 2,
+                max: 2,
+                found: 1,
+            },
+        ),
+        (
+            "push([1]);",
+            EvaluatorErrorKind::ArityError {
+                min: 2,
+                max: 2,
+                found: 1,
+            },
+        ),
+        (
+            "[1, 2, 3][3];",
+            EvaluatorErrorKind::IndexError {
+                index: 3,
+                length: 3,
+            },
+        ),
+        (
+            "[1, 2, 3][-4];",
+            EvaluatorErrorKind::IndexError {
+                index: -4,
+                length: 3,
+            },
+        ),
+        ("10 / (5 - 5);", EvaluatorErrorKind::ZeroDivision),
+    ];
+
+    for (input, expected) in tests {
+        let error = expect_eval_error(input);
+        assert_eq!(error.kind, expected, "Input: {input}");
+    }
+}
+
+#[test]
+fn runtime_error_display() {
+    let tests = vec![
+        ("foo;", "NameError: Unknown identifier found: foo"),
+        ("true + 1;", "TypeError: Expected number, found boolean"),
+        (
+            "int(1e308 * 10);",
+            "ValueError: Cannot convert inf to an integer",
+        ),
+        (
+            r#"float("abc");"#,
+            r#"ValueError: Cannot convert "abc" to a float"#,
+        ),
+        ("len();", "ArityError: Expected 1 argument, found 0"),
+        ("push(1);", "ArityError: Expected 2 arguments, found 1"),
+        ("range();", "ArityError: Expected 1 or 2 arguments, found 0"),
+        (
+            "range(1, 2, 3);",
+            "ArityError: Expected 1 or 2 arguments, found 3",
+        ),
+        (
+            "[1][1];",
+            "IndexError: Index 1 is out of bounds for length 1",
+        ),
+        ("tail([]);", "ValueError: tail expects a non-empty array"),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(expect_eval_error(input).to_string(), expected);
+    }
+}
+
+#[test]
+fn runtime_error_backtraces() {
+    // None of these calls are in tail position, so each keeps its own frame
+    let input = "let apply = fun(f, x) {\n  f(x) + 0\n};\nlet rec count = fun(n) {\n  if (n == 0) { apply(fun(x) { x / 0 }, 1) + 0 } else { count(n - 1) + 0 }\n};\ncount(1);";
+    let error = expect_eval_error(input);
+    let frames: Vec<(&str, usize)> = error
+        .backtrace
+        .iter()
+        .map(|frame| (frame.function_name.as_str(), frame.call_site.line))
+        .collect();
+    assert_eq!(
+        frames,
+        vec![("count", 7), ("count", 5), ("apply", 5), ("<anonymous>", 2)]
+    );
+    assert_eq!(
+        error.backtrace[0],
+        Frame::new("count", "<program>", Span::new(141, 149, 7, 1))
+    );
+
+    // Errors outside of any function have no backtrace, and the call stack is unwound after a
+    // failed call
+    assert!(expect_eval_error("1 / 0;").backtrace.is_empty());
+    assert!(
+        expect_eval_error("let f = fun() { 1 }; f(); x;")
+            .backtrace
+            .is_empty()
+    );
+}
+
+#[test]
+fn tail_calls() {
+    // Each of these recurses far deeper than the native stack would allow without tail calls
+    let tests = vec![
+        (
+            "let rec sum = fun(n, acc) { if n == 0 { acc } else { sum(n - 1, acc + n) } }; sum(20000, 0);",
+            "200010000",
+        ),
+        (
+            "let count = fun(n) { if n > 0 { return count(n - 1); } n }; count(20000);",
+            "0",
+        ),
+        (
+            "let count = fun(n) { while true { if n == 0 { return \"done\"; } return count(n - 1); } }; count(20000);",
+            r#""done""#,
+        ),
+        (
+            "let is_even = fun(n) { if n == 0 { true } else { is_odd(n - 1) } }; let is_odd = fun(n) { if n == 0 { false } else { is_even(n - 1) } }; is_even(20001);",
+            "false",
+        ),
+        (
+            "let rec map_helper = fun(ret, arr, f, index) { if index < len(arr) { let item = f(arr[index]); map_helper(push(ret, item), arr, f, index + 1) } else { ret } }; let mut items = []; for i in range(20000) { items = push(items, i); } let doubled = map_helper([], items, fun(n) { n * 2 }, 0); doubled[19999];",
+            "39998",
+        ),
+        (
+            "let count = fun(n) { if n == 0 { \"done\" } else if n % 2 == 0 { let m = n - 1; count(m) } else { count(n - 1) } }; count(20000);",
+            r#""done""#,
+        ),
+        // Calls outside of tail position are still applied immediately
+        (
+            "let rec fact = fun(n) { if n == 0 { 1 } else { n * fact(n - 1) } }; fact(10);",
+            "3628800",
+        ),
+        (
+            "let f = fun() { let x = g(); x + 1 }; let g = fun() { 1 }; f();",
+            "2",
+        ),
+        ("let f = fun(x) { x }; [f(1), f(2)];", "[1, 2]"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        assert_eq!(evaluated.inspect(), expected, "Input: {input}");
+    }
+
+    // A tail call replaces the frame of the call that made it, so only the innermost call shows up
+    // in backtraces
+    let input = "let apply = fun(f, x) {\n  f(x)\n};\nlet rec count = fun(n) {\n  if (n == 0) { apply(fun(x) { x / 0 }, 1) } else { count(n - 1) }\n};\ncount(1);";
+    let error = expect_eval_error(input);
+    assert_eq!(error.kind, EvaluatorErrorKind::ZeroDivision);
+    assert_eq!(
+        error.backtrace,
+        vec![Frame::new("<anonymous>", "apply", Span::new(26, 30, 2, 3))]
+    );
+
+    // Arity errors are raised in the frame that made the tail call
+    let error = expect_eval_error("let f = fun(a) { a };\nlet g = fun() { f() };\ng();");
+    assert_eq!(
+        error.kind,
+        EvaluatorErrorKind::ArityError {
+            min: 1,
+            max: 1,
+            found: 0,
+        }
+    );
+    assert_eq!(error.span, Some(Span::new(38, 41, 2, 17)));
+    assert_eq!(
+        error.backtrace,
+        vec![Frame::new("g", "<program>", Span::new(45, 48, 3, 1))]
+    );
+}
+
+#[test]
+fn deep_recursion() {
+    // Calls outside of tail position nest on the evaluator's own stack rather than the native one
+    let tests = vec![
+        (
+            "let rec reverse = fun(arr) { if len(arr) <= 1 { arr } else { push(reverse(tail(arr)), arr[0]) } }; let mut items = []; for i in range(20000) { items = push(items, i); } reverse(items)[0];",
+            "19999",
+        ),
+        (
+            "let rec depth = fun(n) { if n == 0 { 0 } else { 1 + depth(n - 1) } }; depth(50000);",
+            "50000",
+        ),
+        (
+            "let rec nest = fun(n) { if n == 0 { [] } else { let inner = nest(n - 1); [len(inner)] } }; nest(50000);",
+            "[1]",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        assert_eq!(evaluated.inspect(), expected, "Input: {input}");
+    }
+
+    // Nesting deeper than the limit is a runtime error rather than a crash
+    let input =
+        "let rec depth = fun(n) {\n  if n == 0 { 0 } else { 1 + depth(n - 1) }\n};\ndepth(100);";
+    let program = Parser::new(Lexer::new(input)).parse_program();
+    let mut evaluator = Evaluator::new().with_max_depth(50);
+    let error = match evaluator.eval(&program, &mut Environment::new()) {
+        Ok(value) => panic!("Expected error, got {}", value.inspect()),
+        Err(error) => error,
+    };
+    assert_eq!(error.kind, EvaluatorErrorKind::StackOverflow { limit: 50 });
+    assert_eq!(error.span, Some(Span::new(54, 66, 2, 30)));
+    assert_eq!(error.backtrace.len(), 50);
+    assert_eq!(
+        error.backtrace[0],
+        Frame::new("depth", "<program>", Span::new(72, 82, 4, 1))
+    );
+
+    // Tail calls don't count towards the limit
+    let input =
+        "let rec count = fun(n) { if n == 0 { \"done\" } else { count(n - 1) } }; count(1000);";
+    let program = Parser::new(Lexer::new(input)).parse_program();
+    let evaluated = Evaluator::new()
+        .with_max_depth(50)
+        .eval(&program, &mut Environment::new())
+        .expect("Evaluation failed");
+    assert_eq!(evaluated.inspect(), r#""done""#);
+}
+
+#[test]
+fn function_outlives_its_program() {
+    // Like successive lines in the REPL, the program defining a function is dropped before the
+    // function is called, and the call drops the last reference to the function
+    let mut env = Environment::new();
+    let mut evaluator = Evaluator::new();
+    let program = Parser::new(Lexer::new(
+        "let mut f = fun(x) { f = null; let y = x * 2; y + 1 };",
+    ))
+    .parse_program();
+    evaluator
+        .eval(&program, &mut env)
+        .expect("Evaluation failed");
+    drop(program);
+
+    let program = Parser::new(Lexer::new("f(20);")).parse_program();
+    let evaluated = evaluator
+        .eval(&program, &mut env)
+        .expect("Evaluation failed");
+    test_integer_object(&evaluated, 41);
+}
+
+#[test]
+fn string_inspect_escapes() {
+    let tests = vec![
+        (r#""a\tb";"#, r#""a\tb""#),
+        (r#""line\n";"#, r#""line\n""#),
+        (r#"r"\d+";"#, r#""\\d+""#),
+        (r#"["\"q\""];"#, r#"["\"q\""]"#),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(test_eval(input).inspect(), expected, "Input: {input}");
+    }
+}
+
+#[test]
+fn string_interpolation() {
+    let tests = vec![
+        (r#"let name = "Bob"; "Hello ${name}!";"#, "Hello Bob!"),
+        (r#""${1 + 2} = ${3}";"#, "3 = 3"),
+        (
+            r#"let items = [1, "two"]; "${len(items)} items: ${items}";"#,
+            r#"2 items: [1, "two"]"#,
+        ),
+        (
+            r#""${true}, ${null}, ${"nested ${1}"}";"#,
+            "true, null, nested 1",
+        ),
+        (r#""a ${if (1 < 2) { "b" } else { "c" }} d";"#, "a b d"),
+        (r#""\${not} $5";"#, "${not} $5"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        let Value::String(string) = &evaluated else {
+            panic!("Expected string object");
+        };
+        assert_eq!(string.as_ref(), expected, "Input: {input}");
+    }
+}
+
+#[test]
+fn display_differs_from_inspect_for_strings() {
+    let tests = vec![
+        (r#""a\"b";"#, "a\"b", r#""a\"b""#),
+        ("5;", "5", "5"),
+        (r#"["a"];"#, r#"["a"]"#, r#"["a"]"#),
+    ];
+
+    for (input, display, inspect) in tests {
+        let evaluated = test_eval(input);
+        assert_eq!(evaluated.display(), display);
+        assert_eq!(evaluated.inspect(), inspect);
+    }
+}
+
+#[test]
+fn float_arithmetic() {
+    let tests = vec![
+        ("2.75;", 2.75),
+        (".5;", 0.5),
+        ("1e-9;", 1e-9),
+        ("-2.5;", -2.5),
+        ("1.5 + 1.5;", 3.0),
+        ("1 + 0.5;", 1.5),
+        ("0.5 * 4;", 2.0),
+        ("7 / 2.0;", 3.5),
+        ("10 - 2.5 * 2;", 5.0),
+        ("(3 + 4 + 5.5) / 3;", 12.5 / 3.0),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        test_float_object(&evaluated, expected);
+    }
+
+    // Only integer division by zero is an error. Float arithmetic follows IEEE 754.
+    let division_tests = vec![
+        ("1.5 / 0;", "inf"),
+        ("-1 / 0.0;", "-inf"),
+        ("1 / -0.0;", "-inf"),
+        ("0.0 / 0;", "NaN"),
+        ("5.0 % 0;", "NaN"),
+        ("0.0 ** -1;", "inf"),
+        ("0 ** -1.0;", "inf"),
+    ];
+
+    for (input, expected) in division_tests {
+        let evaluated = test_eval(input);
+        assert_eq!(evaluated.inspect(), expected, "Input: {input}");
+    }
+}
+
+#[test]
+fn mixed_numeric_comparisons() {
+    let tests = vec![
+        ("1 == 1.0;", true),
+        ("1.0 != 1;", false),
+        ("0.1 + 0.2 == 0.3;", false),
+        ("2 > 1.5;", true),
+        ("1.5 >= 2;", false),
+        ("-0.5 < 0;", true),
+        ("3 <= 3.0;", true),
+        ("1 == true;", false),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        test_boolean_object(&evaluated, expected);
+    }
+}
+
+#[test]
+fn numeric_conversion_builtins() {
+    let integer_tests = vec![
+        ("int(3.9);", 3),
+        ("int(-3.9);", -3),
+        ("int(7);", 7),
+        (r#"int(" 42 ");"#, 42),
+        ("floor(-1.5);", -2),
+        ("ceil(1.2);", 2),
+        ("round(2.5);", 3),
+        ("round(-2.5);", -3),
+        ("round(2.4);", 2),
+        ("floor(5);", 5),
+    ];
+    for (input, expected) in integer_tests {
+        let evaluated = test_eval(input);
+        test_integer_object(&evaluated, expected);
+    }
+
+    let float_tests = vec![
+        ("float(2);", 2.0),
+        ("float(2.5);", 2.5),
+        (r#"float("1e3");"#, 1000.0),
+    ];
+    for (input, expected) in float_tests {
+        let evaluated = test_eval(input);
+        test_float_object(&evaluated, expected);
+    }
+}
+
+#[cfg(test)]
+fn test_float_object(value: &Value, expected: f64) {
+    let Value::Float(result) = value else {
+        panic!("Expected float, got {}", value.inspect());
+    };
+    assert_eq!(*result, expected);
+}
+
+#[test]
+fn integer_overflow_promotes_to_big_integer() {
+    let tests = vec![
+        ("9223372036854775807 + 1;", "9223372036854775808"),
+        ("-9223372036854775807 - 2;", "-9223372036854775809"),
+        ("4294967296 * 4294967296;", "18446744073709551616"),
+        ("-(-9223372036854775807 - 1);", "9223372036854775808"),
+        ("(-9223372036854775807 - 1) / -1;", "9223372036854775808"),
+        ("99999999999999999999;", "99999999999999999999"),
+        ("99999999999999999999 * 0;", "0"),
+        ("99999999999999999999 / 99999999999999999999;", "1"),
+        (
+            "let rec factorial = fun(n) { if (n == 1) { 1 } else { n * factorial(n - 1) } }; factorial(25);",
+            "15511210043330985984000000",
+        ),
+        (
+            r#"int("123456789012345678901234567890");"#,
+            "123456789012345678901234567890",
+        ),
+        ("int(1e20);", "100000000000000000000"),
+        ("float(99999999999999999999);", "1e20"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        assert_eq!(evaluated.inspect(), expected, "Input: {input}");
+    }
+
+    // Results that fit back into an i64 are demoted, so they can be used as indices
+    let evaluated = test_eval("99999999999999999999 - 99999999999999999998;");
+    test_integer_object(&evaluated, 1);
+}
+
+#[test]
+fn big_integer_comparisons() {
+    let tests = vec![
+        ("99999999999999999999 == 99999999999999999999;", true),
+        ("99999999999999999999 != 99999999999999999998;", true),
+        ("99999999999999999999 > 9223372036854775807;", true),
+        ("-99999999999999999999 < 1;", true),
+        ("99999999999999999999 > 1.5;", true),
+        ("100000000000000000000 == 1e20;", true),
+        ("if (99999999999999999999) { true } else { false };", true),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        test_boolean_object(&evaluated, expected);
+    }
+}
+
+#[test]
+fn arithmetic_and_bitwise_operators() {
+    let tests = vec![
+        ("7 % 3;", "1"),
+        ("-7 % 3;", "-1"),
+        ("7.5 % 2;", "1.5"),
+        ("99999999999999999999 % 7;", "1"),
+        ("2 ** 10;", "1024"),
+        ("2 ** 3 ** 2;", "512"),
+        ("-2 ** 2;", "-4"),
+        ("(-2) ** 3;", "-8"),
+        ("2 ** -1;", "0.5"),
+        ("4 ** 0.5;", "2.0"),
+        ("2 ** 64;", "18446744073709551616"),
+        ("6 & 3;", "2"),
+        ("6 | 3;", "7"),
+        ("6 ^ 3;", "5"),
+        ("1 << 4;", "16"),
+        ("1 << 64;", "18446744073709551616"),
+        ("-16 >> 2;", "-4"),
+        ("99999999999999999999 >> 60;", "86"),
+        ("(1 << 64) & 1;", "0"),
+        ("1 ** 4000000000;", "1"),
+        ("(-1) ** 4000000001;", "-1"),
+        ("0 << 10000000000;", "0"),
+        ("1 >> 10000000000;", "0"),
+        ("(2 ** 100000) >> 99999;", "2"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        assert_eq!(evaluated.inspect(), expected, "Input: {input}");
+    }
+
+    let error_tests = vec![
+        ("5 % 0;", EvaluatorErrorKind::ZeroDivision),
+        ("0 ** -1;", EvaluatorErrorKind::ZeroDivision),
+        (
+            "1.5 & 1;",
+            EvaluatorErrorKind::TypeError {
+                expected: "integer".to_string(),
+                found: "float".to_string(),
+            },
+        ),
+        (
+            "1 << -1;",
+            EvaluatorErrorKind::ValueError {
+                message: "Negative shift amount -1".to_string(),
+            },
+        ),
+        (
+            "2 ** 99999999999;",
+            EvaluatorErrorKind::ValueError {
+                message: "Exponent 99999999999 is too large".to_string(),
+            },
+        ),
+        // Integers that would be too large to compute are rejected before computing them
+        (
+            "2 ** 4000000000;",
+            EvaluatorErrorKind::ValueError {
+                message: "Result of `**` would have more than 1048576 bits".to_string(),
+            },
+        ),
+        (
+            "(1 << 1000) ** 2000;",
+            EvaluatorErrorKind::ValueError {
+                message: "Result of `**` would have more than 1048576 bits".to_string(),
+            },
+        ),
+        (
+            "1 << 10000000000;",
+            EvaluatorErrorKind::ValueError {
+                message: "Result of `<<` would have more than 1048576 bits".to_string(),
+            },
+        ),
+        (
+            "(1 << 1000000) << 100000;",
+            EvaluatorErrorKind::ValueError {
+                message: "Result of `<<` would have more than 1048576 bits".to_string(),
+            },
+        ),
+    ];
+
+    for (input, expected) in error_tests {
+        assert_eq!(expect_eval_error(input).kind, expected, "Input: {input}");
+    }
+}
+
+#[test]
+fn logical_operators_short_circuit() {
+    let tests = vec![
+        ("true && true;", true),
+        ("true && false;", false),
+        ("false || true;", true),
+        ("false || false;", false),
+        ("1 && 2.5;", true),
+        ("null || 0;", false),
+        ("1 < 2 && 2 < 3;", true),
+        ("false && 1 / 0;", false),
+        ("true || missing;", true),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        test_boolean_object(&evaluated, expected);
+    }
+
+    let error = expect_eval_error("true && 1 / 0;");
+    assert_eq!(error.kind, EvaluatorErrorKind::ZeroDivision);
+}
+
+#[test]
+fn structural_equality() {
+    let tests = vec![
+        (r#""abc" == "abc";"#, true),
+        (r#""abc" != "abd";"#, true),
+        (r#""1" == 1;"#, false),
+        ("[1, 2, 3] == [1, 2, 3];", true),
+        ("[1, 2, 3] == [1, 2];", false),
+        ("[1, [2, 3]] == [1, [2, 3]];", true),
+        ("[1, [2, 3]] != [1, [2, 4]];", true),
+        ("[1, 2.0] == [1.0, 2];", true),
+        (r#"[null, true, "x"] == [null, true, "x"];"#, true),
+        ("[] == [];", true),
+        ("[] == null;", false),
+        ("let f = fun(x) { x }; f == f;", false),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        test_boolean_object(&evaluated, expected);
+    }
+}
+
+#[test]
+fn string_and_array_ordering() {
+    let tests = vec![
+        (r#""apple" < "banana";"#, true),
+        (r#""apple" < "apples";"#, true),
+        (r#""b" > "abc";"#, true),
+        (r#""Z" < "a";"#, true),
+        (r#""é" > "z";"#, true),
+        (r#""abc" <= "abc";"#, true),
+        (r#""abc" >= "abd";"#, false),
+        ("[1, 2] < [1, 3];", true),
+        ("[1, 2] < [1, 2, 0];", true),
+        ("[2] > [1, 9, 9];", true),
+        ("[1, [2, 3]] < [1, [2, 4]];", true),
+        ("[] <= [];", true),
+        ("[1.5] < [2];", true),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        test_boolean_object(&evaluated, expected);
+    }
+
+    let type_error = |expected: &str, found: &str| EvaluatorErrorKind::TypeError {
+        expected: expected.to_string(),
+        found: found.to_string(),
+    };
+    let error_tests = vec![
+        (r#""a" < 1;"#, type_error("string", "integer")),
+        (r#"1 < "a";"#, type_error("number", "string")),
+        ("[1] < [true];", type_error("number", "boolean")),
+        (
+            "true < false;",
+            type_error("number, string or array", "boolean"),
+        ),
+        (
+            "null >= null;",
+            type_error("number, string or array", "null"),
+        ),
+    ];
+
+    for (input, expected) in error_tests {
+        assert_eq!(expect_eval_error(input).kind, expected, "Input: {input}");
+    }
+
+    // The error points at the operand that can't be ordered
+    let error = expect_eval_error(r#""a" < 1;"#);
+    assert_eq!(error.span.map(|span| span.start), Some(6));
+}
+
+#[test]
+fn deeply_nested_values() {
+    // Comparing, inspecting and dropping these would overflow the Rust stack if done recursively
+    let depth = 100000;
+    let tests = vec![
+        (
+            "let mut a = []; let mut b = []; for i in range(DEPTH) { a = [a]; b = [b]; } [a == b, a != [b], a < [b], [b] > a];",
+            "[true, true, true, true]",
+        ),
+        (
+            "let mut a = []; for i in range(DEPTH) { a = [a]; } len(\"${a}\");",
+            "200002",
+        ),
+        (
+            r#"let mut h = {}; for i in range(DEPTH) { h = {"k": h}; } h == h;"#,
+            "true",
+        ),
+        (
+            "struct Node { next } let mut n = null; for i in range(DEPTH) { n = Node(n); } n == n;",
+            "true",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let input = input.replace("DEPTH", &depth.to_string());
+        let evaluated = test_eval(&input);
+        assert_eq!(evaluated.inspect(), expected, "Input: {input}");
+    }
+
+    let evaluated = test_eval(
+        "let mut a = []; for i in range(DEPTH) { a = [a]; } a;"
+            .replace("DEPTH", &depth.to_string())
+            .as_str(),
+    );
+    let inspected = evaluated.inspect();
+    assert_eq!(inspected.len(), depth * 2 + 2);
+    assert!(inspected.starts_with("[[[") && inspected.ends_with("]]]"));
+    drop(evaluated);
+}
+
+#[test]
+fn string_operators() {
+    let tests = vec![
+        (r#""foo" + "bar";"#, "foobar"),
+        (r#""" + "";"#, ""),
+        (r#""ab" * 3;"#, "ababab"),
+        (r#"2 * "hé";"#, "héhé"),
+        (r#""ab" * 0;"#, ""),
+        (r#""" * 9223372036854775807;"#, ""),
+        (r#"let s = "x"; s + s * 2;"#, "xxx"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        let Value::String(string) = &evaluated else {
+            panic!("Expected string");
+        };
+        assert_eq!(string.as_ref(), expected, "Input: {input}");
+    }
+
+    let error_tests = vec![
+        (
+            r#""a" + 1;"#,
+            EvaluatorErrorKind::TypeError {
+                expected: "string".to_string(),
+                found: "integer".to_string(),
+            },
+        ),
+        (
+            r#""a" * 1.5;"#,
+            EvaluatorErrorKind::TypeError {
+                expected: "integer".to_string(),
+                found: "float".to_string(),
+            },
+        ),
+        (
+            r#""a" * -1;"#,
+            EvaluatorErrorKind::ValueError {
+                message: "Cannot repeat a string -1 times".to_string(),
+            },
+        ),
+        // Strings that would be too large to allocate are rejected before building them
+        (
+            r#""ab" * 99999999999;"#,
+            EvaluatorErrorKind::ValueError {
+                message: "Repeating a string 99999999999 times would make it longer than 1073741824 bytes".to_string(),
+            },
+        ),
+        (
+            r#""ab" * 9223372036854775807;"#,
+            EvaluatorErrorKind::ValueError {
+                message: "Repeating a string 9223372036854775807 times would make it longer than 1073741824 bytes".to_string(),
+            },
+        ),
+    ];
+
+    for (input, expected) in error_tests {
+        assert_eq!(expect_eval_error(input).kind, expected, "Input: {input}");
+    }
+}
+
+#[test]
+fn string_indexing_and_slicing() {
+    // Strings are indexed by Unicode scalar value, not by byte
+    let tests = vec![
+        (r#""hello"[0];"#, "h"),
+        (r#""hello"[4];"#, "o"),
+        (r#""héllo"[1];"#, "é"),
+        (r#""héllo"[2];"#, "l"),
+        (r#""hello"[-1];"#, "o"),
+        (r#""héllo"[-4];"#, "é"),
+        (r#""日本語"[1];"#, "本"),
+        (r#""hello"[1:3];"#, "el"),
+        (r#""héllo"[1:3];"#, "él"),
+        (r#""hello"[:2];"#, "he"),
+        (r#""hello"[3:];"#, "lo"),
+        (r#""hello"[-3:];"#, "llo"),
+        (r#""hello"[:-1];"#, "hell"),
+        (r#""hello"[:];"#, "hello"),
+        (r#""hello"[3:1];"#, ""),
+        (r#""hello"[2:100];"#, "llo"),
+        (r#""hello"[-100:2];"#, "he"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        let Value::String(string) = &evaluated else {
+            panic!("Expected string");
+        };
+        assert_eq!(string.as_ref(), expected, "Input: {input}");
+    }
+
+    let evaluated = test_eval(r#"len("héllo");"#);
+    test_integer_object(&evaluated, 5);
+
+    let error_tests = vec![
+        (
+            r#""hello"[5];"#,
+            EvaluatorErrorKind::IndexError {
+                index: 5,
+                length: 5,
+            },
+        ),
+        (
+            r#""héllo"[-6];"#,
+            EvaluatorErrorKind::IndexError {
+                index: -6,
+                length: 5,
+            },
+        ),
+        (
+            r#""hello"["a"];"#,
+            EvaluatorErrorKind::TypeError {
+                expected: "integer".to_string(),
+                found: "string".to_string(),
+            },
+        ),
+        (
+            "5[0];",
+            EvaluatorErrorKind::TypeError {
+                expected: "array, string or hash".to_string(),
+                found: "integer".to_string(),
+            },
+        ),
+        (
+            r#""hello"[true:];"#,
+            EvaluatorErrorKind::TypeError {
+                expected: "integer".to_string(),
+                found: "boolean".to_string(),
+            },
+        ),
+    ];
+
+    for (input, expected) in error_tests {
+        assert_eq!(expect_eval_error(input).kind, expected, "Input: {input}");
+    }
+}
+
+#[test]
+fn array_slicing() {
+    let tests = vec![
+        ("[1, 2, 3, 4][1:3];", "[2, 3]"),
+        ("[1, 2, 3, 4][:2];", "[1, 2]"),
+        ("[1, 2, 3, 4][2:];", "[3, 4]"),
+        ("[1, 2, 3, 4][-2:];", "[3, 4]"),
+        ("[1, 2, 3, 4][:];", "[1, 2, 3, 4]"),
+        ("[1, 2, 3, 4][3:1];", "[]"),
+        ("[][0:5];", "[]"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        test_array_object(&evaluated, expected);
+    }
+}
+
+#[test]
+fn persistent_arrays() {
+    let tests = vec![
+        ("[1, 2] + [3];", "[1, 2, 3]"),
+        ("[] + [];", "[]"),
+        ("[[1]] + [[2], []];", "[[1], [2], []]"),
+        // Arrays are values, so building a new array leaves the original unchanged
+        (
+            "let a = [1, 2]; let b = push(a, 3); let c = a + b; [a, b, c];",
+            "[[1, 2], [1, 2, 3], [1, 2, 1, 2, 3]]",
+        ),
+        (
+            "let a = [1, 2, 3]; let b = tail(a); let c = a[1:]; [a, b, c, b == c];",
+            "[[1, 2, 3], [2, 3], [2, 3], true]",
+        ),
+        // Large arrays share structure rather than being copied by each operation
+        (
+            "let mut a = []; for i in range(10000) { a = push(a, i); } let mut total = 0; while len(a) > 0 { total = total + a[0]; a = tail(a); } total;",
+            "49995000",
+        ),
+        (
+            "let mut a = []; for i in range(10000) { a = push(a, i); } [len(a + a), a[9999], a[5000:5003]];",
+            "[20000, 9999, [5000, 5001, 5002]]",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        assert_eq!(evaluated.inspect(), expected, "Input: {input}");
+    }
+
+    assert_eq!(
+        expect_eval_error("[1] + 1;").kind,
+        EvaluatorErrorKind::TypeError {
+            expected: "array".to_string(),
+            found: "integer".to_string(),
+        }
+    );
+}
+
+#[test]
+fn hash_literals_and_indexing() {
+    let tests = vec![
+        ("{};", "{}"),
+        (
+            r#"{"b": 1, "a": 2, 3: true, false: null, null: [1]};"#,
+            r#"{"b": 1, "a": 2, 3: true, false: null, null: [1]}"#,
+        ),
+        // Later duplicates replace the value, but keep the position of the first key
+        (r#"{"a": 1, "b": 2, "a": 3};"#, r#"{"a": 3, "b": 2}"#),
+        (
+            r#"let k = "x"; {k: 1 + 1, k + k: {}};"#,
+            r#"{"x": 2, "xx": {}}"#,
+        ),
+        (r#"{"one": 1, "two": 2}["two"];"#, "2"),
+        (r#"let m = {1: "a", true: "b"}; m[1] + m[true];"#, r#""ab""#),
+        (r#"{"a": {"b": [10, 20]}}["a"]["b"][1];"#, "20"),
+        ("{null: 5}[null];", "5"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        assert_eq!(evaluated.inspect(), expected, "Input: {input}");
+    }
+
+    let error_tests = vec![
+        (
+            r#"{"a": 1}["b"];"#,
+            EvaluatorErrorKind::KeyError {
+                key: r#""b""#.to_string(),
+            },
+        ),
+        (
+            "{1.5: 1};",
+            EvaluatorErrorKind::TypeError {
+                expected: "integer, string, boolean or null".to_string(),
+                found: "float".to_string(),
+            },
+        ),
+        (
+            "{1: 1}[[1]];",
+            EvaluatorErrorKind::TypeError {
+                expected: "integer, string, boolean or null".to_string(),
+                found: "array".to_string(),
+            },
+        ),
+    ];
+
+    for (input, expected) in error_tests {
+        assert_eq!(expect_eval_error(input).kind, expected, "Input: {input}");
+    }
+}
+
+#[test]
+fn hash_builtins() {
+    let tests = vec![
+        (r#"keys({"b": 1, "a": 2});"#, r#"["b", "a"]"#),
+        (r#"values({"b": 1, "a": 2});"#, "[1, 2]"),
+        (r#"has({"a": 1}, "a");"#, "true"),
+        (r#"has({"a": 1}, "b");"#, "false"),
+        (r#"put({"a": 1}, "b", 2);"#, r#"{"a": 1, "b": 2}"#),
+        (r#"put({"a": 1, "b": 2}, "a", 3);"#, r#"{"a": 3, "b": 2}"#),
+        (
+            r#"remove({"a": 1, "b": 2, "c": 3}, "b");"#,
+            r#"{"a": 1, "c": 3}"#,
+        ),
+        (r#"remove({"a": 1}, "z");"#, r#"{"a": 1}"#),
+        // put and remove leave the original hash unchanged
+        (
+            r#"let m = {"a": 1}; let n = put(m, "b", 2); m;"#,
+            r#"{"a": 1}"#,
+        ),
+        (
+            r#"let m = {"a": 1}; let n = remove(m, "a"); m;"#,
+            r#"{"a": 1}"#,
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        assert_eq!(evaluated.inspect(), expected, "Input: {input}");
+    }
+
+    let equality_tests = vec![
+        (r#"{"a": 1, "b": 2} == {"b": 2, "a": 1};"#, true),
+        (r#"{"a": [1]} == {"a": [1.0]};"#, true),
+        (r#"{"a": 1} == {"a": 2};"#, false),
+        (r#"{"a": 1} == {"a": 1, "b": 2};"#, false),
+        ("{} == [];", false),
+    ];
+
+    for (input, expected) in equality_tests {
+        let evaluated = test_eval(input);
+        test_boolean_object(&evaluated, expected);
+    }
+
+    let error = expect_eval_error("keys([1]);");
+    assert_eq!(
+        error.kind,
+        EvaluatorErrorKind::TypeError {
+            expected: "hash".to_string(),
+            found: "array".to_string(),
+        }
+    );
+}
+
+#[test]
+fn loop_statements() {
+    let tests = vec![
+        (
+            "let f = fun() { for x in [1, 2, 3, 4] { if x > 2 { return x; } } }; f();",
+            "3",
+        ),
+        (
+            "let f = fun() { for x in [1, 2, 3] { if x < 3 { continue; } return x * 10; } }; f();",
+            "30",
+        ),
+        (
+            r#"let f = fun() { for c in "héllo" { if c != "h" { return c; } } }; f();"#,
+            r#""é""#,
+        ),
+        (
+            r#"let f = fun() { for k in {"b": 1, "a": 2} { return k; } }; f();"#,
+            r#""b""#,
+        ),
+        (
+            "let f = fun() { for i in range(5, 10) { return i; } }; f();",
+            "5",
+        ),
+        (
+            "let f = fun() { for i in range(3, 1) { return i; } }; f();",
+            "null",
+        ),
+        ("let f = fun() { while true { return 1; } }; f();", "1"),
+        ("while true { break; }", "null"),
+        ("while false { 1 / 0; }", "null"),
+        ("for x in [] { 1 / 0; }", "null"),
+        // break only leaves the innermost loop
+        (
+            "let f = fun() { for i in range(3) { for j in range(3) { break; } if i == 2 { return i; } } }; f();",
+            "2",
+        ),
+        // A long loop doesn't grow the native stack
+        (
+            "let f = fun() { for i in range(100000) { if i == 99999 { return i; } } }; f();",
+            "99999",
+        ),
+        ("range(2, 5);", "range(2, 5)"),
+        ("range(3) == range(0, 3);", "true"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        assert_eq!(evaluated.inspect(), expected, "Input: {input}");
+    }
+
+    let error_tests = vec![
+        (
+            "for x in [1] { x; } x;",
+            EvaluatorErrorKind::NameError {
+                name: "x".to_string(),
+            },
+        ),
+        (
+            "for x in [1] { let y = x; } y;",
+            EvaluatorErrorKind::NameError {
+                name: "y".to_string(),
+            },
+        ),
+        (
+            "for x in 5 { x; }",
+            EvaluatorErrorKind::TypeError {
+                expected: "array, string, range or hash".to_string(),
+                found: "integer".to_string(),
+            },
+        ),
+        (
+            "range(1.5);",
+            EvaluatorErrorKind::TypeError {
+                expected: "integer".to_string(),
+                found: "float".to_string(),
+            },
+        ),
+        (
+            "range();",
+            EvaluatorErrorKind::ArityError {
+                min: 1,
+                max: 2,
+                found: 0,
+            },
+        ),
+    ];
+
+    for (input, expected) in error_tests {
+        assert_eq!(expect_eval_error(input).kind, expected, "Input: {input}");
+    }
+}
+
+#[test]
+fn mutable_bindings() {
+    let tests = vec![
+        ("let mut x = 1; x = x + 1; x;", "2"),
+        ("let mut x = 1; let mut y = 2; x = y = 5; x + y;", "10"),
+        ("let mut x = 1; x = 7;", "7"),
+        (
+            "let mut total = 0; for i in range(1, 101) { total = total + i; } total;",
+            "5050",
+        ),
+        ("let mut i = 0; while i < 10 { i = i + 1; } i;", "10"),
+        (
+            "let mut i = 0; while true { i = i + 1; if i == 5 { break; } } i;",
+            "5",
+        ),
+        // Assignments inside blocks update the binding in the enclosing scope
+        ("let mut x = 1; if true { x = 2; } x;", "2"),
+        // Closures see updates to captured bindings, in both directions
+        ("let mut x = 1; let get = fun() { x }; x = 5; get();", "5"),
+        (
+            "let mut count = 0; let bump = fun() { count = count + 1; }; bump(); bump(); count;",
+            "2",
+        ),
+        (
+            "let counter = fun() { let mut n = 0; fun() { n = n + 1; n } }; let c = counter(); c(); c(); c();",
+            "3",
+        ),
+        // A let in a nested scope shadows the binding rather than assigning to it
+        (
+            "let mut x = 1; let f = fun() { x }; if true { let x = 2; } f();",
+            "1",
+        ),
+        ("let x = 1; let mut x = 2; x = 3; x;", "3"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        assert_eq!(evaluated.inspect(), expected, "Input: {input}");
+    }
+
+    let error_tests = vec![
+        (
+            "let x = 1; x = 2;",
+            EvaluatorErrorKind::ImmutableAssignment {
+                name: "x".to_string(),
+            },
+        ),
+        (
+            "let f = fun(n) { n = 2; }; f(1);",
+            EvaluatorErrorKind::ImmutableAssignment {
+                name: "n".to_string(),
+            },
+        ),
+        (
+            "for i in [1] { i = 2; }",
+            EvaluatorErrorKind::ImmutableAssignment {
+                name: "i".to_string(),
+            },
+        ),
+        (
+            "y = 1;",
+            EvaluatorErrorKind::NameError {
+                name: "y".to_string(),
+            },
+        ),
+    ];
+
+    for (input, expected) in error_tests {
+        assert_eq!(expect_eval_error(input).kind, expected, "Input: {input}");
+    }
+
+    let error = expect_eval_error("let x = 1;\nx = 2;");
+    assert_eq!(
+        error.to_string(),
+        "AssignmentError: Cannot assign to immutable binding `x`"
+    );
+    assert_eq!(error.span, Some(Span::new(11, 12, 2, 1)));
+}
+
+#[test]
+fn closures_capture_live_scopes() {
+    let tests = vec![
+        // Functions see bindings defined after them in their defining scope, so plain let
+        // bindings can be recursive
+        ("let f = fun() { g() }; let g = fun() { 5 }; f();", "5"),
+        (
+            "let fib = fun(n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }; fib(15);",
+            "610",
+        ),
+        (
+            "let is_even = fun(n) { if n == 0 { true } else { is_odd(n - 1) } }; let is_odd = fun(n) { if n == 0 { false } else { is_even(n - 1) } }; is_odd(7);",
+            "true",
+        ),
+        // A later let shadows a binding rather than updating it, so functions that captured the
+        // earlier binding keep seeing it
+        ("let x = 1; let h = fun() { x }; let x = 2; h();", "1"),
+        ("let x = 1; let h = fun() { x }; let x = 2; h() + x;", "3"),
+        (
+            "let f = fun(n) { 0 }; let f = fun(n) { if n == 0 { 1 } else { f(n - 1) } }; f(3);",
+            "1",
+        ),
+        (
+            "let mut x = 1; let h = fun() { x = x + 1; x }; let x = 10; h(); h();",
+            "3",
+        ),
+        // Each call gets a fresh scope, so parameters don't leak between calls or into the
+        // defining scope
+        (
+            "let make = fun(n) { fun() { n } }; let one = make(1); let two = make(2); one() + two();",
+            "3",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = test_eval(input);
+        assert_eq!(evaluated.inspect(), expected, "Input: {input}");
+    }
+
+    let error = expect_eval_error("let f = fun(n) { n }; f(1); n;");
+    assert_eq!(
+        error.kind,
+        EvaluatorErrorKind::NameError {
+            name: "n".to_string(),
+        }
+    );
+}
+
+#[test]
+fn functions_bound_in_their_scope_are_freed() {
+    use crate::ast::Identifier;
+    use crate::token::{Token, TokenType};
+    use std::rc::Rc;
+
+    let point = Rc::new(Point { x: 1, y: 2 });
+    let name = Identifier::new(Token::new(TokenType::Ident, "p"), "p");
+    let tests = vec![
+        "let f = fun() { p }; f();",
+        "let f = fun(n) { if n == 0 { p } else { f(n - 1) } }; f(3);",
+        "let rec even = fun(n) { if n == 0 { p } else { odd(n - 1) } } and odd = fun(n) { even(n - 1) }; even(4);",
+        "let make = fun() { let g = fun() { p }; g }; let call = fun(f) { f() }; call(make());",
+        "let mut f = null; f = fun() { p }; f();",
+    ];
+
+    for input in tests {
+        let program = Parser::new(Lexer::new(input)).parse_program();
+        let mut env = Environment::new();
+        env.insert(&name, Value::Object(point.clone()));
+        let result = Evaluator::new().eval(&program, &mut env);
+        assert_eq!(
+            result.expect("Evaluation failed").inspect(),
+            "Point(1, 2)",
+            "Input: {input}"
+        );
+        drop(env);
+        assert_eq!(Rc::strong_count(&point), 1, "Input: {input}");
+    }
+}
+
+#[cfg(test)]
+struct Point {
+    x: i64,
+    y: i64,
+}
+
+#[cfg(test)]
+impl crate::object::Object for Point {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn inspect(&self) -> String {
+        format!("Point({}, {})", self.x, self.y)
+    }
+
+    fn type_name(&self) -> &'static str {
+        "point"
+    }
+}
+
+#[test]
+fn host_objects() {
+    use crate::ast::Identifier;
+    use crate::token::{Token, TokenType};
+    use std::rc::Rc;
+
+    let point = Value::Object(Rc::new(Point { x: 1, y: 2 }));
+    let name = Identifier::new(Token::new(TokenType::Ident, "p"), "p");
+    let tests = vec![
+        ("p;", Ok("Point(1, 2)")),
+        (r#""at ${p}";"#, Ok(r#""at Point(1, 2)""#)),
+        ("[p, p][1];", Ok("Point(1, 2)")),
+        ("p + 1;", Err("Expected number, found point")),
+    ];
+
+    for (input, expected) in tests {
+        let program = Parser::new(Lexer::new(input)).parse_program();
+        let mut env = Environment::new();
+        env.insert(&name, point.clone());
+        let result = Evaluator::new().eval(&program, &mut env);
+        match expected {
+            Ok(expected) => assert_eq!(
+                result.expect("Evaluation failed").inspect(),
+                expected,
+                "Input: {input}"
+            ),
+            Err(expected) => assert_eq!(
+                result.err().expect("Expected error").kind.message(),
+                expected,
+                "Input: {input}"
+            ),
+        }
+    }
+
+    let Value::Object(object) = &point else {
+        panic!("Expected host object");
+    };
+    let point = object
+        .as_any()
+        .downcast_ref::<Point>()
+        .expect("Expected point");
+    assert_eq!((point.x, point.y), (1, 2));
+}
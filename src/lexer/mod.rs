@@ -2,58 +2,139 @@ mod tests;
 
 use std::collections::HashMap;
 
-use crate::token::{Token, TokenType};
+use crate::token::{Span, Token, TokenType};
+
+/// What the lexer is in the middle of reading. Modes nest, since a string can contain an
+/// interpolated expression, which can itself contain a string.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    String,
+    // Backslashes and `${` have no special meaning in raw strings
+    RawString,
+    // Inside a `${...}` segment of a string. Counts the `{` that are still open within the
+    // expression, so the `}` that ends the segment can be told apart from one closing a block.
+    Interpolation { open_braces: usize },
+}
 
 pub struct Lexer {
     input: Vec<char>,
+    // The (byte offset, line, column) of every character in `input`, plus one extra entry for the
+    // end of the input. Used to build the span of each token.
+    positions: Vec<(usize, usize, usize)>,
+    // Index of `cur_char` in `input`. Equal to `input.len()` once the end of the input is reached.
     cur_position: usize,
     // Always points to 1 ahead of `cur_position`
     read_position: usize,
     cur_char: char,
-    in_string: bool,
+    // Empty when lexing code outside of any string
+    modes: Vec<Mode>,
 }
 
 impl Lexer {
     pub fn new(input: &str) -> Self {
+        let mut positions = Vec::new();
+        let (mut offset, mut line, mut column) = (0, 1, 1);
+        for c in input.chars() {
+            positions.push((offset, line, column));
+            offset += c.len_utf8();
+            if c == '\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+        }
+        positions.push((offset, line, column));
+
         let mut lexer = Self {
             input: input.chars().collect(),
+            positions,
             cur_position: 0,
             read_position: 0,
             cur_char: '\0',
-            in_string: false,
+            modes: Vec::new(),
         };
         lexer.read_char();
         lexer
     }
 
     pub fn next_token(&mut self) -> Token {
-        if self.in_string && self.cur_char != '"' {
-            let value = self.read_til_double_quotation();
-            return Token::new(TokenType::StringValue, &value);
+        if self.in_string() && self.cur_char != '"' {
+            if self.cur_char == '$' && self.peek_char() == '{' && self.mode() == Some(Mode::String)
+            {
+                let start = self.cur_position;
+                self.read_char(); // consume the '$'
+                self.read_char(); // consume the '{'
+                self.modes.push(Mode::Interpolation { open_braces: 0 });
+                return Token::with_span(
+                    TokenType::InterpolationStart,
+                    "${",
+                    self.span(start, self.cur_position),
+                );
+            }
+            return self.read_string_contents();
         }
         self.skip_whitespace_and_comments();
 
-        let token = match self.cur_char {
-            '=' => {
-                if self.peek_char() == '=' {
+        let start = self.cur_position;
+        let mut token = match self.cur_char {
+            '=' => match self.peek_char() {
+                '=' => {
                     self.read_char(); // consume the second '='
                     Token::new(TokenType::Eq, "==")
-                } else {
-                    Token::new(TokenType::Assign, "=")
                 }
-            }
+                '>' => {
+                    self.read_char(); // consume the '>'
+                    Token::new(TokenType::FatArrow, "=>")
+                }
+                _ => Token::new(TokenType::Assign, "="),
+            },
             '+' => Token::new(TokenType::Plus, "+"),
             '(' => Token::new(TokenType::LParen, "("),
             ')' => Token::new(TokenType::RParen, ")"),
-            '{' => Token::new(TokenType::LBrace, "{"),
-            '}' => Token::new(TokenType::RBrace, "}"),
+            '{' => {
+                if let Some(Mode::Interpolation { open_braces }) = self.modes.last_mut() {
+                    *open_braces += 1;
+                }
+                Token::new(TokenType::LBrace, "{")
+            }
+            '}' => match self.modes.last_mut() {
+                Some(Mode::Interpolation { open_braces: 0 }) => {
+                    self.modes.pop();
+                    Token::new(TokenType::InterpolationEnd, "}")
+                }
+                Some(Mode::Interpolation { open_braces }) => {
+                    *open_braces -= 1;
+                    Token::new(TokenType::RBrace, "}")
+                }
+                _ => Token::new(TokenType::RBrace, "}"),
+            },
             '[' => Token::new(TokenType::LSquare, "["),
+            ':' => Token::new(TokenType::Colon, ":"),
             ']' => Token::new(TokenType::RSquare, "]"),
             '"' => {
-                self.in_string = !self.in_string;
+                if self.in_string() {
+                    self.modes.pop();
+                } else {
+                    self.modes.push(Mode::String);
+                }
                 Token::new(TokenType::DoubleQuotation, "\"")
             }
+            'r' if self.peek_char() == '"' => {
+                self.read_char(); // consume the 'r'
+                self.modes.push(Mode::RawString);
+                Token::new(TokenType::DoubleQuotation, "r\"")
+            }
             ',' => Token::new(TokenType::Comma, ","),
+            '.' if self.peek_char() == '.'
+                && self.input.get(self.read_position + 1) == Some(&'.') =>
+            {
+                self.read_char(); // consume the second '.'
+                self.read_char(); // consume the third '.'
+                Token::new(TokenType::Ellipsis, "...")
+            }
+            // A `.` before a digit starts a float literal, eg. `.5`
+            '.' if !self.peek_char().is_ascii_digit() => Token::new(TokenType::Dot, "."),
             ';' => Token::new(TokenType::Semicolon, ";"),
             '!' => {
                 if self.peek_char() == '=' {
@@ -65,23 +146,54 @@ impl Lexer {
             }
             '-' => Token::new(TokenType::Minus, "-"),
             '/' => Token::new(TokenType::Slash, "/"),
-            '*' => Token::new(TokenType::Star, "*"),
-            '<' => {
-                if self.peek_char() == '=' {
-                    self.read_char(); // consume the second '='
-                    Token::new(TokenType::LessEq, "<=")
+            '*' => {
+                if self.peek_char() == '*' {
+                    self.read_char(); // consume the second '*'
+                    Token::new(TokenType::DoubleStar, "**")
                 } else {
-                    Token::new(TokenType::LArrow, "<")
+                    Token::new(TokenType::Star, "*")
                 }
             }
-            '>' => {
-                if self.peek_char() == '=' {
-                    self.read_char(); // consume the second '='
-                    Token::new(TokenType::GreaterEq, ">=")
+            '%' => Token::new(TokenType::Percent, "%"),
+            '^' => Token::new(TokenType::Caret, "^"),
+            '&' => {
+                if self.peek_char() == '&' {
+                    self.read_char(); // consume the second '&'
+                    Token::new(TokenType::LogicalAnd, "&&")
+                } else {
+                    Token::new(TokenType::Ampersand, "&")
+                }
+            }
+            '|' => {
+                if self.peek_char() == '|' {
+                    self.read_char(); // consume the second '|'
+                    Token::new(TokenType::LogicalOr, "||")
                 } else {
-                    Token::new(TokenType::RArrow, ">")
+                    Token::new(TokenType::Pipe, "|")
                 }
             }
+            '<' => match self.peek_char() {
+                '=' => {
+                    self.read_char(); // consume the '='
+                    Token::new(TokenType::LessEq, "<=")
+                }
+                '<' => {
+                    self.read_char(); // consume the second '<'
+                    Token::new(TokenType::LShift, "<<")
+                }
+                _ => Token::new(TokenType::LArrow, "<"),
+            },
+            '>' => match self.peek_char() {
+                '=' => {
+                    self.read_char(); // consume the '='
+                    Token::new(TokenType::GreaterEq, ">=")
+                }
+                '>' => {
+                    self.read_char(); // consume the second '>'
+                    Token::new(TokenType::RShift, ">>")
+                }
+                _ => Token::new(TokenType::RArrow, ">"),
+            },
             '\0' => Token::new(TokenType::Eof, ""),
             c => {
                 if c.is_alphabetic() || Self::is_underscore(c) {
@@ -93,53 +205,56 @@ impl Lexer {
                     self.unread_char();
                     let token_type = Self::lookup_ident(&word);
                     Token::new(token_type, &word)
-                } else if c.is_numeric() {
-                    let number = self.read_number();
+                } else if c.is_numeric() || (c == '.' && self.peek_char().is_ascii_digit()) {
+                    let (number, is_float) = self.read_number();
                     // Unread a character here because the functions used here (`read_word`, `read_number`)
                     // reads until the first character NOT in the literal. Then the `read_char` call below
                     // would then skip this character entirely, so we add a `unread_char` call here to
                     // not skip it.
                     self.unread_char();
-                    Token::new(TokenType::Int, &number)
+                    if is_float {
+                        Token::new(TokenType::Float, &number)
+                    } else {
+                        Token::new(TokenType::Int, &number)
+                    }
                 } else {
                     Token::new(TokenType::Illegal, &c.to_string())
                 }
             }
         };
+        // `cur_position` points to the last character of the token here
+        token.span = self.span(start, self.cur_position + 1);
         self.read_char();
         token
     }
 
     pub fn read_char(&mut self) {
-        if self.read_position >= self.input.len() {
-            self.cur_char = '\0';
-        } else {
-            self.cur_char = self.input[self.read_position];
-            self.cur_position = self.read_position;
-            self.read_position += 1;
-        }
+        self.cur_position = self.read_position.min(self.input.len());
+        self.cur_char = self.input.get(self.cur_position).copied().unwrap_or('\0');
+        self.read_position = self.cur_position + 1;
     }
 
     fn peek_char(&self) -> char {
-        if self.read_position >= self.input.len() {
-            '\0'
-        } else {
-            self.input[self.read_position]
-        }
+        self.input.get(self.read_position).copied().unwrap_or('\0')
     }
 
     /// Unreads a char by moving the position pointers back by 1.
     fn unread_char(&mut self) {
-        if self.cur_position == 0 {
-            self.cur_char = '\0';
-            self.read_position = 0;
-        } else {
-            self.read_position = self.cur_position;
+        if self.cur_position > 0 {
             self.cur_position -= 1;
+            self.read_position = self.cur_position + 1;
             self.cur_char = self.input[self.cur_position];
         }
     }
 
+    /// Returns the span covering the characters in `input[start..end]`.
+    fn span(&self, start: usize, end: usize) -> Span {
+        let end = end.clamp(start, self.input.len());
+        let (start_offset, line, column) = self.positions[start];
+        let (end_offset, _, _) = self.positions[end];
+        Span::new(start_offset, end_offset, line, column)
+    }
+
     fn read_word(&mut self) -> String {
         let mut word = String::new();
         while self.cur_char.is_alphanumeric() || Self::is_underscore(self.cur_char) {
@@ -151,13 +266,62 @@ impl Lexer {
 
     // Note: Even though this function is called `read_number`, it returns a String.
     // This is because in the lexing stage, all token literals are String types.
-    fn read_number(&mut self) -> String {
+    // Also returns whether the number is a float, ie. it has a fractional part (`3.14`, `.5`) or
+    // an exponent (`1e-9`). A `.` or `e` only belongs to the number if a digit follows it.
+    //
+    // Integers can be written in hex, octal or binary with a `0x`, `0o` or `0b` prefix, and any
+    // number can contain `_` digit separators. The literal isn't validated here: every
+    // alphanumeric character after a prefix is included, so that the parser can point out
+    // invalid digits like the `Z` in `0xZZ`.
+    fn read_number(&mut self) -> (String, bool) {
         let mut number = String::new();
-        while self.cur_char.is_numeric() {
+        if self.cur_char == '0' && matches!(self.peek_char(), 'x' | 'o' | 'b') {
+            number.push(self.cur_char);
+            self.read_char();
+            number.push(self.cur_char);
+            self.read_char();
+            while self.cur_char.is_alphanumeric() || self.cur_char == '_' {
+                number.push(self.cur_char);
+                self.read_char();
+            }
+            return (number, false);
+        }
+
+        let mut is_float = false;
+        self.read_digits(&mut number);
+        if self.cur_char == '.' && self.peek_char().is_ascii_digit() {
+            is_float = true;
+            number.push('.');
+            self.read_char();
+            self.read_digits(&mut number);
+        }
+        if self.cur_char == 'e' || self.cur_char == 'E' {
+            let sign = self.peek_char();
+            let has_sign = sign == '+' || sign == '-';
+            let after_sign = self
+                .input
+                .get(self.read_position + has_sign as usize)
+                .copied()
+                .unwrap_or('\0');
+            if after_sign.is_ascii_digit() {
+                is_float = true;
+                number.push(self.cur_char);
+                self.read_char();
+                if has_sign {
+                    number.push(sign);
+                    self.read_char();
+                }
+                self.read_digits(&mut number);
+            }
+        }
+        (number, is_float)
+    }
+
+    fn read_digits(&mut self, number: &mut String) {
+        while self.cur_char.is_numeric() || self.cur_char == '_' {
             number.push(self.cur_char);
             self.read_char();
         }
-        number
     }
 
     /// Returns TokenType::Ident if `word` is not a keyword in the Monkey
@@ -167,7 +331,7 @@ impl Lexer {
     ///
     /// Basic usage:
     ///
-    /// ```
+    /// ```ignore
     /// assert!(lookup_ident("let") == TokenType::Let)
     /// assert!(lookup_ident("fun") == TokenType::Function)
     /// assert!(lookup_ident("skibidi") == TokenType::Ident)
@@ -176,12 +340,22 @@ impl Lexer {
         let mut keywords = HashMap::new();
         keywords.insert("let", TokenType::Let);
         keywords.insert("rec", TokenType::Rec);
+        keywords.insert("mut", TokenType::Mut);
+        keywords.insert("and", TokenType::And);
         keywords.insert("fun", TokenType::Function);
         keywords.insert("true", TokenType::True);
         keywords.insert("false", TokenType::False);
         keywords.insert("if", TokenType::If);
         keywords.insert("else", TokenType::Else);
         keywords.insert("return", TokenType::Return);
+        keywords.insert("while", TokenType::While);
+        keywords.insert("for", TokenType::For);
+        keywords.insert("in", TokenType::In);
+        keywords.insert("break", TokenType::Break);
+        keywords.insert("continue", TokenType::Continue);
+        keywords.insert("match", TokenType::Match);
+        keywords.insert("struct", TokenType::Struct);
+        keywords.insert("with", TokenType::With);
         keywords.insert("null", TokenType::Null);
         *keywords.get(word).unwrap_or(&TokenType::Ident)
     }
@@ -208,18 +382,153 @@ impl Lexer {
 
     // Advances characters until newline is encountered. This is used to handle comments
     fn skip_til_newline(&mut self) {
-        while self.cur_char != '\n' {
+        while self.cur_char != '\n' && self.cur_char != '\0' {
             self.read_char();
         }
     }
 
-    // Advances characters until double quote is encountered. This is used to handle strings
-    fn read_til_double_quotation(&mut self) -> String {
-        let mut ret = String::new();
-        while self.cur_char != '"' {
-            ret.push(self.cur_char);
+    /// Reads the contents of a string up to the closing double quote or the next `${`, decoding
+    /// escape sequences unless the string is raw. Returns a StringValue token, or:
+    /// - an InvalidEscape token if the contents start with an unrecognised escape sequence. If one
+    ///   appears later on, the contents before it are returned first.
+    /// - an UnterminatedString token if the end of the input is reached first
+    fn read_string_contents(&mut self) -> Token {
+        let start = self.cur_position;
+        let mut value = String::new();
+        loop {
+            match self.cur_char {
+                '"' => break,
+                '$' if self.peek_char() == '{' && self.mode() == Some(Mode::String) => break,
+                '\0' if self.cur_position >= self.input.len() => {
+                    self.modes.pop();
+                    return Token::with_span(
+                        TokenType::UnterminatedString,
+                        &value,
+                        self.span(start, self.cur_position),
+                    );
+                }
+                '\\' if self.mode() == Some(Mode::String) => {
+                    let escape_start = self.cur_position;
+                    match self.read_escape_sequence() {
+                        Ok(c) => value.push(c),
+                        Err(sequence) if escape_start == start => {
+                            return Token::with_span(
+                                TokenType::InvalidEscape,
+                                &sequence,
+                                self.span(escape_start, self.cur_position),
+                            );
+                        }
+                        Err(_) => {
+                            // Lex the invalid sequence as its own token on the next call
+                            self.seek(escape_start);
+                            break;
+                        }
+                    }
+                }
+                c => {
+                    value.push(c);
+                    self.read_char();
+                }
+            }
+        }
+        Token::with_span(
+            TokenType::StringValue,
+            &value,
+            self.span(start, self.cur_position),
+        )
+    }
+
+    /// Reads an escape sequence starting at the backslash in `cur_char`, leaving `cur_char` on the
+    /// character after it. Returns the character the sequence stands for, or the text of the
+    /// sequence if it isn't valid.
+    ///
+    /// Supported sequences are `\n`, `\t`, `\r`, `\0`, `\"`, `\\`, `\$` and `\u{...}`, where `...`
+    /// is 1 to 6 hex digits naming a Unicode scalar value.
+    fn read_escape_sequence(&mut self) -> Result<char, String> {
+        self.read_char(); // consume the '\'
+        let escaped = self.cur_char;
+        if self.cur_position < self.input.len() {
             self.read_char();
         }
-        ret
+        match escaped {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            '0' => Ok('\0'),
+            '"' => Ok('"'),
+            '$' => Ok('$'),
+            '\\' => Ok('\\'),
+            'u' => {
+                let mut sequence = "\\u".to_string();
+                if self.cur_char != '{' {
+                    return Err(sequence);
+                }
+                sequence.push('{');
+                self.read_char();
+                // Read every alphanumeric character so that the whole sequence is reported if
+                // it contains something other than hex digits
+                let mut digits = String::new();
+                while self.cur_char.is_ascii_alphanumeric() {
+                    digits.push(self.cur_char);
+                    self.read_char();
+                }
+                sequence.push_str(&digits);
+                if self.cur_char != '}' {
+                    return Err(sequence);
+                }
+                sequence.push('}');
+                self.read_char();
+                if digits.is_empty()
+                    || digits.len() > 6
+                    || !digits.chars().all(|c| c.is_ascii_hexdigit())
+                {
+                    return Err(sequence);
+                }
+                u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or(sequence)
+            }
+            '\0' => Err("\\".to_string()),
+            c => Err(format!("\\{c}")),
+        }
+    }
+
+    fn mode(&self) -> Option<Mode> {
+        self.modes.last().copied()
+    }
+
+    /// Returns true if the lexer is reading the contents of a string, rather than code.
+    fn in_string(&self) -> bool {
+        matches!(self.mode(), Some(Mode::String | Mode::RawString))
+    }
+
+    /// Moves the lexer back to `position`, so lexing continues from there.
+    fn seek(&mut self, position: usize) {
+        self.cur_position = position;
+        self.read_position = position + 1;
+        self.cur_char = self.input.get(position).copied().unwrap_or('\0');
+    }
+}
+
+/// Returns `value` as it would be written in a string literal, without the surrounding quotes.
+/// This is the inverse of the escape sequence decoding done while lexing, so quotes, backslashes,
+/// control characters and the `$` of a `${` are escaped.
+pub fn escape_string(value: &str) -> String {
+    let mut ret = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '$' if chars.peek() == Some(&'{') => ret.push_str("\\$"),
+            '\n' => ret.push_str("\\n"),
+            '\t' => ret.push_str("\\t"),
+            '\r' => ret.push_str("\\r"),
+            '\0' => ret.push_str("\\0"),
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            c if c.is_control() => ret.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => ret.push(c),
+        }
     }
+    ret
 }
@@ -1,7 +1,7 @@
 #[cfg(test)]
-use crate::lexer::Lexer;
+use crate::lexer::{Lexer, escape_string};
 #[cfg(test)]
-use crate::token::TokenType;
+use crate::token::{Span, TokenType};
 
 #[test]
 fn test_next_token_simple() {
@@ -201,3 +201,358 @@ fn test_next_token_complex() {
         assert_eq!(token.literal, expected_token_literals[i]);
     }
 }
+
+#[test]
+fn test_next_token_operators() {
+    let input = "% ** * & && | || ^ << <= < >> >= >";
+
+    let mut lexer = Lexer::new(input);
+
+    let expected_token_types = [
+        TokenType::Percent,
+        TokenType::DoubleStar,
+        TokenType::Star,
+        TokenType::Ampersand,
+        TokenType::LogicalAnd,
+        TokenType::Pipe,
+        TokenType::LogicalOr,
+        TokenType::Caret,
+        TokenType::LShift,
+        TokenType::LessEq,
+        TokenType::LArrow,
+        TokenType::RShift,
+        TokenType::GreaterEq,
+        TokenType::RArrow,
+        TokenType::Eof,
+    ];
+
+    for expected in expected_token_types {
+        assert_eq!(lexer.next_token().token_type, expected);
+    }
+}
+
+#[test]
+fn test_loop_keywords() {
+    let input = "while for in break continue inside";
+
+    let mut lexer = Lexer::new(input);
+
+    let expected_token_types = [
+        TokenType::While,
+        TokenType::For,
+        TokenType::In,
+        TokenType::Break,
+        TokenType::Continue,
+        TokenType::Ident,
+        TokenType::Eof,
+    ];
+
+    for expected in expected_token_types {
+        assert_eq!(lexer.next_token().token_type, expected);
+    }
+}
+
+#[test]
+fn test_let_keywords() {
+    let input = "let rec mut and android";
+
+    let mut lexer = Lexer::new(input);
+
+    let expected_token_types = [
+        TokenType::Let,
+        TokenType::Rec,
+        TokenType::Mut,
+        TokenType::And,
+        TokenType::Ident,
+        TokenType::Eof,
+    ];
+
+    for expected in expected_token_types {
+        assert_eq!(lexer.next_token().token_type, expected);
+    }
+}
+
+#[test]
+fn test_ellipsis() {
+    let input = "[a, ...rest] 1..2 .";
+
+    let mut lexer = Lexer::new(input);
+
+    let expected_tokens = [
+        (TokenType::LSquare, "["),
+        (TokenType::Ident, "a"),
+        (TokenType::Comma, ","),
+        (TokenType::Ellipsis, "..."),
+        (TokenType::Ident, "rest"),
+        (TokenType::RSquare, "]"),
+    ];
+
+    for (expected_type, expected_literal) in expected_tokens {
+        let token = lexer.next_token();
+        assert_eq!(token.token_type, expected_type);
+        assert_eq!(token.literal, expected_literal);
+    }
+}
+
+#[test]
+fn test_match_tokens() {
+    let input = "match x { _ => 1 } == =";
+
+    let mut lexer = Lexer::new(input);
+
+    let expected_token_types = [
+        TokenType::Match,
+        TokenType::Ident,
+        TokenType::LBrace,
+        TokenType::Ident,
+        TokenType::FatArrow,
+        TokenType::Int,
+        TokenType::RBrace,
+        TokenType::Eq,
+        TokenType::Assign,
+        TokenType::Eof,
+    ];
+
+    for expected in expected_token_types {
+        assert_eq!(lexer.next_token().token_type, expected);
+    }
+}
+
+#[test]
+fn test_record_tokens() {
+    let input = "struct P { x } p.x p with { x: .5 }";
+
+    let mut lexer = Lexer::new(input);
+
+    let expected_tokens = [
+        (TokenType::Struct, "struct"),
+        (TokenType::Ident, "P"),
+        (TokenType::LBrace, "{"),
+        (TokenType::Ident, "x"),
+        (TokenType::RBrace, "}"),
+        (TokenType::Ident, "p"),
+        (TokenType::Dot, "."),
+        (TokenType::Ident, "x"),
+        (TokenType::Ident, "p"),
+        (TokenType::With, "with"),
+        (TokenType::LBrace, "{"),
+        (TokenType::Ident, "x"),
+        (TokenType::Colon, ":"),
+        (TokenType::Float, ".5"),
+        (TokenType::RBrace, "}"),
+        (TokenType::Eof, ""),
+    ];
+
+    for (token_type, literal) in expected_tokens {
+        let token = lexer.next_token();
+        assert_eq!(token.token_type, token_type);
+        assert_eq!(token.literal, literal);
+    }
+}
+
+#[test]
+fn test_token_spans() {
+    let input = "let x = 5;\n# comment\n  x + \"héllo\";";
+
+    let mut lexer = Lexer::new(input);
+
+    // (start, end, line, column) of each token
+    let expected_spans = [
+        (0, 3, 1, 1),
+        (4, 5, 1, 5),
+        (6, 7, 1, 7),
+        (8, 9, 1, 9),
+        (9, 10, 1, 10),
+        (23, 24, 3, 3),
+        (25, 26, 3, 5),
+        (27, 28, 3, 7),
+        (28, 34, 3, 8),
+        (34, 35, 3, 13),
+        (35, 36, 3, 14),
+        (36, 36, 3, 15),
+    ];
+
+    for (start, end, line, column) in expected_spans {
+        let token = lexer.next_token();
+        assert_eq!(
+            token.span,
+            Span::new(start, end, line, column),
+            "Wrong span for token {:?}",
+            token.token_type
+        );
+    }
+}
+
+#[test]
+fn test_identifier_at_end_of_input() {
+    let mut lexer = Lexer::new("foo");
+    let token = lexer.next_token();
+    assert_eq!(token.token_type, TokenType::Ident);
+    assert_eq!(token.literal, "foo");
+    assert_eq!(lexer.next_token().token_type, TokenType::Eof);
+}
+
+#[test]
+fn test_string_escapes_and_errors() {
+    let input = r#""a\n" "x\qy" r"\n" "open"#;
+
+    let mut lexer = Lexer::new(input);
+
+    let expected_tokens = [
+        (TokenType::DoubleQuotation, "\""),
+        (TokenType::StringValue, "a\n"),
+        (TokenType::DoubleQuotation, "\""),
+        (TokenType::DoubleQuotation, "\""),
+        (TokenType::StringValue, "x"),
+        (TokenType::InvalidEscape, "\\q"),
+        (TokenType::StringValue, "y"),
+        (TokenType::DoubleQuotation, "\""),
+        (TokenType::DoubleQuotation, "r\""),
+        (TokenType::StringValue, "\\n"),
+        (TokenType::DoubleQuotation, "\""),
+        (TokenType::DoubleQuotation, "\""),
+        (TokenType::UnterminatedString, "open"),
+        (TokenType::Eof, ""),
+    ];
+
+    for (token_type, literal) in expected_tokens {
+        let token = lexer.next_token();
+        assert_eq!(token.token_type, token_type);
+        assert_eq!(token.literal, literal);
+    }
+}
+
+#[test]
+fn test_escape_string() {
+    let tests = [
+        ("plain", "plain"),
+        ("tab\tnewline\n", "tab\\tnewline\\n"),
+        ("\"quoted\" \\", "\\\"quoted\\\" \\\\"),
+        ("bell\u{7}", "bell\\u{7}"),
+        ("héllo 😀", "héllo 😀"),
+        ("${x} $5", "\\${x} $5"),
+    ];
+
+    for (value, expected) in tests {
+        assert_eq!(escape_string(value), expected);
+    }
+}
+
+#[test]
+fn test_string_interpolation() {
+    let input = r#""a ${x + f("b ${y}")} { \${c}" ${"#;
+
+    let mut lexer = Lexer::new(input);
+
+    let expected_tokens = [
+        (TokenType::DoubleQuotation, "\""),
+        (TokenType::StringValue, "a "),
+        (TokenType::InterpolationStart, "${"),
+        (TokenType::Ident, "x"),
+        (TokenType::Plus, "+"),
+        (TokenType::Ident, "f"),
+        (TokenType::LParen, "("),
+        (TokenType::DoubleQuotation, "\""),
+        (TokenType::StringValue, "b "),
+        (TokenType::InterpolationStart, "${"),
+        (TokenType::Ident, "y"),
+        (TokenType::InterpolationEnd, "}"),
+        (TokenType::DoubleQuotation, "\""),
+        (TokenType::RParen, ")"),
+        (TokenType::InterpolationEnd, "}"),
+        (TokenType::StringValue, " { ${c}"),
+        (TokenType::DoubleQuotation, "\""),
+        // Outside of a string, `$` is just an illegal character
+        (TokenType::Illegal, "$"),
+        (TokenType::LBrace, "{"),
+        (TokenType::Eof, ""),
+    ];
+
+    for (token_type, literal) in expected_tokens {
+        let token = lexer.next_token();
+        assert_eq!(token.token_type, token_type);
+        assert_eq!(token.literal, literal);
+    }
+}
+
+#[test]
+fn test_braces_inside_interpolation() {
+    let input = r#""${fun() { 1 }()}""#;
+
+    let mut lexer = Lexer::new(input);
+
+    let expected_token_types = [
+        TokenType::DoubleQuotation,
+        TokenType::InterpolationStart,
+        TokenType::Function,
+        TokenType::LParen,
+        TokenType::RParen,
+        TokenType::LBrace,
+        TokenType::Int,
+        TokenType::RBrace,
+        TokenType::LParen,
+        TokenType::RParen,
+        TokenType::InterpolationEnd,
+        TokenType::DoubleQuotation,
+        TokenType::Eof,
+    ];
+
+    for token_type in expected_token_types {
+        assert_eq!(lexer.next_token().token_type, token_type);
+    }
+}
+
+#[test]
+fn test_number_literals() {
+    let input = "3.14 .5 1e-9 2.5E+3 7 1. 2e x";
+
+    let mut lexer = Lexer::new(input);
+
+    let expected_tokens = [
+        (TokenType::Float, "3.14"),
+        (TokenType::Float, ".5"),
+        (TokenType::Float, "1e-9"),
+        (TokenType::Float, "2.5E+3"),
+        (TokenType::Int, "7"),
+        // A `.` or exponent without digits after it isn't part of the number
+        (TokenType::Int, "1"),
+        (TokenType::Dot, "."),
+        (TokenType::Int, "2"),
+        (TokenType::Ident, "e"),
+        (TokenType::Ident, "x"),
+        (TokenType::Eof, ""),
+    ];
+
+    for (token_type, literal) in expected_tokens {
+        let token = lexer.next_token();
+        assert_eq!(token.token_type, token_type);
+        assert_eq!(token.literal, literal);
+    }
+}
+
+#[test]
+fn test_prefixed_number_literals() {
+    let input = "0x1F 0o17 0b1010 1_000 0xZZ 0b 1_ 0x1.5";
+
+    let mut lexer = Lexer::new(input);
+
+    // Malformed literals are lexed whole, and reported by the parser
+    let expected_tokens = [
+        (TokenType::Int, "0x1F"),
+        (TokenType::Int, "0o17"),
+        (TokenType::Int, "0b1010"),
+        (TokenType::Int, "1_000"),
+        (TokenType::Int, "0xZZ"),
+        (TokenType::Int, "0b"),
+        (TokenType::Int, "1_"),
+        (TokenType::Int, "0x1"),
+        (TokenType::Float, ".5"),
+        (TokenType::Eof, ""),
+    ];
+
+    for (token_type, literal) in expected_tokens {
+        let token = lexer.next_token();
+        assert_eq!(token.token_type, token_type);
+        assert_eq!(token.literal, literal);
+    }
+}
@@ -0,0 +1,12 @@
+pub mod ast;
+// The bytecode compiler is a work in progress and isn't wired up to the CLI yet
+#[allow(dead_code)]
+pub mod code;
+#[allow(dead_code)]
+pub mod compiler;
+pub mod diagnostics;
+pub mod evaluator;
+pub mod lexer;
+pub mod object;
+pub mod parser;
+pub mod token;
@@ -1,20 +1,12 @@
-mod ast;
-mod code;
-mod compiler;
-mod evaluator;
-mod lexer;
-mod object;
-mod parser;
-mod token;
-
 use std::env;
 use std::fs;
 use std::io::{self, Write};
 use std::process;
 
-use evaluator::{Evaluator, environment::Environment};
-use lexer::Lexer;
-use parser::{Parser, has_parser_errors};
+use donkey_lang::diagnostics::{Diagnostic, render_traceback, use_color};
+use donkey_lang::evaluator::{Evaluator, EvaluatorError, environment::Environment};
+use donkey_lang::lexer::Lexer;
+use donkey_lang::parser::{ParseError, parse};
 
 fn execute_file(filename: &str) {
     let contents = match fs::read_to_string(filename) {
@@ -25,12 +17,9 @@ fn execute_file(filename: &str) {
         }
     };
 
-    let lexer = Lexer::new(&contents);
-    let mut parser = Parser::new(lexer);
-    let program = parser.parse_program();
+    let (program, errors) = parse(Lexer::new(&contents));
 
-    if has_parser_errors(&parser) {
-        eprintln!("Parser errors found in file '{filename}'");
+    if report_parser_errors(&errors, filename, &contents) {
         process::exit(1);
     }
 
@@ -39,7 +28,7 @@ fn execute_file(filename: &str) {
     match evaluator.eval(&program, &mut env) {
         Ok(_) => {}
         Err(e) => {
-            eprintln!("Runtime error: {}", e.error_message);
+            report_runtime_error(&e, filename, &contents);
             process::exit(1);
         }
     }
@@ -59,18 +48,14 @@ fn run_repl() {
                 break;
             }
             Ok(_) => {
-                let lexer = Lexer::new(&input_string);
-                let mut parser = Parser::new(lexer);
-                let program = parser.parse_program();
-                if !has_parser_errors(&parser) {
+                let (program, errors) = parse(Lexer::new(&input_string));
+                if !report_parser_errors(&errors, "<repl>", &input_string) {
                     let mut evaluator = Evaluator::new();
                     match evaluator.eval(&program, &mut env) {
                         Ok(value) => {
                             println!("{}", value.inspect());
                         }
-                        Err(e) => {
-                            println!("Error evaluating program: {}", e.error_message)
-                        }
+                        Err(e) => report_runtime_error(&e, "<repl>", &input_string),
                     }
                 }
             }
@@ -82,6 +67,31 @@ fn run_repl() {
     }
 }
 
+/// Prints every error the parser encountered to stderr. Returns true if there were any errors.
+fn report_parser_errors(errors: &[ParseError], filename: &str, source: &str) -> bool {
+    for error in errors {
+        eprintln!(
+            "{}",
+            Diagnostic::from(error).render(filename, source, use_color())
+        );
+    }
+    if errors.len() > 1 {
+        eprintln!("Found {} parse errors in '{filename}'", errors.len());
+    }
+    !errors.is_empty()
+}
+
+/// Prints a runtime error to stderr, preceded by a traceback if it occurred inside a function call
+fn report_runtime_error(error: &EvaluatorError, filename: &str, source: &str) {
+    if let Some(traceback) = render_traceback(error, filename, source) {
+        eprintln!("{traceback}");
+    }
+    eprintln!(
+        "{}",
+        Diagnostic::from(error).render(filename, source, use_color())
+    );
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
 
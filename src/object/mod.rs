@@ -1,252 +1,403 @@
 use std::any::Any;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
-use dyn_clone::DynClone;
+use im_rc::Vector;
+use indexmap::IndexMap;
+use num_bigint::BigInt;
 
-use crate::ast::{BlockStatement, Identifier, Node};
+use crate::ast::{BlockStatement, Node, Pattern};
 use crate::evaluator::EvaluatorError;
 use crate::evaluator::environment::Environment;
+use crate::lexer::escape_string;
 
-pub trait Object: Any + DynClone {
-    fn as_any(&self) -> &dyn Any;
-    fn inspect(&self) -> String;
-}
-
-dyn_clone::clone_trait_object!(Object);
-
-// ========== Integer Start ==========
+// ========== Value Start ==========
 
+/// A runtime value. Numbers, booleans and null are stored inline, while strings, arrays, hashes
+/// and functions are shared behind an `Rc`, so cloning a value never copies its contents. Donkey
+/// values are immutable, so sharing them is never observable.
 #[derive(Clone)]
-pub struct Integer {
-    pub value: i64,
+pub enum Value {
+    Integer(i64),
+    // An integer outside the range of i64. Arithmetic on Integers promotes to BigIntegers when it
+    // overflows, so to users there is a single integer type.
+    BigInteger(Rc<BigInt>),
+    Float(f64),
+    Boolean(bool),
+    Null,
+    String(Rc<str>),
+    /// A persistent vector, so `push`, `tail`, indexing, slicing and concatenation share
+    /// structure with the original array and take O(log n) time rather than copying it.
+    Array(Rc<Vector<Value>>),
+    /// A map from keys to values, which remembers the order keys were first inserted in.
+    Hash(Rc<IndexMap<HashKey, Value>>),
+    /// The integers from `start` up to but not including `end`, created by the `range` builtin.
+    /// Ranges are lazy, so iterating over a large range doesn't allocate an array.
+    Range {
+        start: i64,
+        end: i64,
+    },
+    Function(Rc<Function>),
+    Builtin(BuiltinFunction),
+    /// A record type declared with `struct`
+    Struct(Rc<Struct>),
+    Record(Rc<Record>),
+    /// A value of a type defined by the host program rather than by the language.
+    Object(Rc<dyn Object>),
 }
 
-impl Object for Integer {
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
+// Values are copied on and off the evaluator's value stack constantly, so they're kept small.
+// Anything larger than a range or a fat pointer belongs behind an `Rc`.
+const _: () = assert!(std::mem::size_of::<Value>() == 24);
 
-    fn inspect(&self) -> String {
-        self.value.to_string()
+impl Value {
+    pub fn string(value: impl Into<Rc<str>>) -> Self {
+        Value::String(value.into())
     }
-}
 
-impl Integer {
-    pub fn new(value: i64) -> Self {
-        Self { value }
+    pub fn array(items: Vector<Value>) -> Self {
+        Value::Array(Rc::new(items))
     }
-}
 
-// ========== Integer End ==========
-
-// ========== Boolean Start ==========
-
-#[derive(Clone)]
-pub struct Boolean {
-    pub value: bool,
-}
-
-impl Object for Boolean {
-    fn as_any(&self) -> &dyn Any {
-        self
+    pub fn hash(pairs: IndexMap<HashKey, Value>) -> Self {
+        Value::Hash(Rc::new(pairs))
     }
 
-    fn inspect(&self) -> String {
-        self.value.to_string()
+    /// The name of the value's type, as shown to users in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Integer(_) | Value::BigInteger(_) => "integer",
+            Value::Float(_) => "float",
+            Value::Boolean(_) => "boolean",
+            Value::Null => "null",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Hash(_) => "hash",
+            Value::Range { .. } => "range",
+            Value::Function(_) => "function",
+            Value::Builtin(_) => "builtin function",
+            Value::Struct(_) => "struct",
+            Value::Record(_) => "record",
+            Value::Object(object) => object.type_name(),
+        }
     }
-}
 
-impl Boolean {
-    pub fn new(value: bool) -> Self {
-        Self { value }
+    pub fn inspect(&self) -> String {
+        // Arrays, hashes and records are written out with an explicit stack of the parts left to
+        // write, rather than recursively, so deeply nested values don't overflow the Rust stack
+        let mut ret = String::new();
+        let mut parts = vec![InspectPart::Value(self)];
+        while let Some(part) = parts.pop() {
+            let value = match part {
+                InspectPart::Text(text) => {
+                    ret.push_str(text);
+                    continue;
+                }
+                InspectPart::Key(key) => {
+                    ret.push_str(&key.to_value().inspect());
+                    ret.push_str(": ");
+                    continue;
+                }
+                InspectPart::Value(value) => value,
+            };
+            // The parts are pushed in reverse, since the last one pushed is written first
+            match value {
+                Value::Array(items) => {
+                    parts.push(InspectPart::Text("]"));
+                    for (index, item) in items.iter().enumerate().rev() {
+                        parts.push(InspectPart::Value(item));
+                        if index > 0 {
+                            parts.push(InspectPart::Text(", "));
+                        }
+                    }
+                    parts.push(InspectPart::Text("["));
+                }
+                Value::Hash(pairs) => {
+                    parts.push(InspectPart::Text("}"));
+                    for (index, (key, value)) in pairs.iter().enumerate().rev() {
+                        parts.push(InspectPart::Value(value));
+                        parts.push(InspectPart::Key(key));
+                        if index > 0 {
+                            parts.push(InspectPart::Text(", "));
+                        }
+                    }
+                    parts.push(InspectPart::Text("{"));
+                }
+                Value::Record(record) if record.values.is_empty() => {
+                    ret.push_str(&format!("{} {{}}", record.struct_type.name));
+                }
+                Value::Record(record) => {
+                    parts.push(InspectPart::Text(" }"));
+                    let fields = record.struct_type.fields.iter().zip(&record.values);
+                    for (index, (field, value)) in fields.enumerate().rev() {
+                        parts.push(InspectPart::Value(value));
+                        parts.push(InspectPart::Text(": "));
+                        parts.push(InspectPart::Text(field));
+                        if index > 0 {
+                            parts.push(InspectPart::Text(", "));
+                        }
+                    }
+                    parts.push(InspectPart::Text(" { "));
+                    parts.push(InspectPart::Text(&record.struct_type.name));
+                }
+                value => ret.push_str(&value.inspect_scalar()),
+            }
+        }
+        ret
     }
-}
-
-// ========== Boolean End ==========
-
-// ========== Array Start ==========
-
-#[derive(Clone)]
-pub struct Array {
-    pub items: Vec<Box<dyn Object>>,
-}
 
-impl Object for Array {
-    fn as_any(&self) -> &dyn Any {
-        self
+    /// Inspects a value that doesn't contain other values
+    fn inspect_scalar(&self) -> String {
+        match self {
+            Value::Integer(value) => value.to_string(),
+            Value::BigInteger(value) => value.to_string(),
+            // Debug formatting always includes a fractional part or exponent, so floats can be
+            // told apart from integers, eg. `3.0` and `1e-9`
+            Value::Float(value) => format!("{value:?}"),
+            Value::Boolean(value) => value.to_string(),
+            Value::Null => "null".to_string(),
+            Value::String(value) => format!("\"{}\"", escape_string(value)),
+            Value::Range { start, end } => format!("range({start}, {end})"),
+            Value::Function(function) => function.inspect(),
+            Value::Builtin(_) => "builtin_function".to_string(),
+            Value::Struct(struct_type) => struct_type.inspect(),
+            Value::Object(object) => object.inspect(),
+            Value::Array(_) | Value::Hash(_) | Value::Record(_) => self.inspect(),
+        }
     }
 
-    fn inspect(&self) -> String {
-        let items_string = self
-            .items
-            .iter()
-            .map(|item| item.inspect())
-            .collect::<Vec<String>>()
-            .join(", ");
-        format!("[{items_string}]")
+    /// How the value is shown by print, println and string interpolation. Unlike `inspect`, this
+    /// shows strings as their contents, without quotes or escapes.
+    pub fn display(&self) -> String {
+        match self {
+            Value::String(value) => value.to_string(),
+            Value::Object(object) => object.display(),
+            value => value.inspect(),
+        }
     }
 }
 
-impl Array {
-    pub fn new(items: Vec<Box<dyn Object>>) -> Self {
-        Self { items }
-    }
+/// A part of a value that's left to be written by `Value::inspect`
+enum InspectPart<'a> {
+    Value(&'a Value),
+    Key(&'a HashKey),
+    Text(&'a str),
 }
 
-// ========== Array End ==========
+// How deeply nested values are dropped recursively, before the rest are set aside to be dropped
+// by the outermost drop
+const MAX_DROP_DEPTH: usize = 100;
 
-// ========== String Start ==========
-
-#[derive(Clone)]
-pub struct StringObject {
-    pub value: String,
+thread_local! {
+    static DROP_DEPTH: Cell<usize> = const { Cell::new(0) };
+    static DEFERRED_DROPS: RefCell<Vec<Value>> = const { RefCell::new(Vec::new()) };
 }
 
-impl Object for StringObject {
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
-
-    fn inspect(&self) -> String {
-        format!("\"{}\"", self.value)
+/// Dropping a value drops the values nested in it, so dropping a deeply nested array recursively
+/// would overflow the Rust stack. Values nested too deeply are set aside instead, and dropped one
+/// at a time once the outermost drop has finished.
+impl Drop for Value {
+    fn drop(&mut self) {
+        // Only values whose contents are freed along with them can nest drops
+        let nested = match self {
+            Value::Array(items) => Rc::strong_count(items) == 1 && !items.is_empty(),
+            Value::Hash(pairs) => Rc::strong_count(pairs) == 1 && !pairs.is_empty(),
+            Value::Record(record) => Rc::strong_count(record) == 1 && !record.values.is_empty(),
+            _ => false,
+        };
+        if !nested {
+            return;
+        }
+        let depth = DROP_DEPTH.get();
+        if depth >= MAX_DROP_DEPTH {
+            let value = std::mem::replace(self, Value::Null);
+            DEFERRED_DROPS.with_borrow_mut(|deferred| deferred.push(value));
+            return;
+        }
+        DROP_DEPTH.set(depth + 1);
+        match self {
+            Value::Array(items) => {
+                if let Some(items) = Rc::get_mut(items) {
+                    drop(std::mem::take(items));
+                }
+            }
+            Value::Hash(pairs) => {
+                if let Some(pairs) = Rc::get_mut(pairs) {
+                    drop(std::mem::take(pairs));
+                }
+            }
+            Value::Record(record) => {
+                if let Some(record) = Rc::get_mut(record) {
+                    drop(std::mem::take(&mut record.values));
+                }
+            }
+            _ => {}
+        }
+        if depth == 0 {
+            while let Some(value) = DEFERRED_DROPS.with_borrow_mut(Vec::pop) {
+                drop(value);
+            }
+        }
+        DROP_DEPTH.set(depth);
     }
 }
 
-impl StringObject {
-    pub fn new(value: String) -> Self {
-        Self { value }
+// ========== Value End ==========
+
+// ========== Object Start ==========
+
+/// An extension point for values of types defined by the host program. Host objects are wrapped
+/// in `Value::Object`, and can be passed around and printed by Donkey code like any other value.
+pub trait Object: Any {
+    fn as_any(&self) -> &dyn Any;
+    fn inspect(&self) -> String;
+    // The name of the object's type, as shown to users in error messages
+    fn type_name(&self) -> &'static str;
+    // How the object is shown by print, println and string interpolation
+    fn display(&self) -> String {
+        self.inspect()
     }
 }
 
-// ========== String End ==========
-
-// ========== Null Start ==========
+// ========== Object End ==========
 
-#[derive(Clone)]
-pub struct Null {}
+// ========== HashKey Start ==========
 
-impl Object for Null {
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
+/// The values that can be used as keys in a hash. Floats, arrays, hashes and functions can't be
+/// used as keys.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum HashKey {
+    Integer(i64),
+    // Only ever holds values outside the range of i64, like Value::BigInteger
+    BigInt(BigInt),
+    String(Rc<str>),
+    Boolean(bool),
+    Null,
+}
 
-    fn inspect(&self) -> String {
-        "null".to_string()
+impl HashKey {
+    /// Returns None if `value` can't be used as a key.
+    pub fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Integer(value) => Some(HashKey::Integer(*value)),
+            Value::BigInteger(value) => Some(HashKey::BigInt(value.as_ref().clone())),
+            Value::String(value) => Some(HashKey::String(value.clone())),
+            Value::Boolean(value) => Some(HashKey::Boolean(*value)),
+            Value::Null => Some(HashKey::Null),
+            _ => None,
+        }
     }
-}
 
-impl Null {
-    pub fn new() -> Self {
-        Self {}
+    pub fn to_value(&self) -> Value {
+        match self {
+            HashKey::Integer(value) => Value::Integer(*value),
+            HashKey::BigInt(value) => Value::BigInteger(Rc::new(value.clone())),
+            HashKey::String(value) => Value::String(value.clone()),
+            HashKey::Boolean(value) => Value::Boolean(*value),
+            HashKey::Null => Value::Null,
+        }
     }
 }
 
-// ========== Null End ==========
+// ========== HashKey End ==========
 
 // ========== Function Start ==========
 
-#[derive(Clone)]
 pub struct Function {
-    pub parameters: Vec<Identifier>,
-    pub body: BlockStatement,
+    // The name the function was bound to with `let`, used in backtraces. None for anonymous
+    // functions.
+    pub name: Option<String>,
+    // Shared with the function literal the function was created from, so creating a closure
+    // doesn't copy its body
+    pub parameters: Rc<[Box<dyn Pattern>]>,
+    pub body: Rc<BlockStatement>,
     pub env: Environment,
 }
 
-impl Object for Function {
-    fn as_any(&self) -> &dyn Any {
-        self
+impl Function {
+    pub fn new(
+        name: Option<String>,
+        parameters: Rc<[Box<dyn Pattern>]>,
+        body: Rc<BlockStatement>,
+        env: Environment,
+    ) -> Self {
+        Self {
+            name,
+            parameters,
+            body,
+            env,
+        }
     }
 
     fn inspect(&self) -> String {
         let parameter_string = self
             .parameters
             .iter()
-            .map(|identifier| identifier.string())
+            .map(|parameter| parameter.string())
             .collect::<Vec<_>>()
             .join(",");
         format!("fun({}) {}", parameter_string, self.body.string())
     }
 }
 
-impl Function {
-    pub fn new(parameters: &[Identifier], body: BlockStatement, env: Environment) -> Self {
-        Self {
-            parameters: parameters.to_vec(),
-            body,
-            env,
-        }
-    }
-}
-
 // ========== Function End ==========
 
-// ========== ReturnValue Start ==========
+// ========== Struct Start ==========
 
-#[derive(Clone)]
-pub struct ReturnValue {
-    pub value: Box<dyn Object>,
+pub struct Struct {
+    pub name: String,
+    pub fields: Vec<String>,
 }
 
-impl Object for ReturnValue {
-    fn as_any(&self) -> &dyn Any {
-        self
+impl Struct {
+    pub fn new(name: String, fields: Vec<String>) -> Self {
+        Self { name, fields }
     }
 
-    fn inspect(&self) -> String {
-        self.value.inspect()
+    /// The position of `field` in the struct's records, or None if it doesn't have that field
+    pub fn field_index(&self, field: &str) -> Option<usize> {
+        self.fields.iter().position(|other| other == field)
     }
-}
 
-impl ReturnValue {
-    pub fn new(value: Box<dyn Object>) -> Self {
-        Self { value }
+    fn inspect(&self) -> String {
+        if self.fields.is_empty() {
+            return format!("struct {} {{}}", self.name);
+        }
+        format!("struct {} {{ {} }}", self.name, self.fields.join(", "))
     }
 }
 
-// ========== ReturnValue End ==========
+// ========== Struct End ==========
 
-// ========== BuiltinFn Start ==========
+// ========== Record Start ==========
 
-#[derive(Clone)]
-pub struct BuiltinFn {
-    pub builtin_fn: Rc<dyn Fn(Vec<Box<dyn Object>>) -> Result<Box<dyn Object>, EvaluatorError>>,
+/// A value of a type declared with `struct`. It has a value for each of the struct's fields, in
+/// the order they were declared.
+pub struct Record {
+    pub struct_type: Rc<Struct>,
+    pub values: Vec<Value>,
 }
 
-impl Object for BuiltinFn {
-    fn as_any(&self) -> &dyn Any {
-        self
+impl Record {
+    pub fn new(struct_type: Rc<Struct>, values: Vec<Value>) -> Self {
+        Self {
+            struct_type,
+            values,
+        }
     }
 
-    fn inspect(&self) -> String {
-        "builtin_function".to_string()
+    /// The value of `field`, or None if the record doesn't have that field
+    pub fn get(&self, field: &str) -> Option<&Value> {
+        self.struct_type
+            .field_index(field)
+            .map(|index| &self.values[index])
     }
 }
 
-impl BuiltinFn {
-    pub fn new(
-        builtin_fn: Rc<dyn Fn(Vec<Box<dyn Object>>) -> Result<Box<dyn Object>, EvaluatorError>>,
-    ) -> Self {
-        Self { builtin_fn }
-    }
-}
+// ========== Record End ==========
 
-// ========== BuiltinFn End ==========
-
-#[derive(Clone)]
-pub struct SelfRef {}
+// ========== BuiltinFn Start ==========
 
-impl Object for SelfRef {
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
+pub type BuiltinFunction = Rc<dyn Fn(Vec<Value>) -> Result<Value, EvaluatorError>>;
 
-    fn inspect(&self) -> String {
-        "self_ref_obj".to_string()
-    }
-}
-
-impl SelfRef {
-    pub fn new() -> Self {
-        Self {}
-    }
-}
+// ========== BuiltinFn End ==========
@@ -1,11 +1,18 @@
 mod tests;
 
 use std::collections::HashMap;
+use std::fmt::{self, Display};
+
+use num_bigint::BigInt;
 
 use crate::ast::{
-    ArrayExpression, BlockStatement, BooleanLiteral, CallExpression, Expression,
-    ExpressionStatement, FunctionLiteral, IfExpression, IndexExpression, InfixExpression,
-    IntegerLiteral, NullLiteral, PrefixExpression, ReturnStatement, StringExpression,
+    ArrayExpression, ArrayPattern, AssignExpression, BigIntegerLiteral, BlockStatement,
+    BooleanLiteral, BreakStatement, CallExpression, ContinueStatement, Expression,
+    ExpressionStatement, FloatLiteral, ForStatement, FunctionLiteral, HashExpression, HashPattern,
+    IfExpression, IndexExpression, InfixExpression, IntegerLiteral, InterpolatedStringExpression,
+    LiteralPattern, MatchArm, MatchExpression, MemberExpression, NullLiteral, Pattern,
+    PrefixExpression, ReturnStatement, SliceExpression, StringExpression, StructStatement,
+    WhileStatement, WildcardPattern, WithExpression,
 };
 
 type PrefixParseFn = fn(&mut Parser) -> Option<Box<dyn Expression>>;
@@ -14,16 +21,205 @@ type InfixParseFn = fn(&mut Parser, Box<dyn Expression>) -> Option<Box<dyn Expre
 use crate::{
     ast::{Identifier, LetStatement, Program, Statement},
     lexer::Lexer,
-    token::{Token, TokenType},
+    token::{Span, Token, TokenType},
 };
 
+/// An error encountered while parsing. Every variant carries the span of the source code the
+/// error refers to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseError {
+    /// A specific token was required, but a different token was found
+    UnexpectedToken {
+        expected: TokenType,
+        found: TokenType,
+        span: Span,
+    },
+    /// A token that can't start an expression was found where an expression was expected
+    NoPrefixParseFn {
+        found: TokenType,
+        span: Span,
+    },
+    InvalidIntegerLiteral {
+        literal: String,
+        span: Span,
+    },
+    /// A numeric literal contains a character that isn't a digit in its base, eg. the `Z` in
+    /// `0xZZ`
+    InvalidDigit {
+        digit: char,
+        radix: u32,
+        span: Span,
+    },
+    /// A `_` digit separator isn't between two digits, eg. `1_` or `0x_1`
+    MisplacedSeparator {
+        span: Span,
+    },
+    /// A `0x`, `0o` or `0b` prefix isn't followed by any digits
+    MissingDigits {
+        prefix: String,
+        span: Span,
+    },
+    /// A float literal is too large to be represented
+    InvalidFloatLiteral {
+        literal: String,
+        span: Span,
+    },
+    InvalidBooleanLiteral {
+        literal: String,
+        span: Span,
+    },
+    /// Something other than an identifier, function literal or field was called
+    InvalidCallTarget {
+        span: Span,
+    },
+    /// The lexer found a character that isn't part of the language
+    IllegalCharacter {
+        character: String,
+        span: Span,
+    },
+    /// A string literal wasn't closed before the end of the input. The span starts at the opening
+    /// quote.
+    UnterminatedString {
+        span: Span,
+    },
+    /// A string literal contains a backslash escape sequence that isn't recognised
+    InvalidEscape {
+        sequence: String,
+        span: Span,
+    },
+    /// Something other than an identifier is on the left of an `=`
+    InvalidAssignmentTarget {
+        span: Span,
+    },
+    /// A `break` or `continue` statement isn't inside a loop in the same function
+    OutsideLoop {
+        keyword: TokenType,
+        span: Span,
+    },
+    /// A `let rec` statement binds something other than a function literal
+    RecNonFunction {
+        span: Span,
+    },
+    /// A pattern contains an expression that isn't a literal, eg. `-x` or an interpolated string
+    InvalidPattern {
+        span: Span,
+    },
+    /// The same field is named twice in a struct declaration or a `with` expression
+    DuplicateField {
+        field: String,
+        span: Span,
+    },
+    /// The same name is bound twice by a pattern, a function's parameters or the functions of a
+    /// `let rec` statement
+    DuplicateBinding {
+        name: String,
+        span: Span,
+    },
+}
+
+impl ParseError {
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::UnexpectedToken { span, .. }
+            | ParseError::NoPrefixParseFn { span, .. }
+            | ParseError::InvalidIntegerLiteral { span, .. }
+            | ParseError::InvalidFloatLiteral { span, .. }
+            | ParseError::InvalidDigit { span, .. }
+            | ParseError::MisplacedSeparator { span }
+            | ParseError::MissingDigits { span, .. }
+            | ParseError::InvalidBooleanLiteral { span, .. }
+            | ParseError::InvalidCallTarget { span }
+            | ParseError::InvalidAssignmentTarget { span }
+            | ParseError::RecNonFunction { span }
+            | ParseError::InvalidPattern { span }
+            | ParseError::DuplicateField { span, .. }
+            | ParseError::DuplicateBinding { span, .. }
+            | ParseError::IllegalCharacter { span, .. }
+            | ParseError::UnterminatedString { span }
+            | ParseError::InvalidEscape { span, .. }
+            | ParseError::OutsideLoop { span, .. } => *span,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            ParseError::UnexpectedToken {
+                expected, found, ..
+            } => format!("Expected {expected}, found {found} instead"),
+            ParseError::NoPrefixParseFn { found, .. } => {
+                format!("Expected an expression, found {found} instead")
+            }
+            ParseError::InvalidIntegerLiteral { literal, .. } => {
+                format!("Could not parse {literal} as integer")
+            }
+            ParseError::InvalidDigit { digit, radix, .. } => {
+                format!("Invalid digit `{digit}` in {} literal", radix_name(*radix))
+            }
+            ParseError::MisplacedSeparator { .. } => {
+                "Digit separator `_` must be between two digits".to_string()
+            }
+            ParseError::MissingDigits { prefix, .. } => {
+                format!("Expected digits after `{prefix}`")
+            }
+            ParseError::InvalidFloatLiteral { literal, .. } => {
+                format!("Could not parse {literal} as float")
+            }
+            ParseError::InvalidBooleanLiteral { literal, .. } => {
+                format!("Could not parse {literal} as a bool")
+            }
+            ParseError::InvalidCallTarget { .. } => {
+                "Expected function literal, identifier or field in call position".to_string()
+            }
+            ParseError::IllegalCharacter { character, .. } => {
+                format!("Illegal character `{character}`")
+            }
+            ParseError::UnterminatedString { .. } => "Unterminated string literal".to_string(),
+            ParseError::InvalidEscape { sequence, .. } => {
+                format!("Invalid escape sequence `{sequence}` in string literal")
+            }
+            ParseError::InvalidAssignmentTarget { .. } => {
+                "Only variables can be assigned to".to_string()
+            }
+            ParseError::OutsideLoop { keyword, .. } => format!("{keyword} outside of a loop"),
+            ParseError::RecNonFunction { .. } => {
+                "Only functions can be defined with `let rec`".to_string()
+            }
+            ParseError::InvalidPattern { .. } => {
+                "Only literal values can be matched in patterns".to_string()
+            }
+            ParseError::DuplicateField { field, .. } => format!("Duplicate field `{field}`"),
+            ParseError::DuplicateBinding { name, .. } => format!("Duplicate binding `{name}`"),
+        }
+    }
+}
+
+/// The name of a base that numeric literals can be written in, as used in error messages
+pub fn radix_name(radix: u32) -> &'static str {
+    match radix {
+        2 => "binary",
+        8 => "octal",
+        16 => "hexadecimal",
+        _ => "decimal",
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let span = self.span();
+        write!(f, "{}:{}: {}", span.line, span.column, self.message())
+    }
+}
+
 pub struct Parser {
     lexer: Lexer,
     cur_token: Token,
     peek_token: Token,
-    errors: Vec<String>,
+    errors: Vec<ParseError>,
     prefix_parse_functions: HashMap<TokenType, PrefixParseFn>,
     infix_parse_functions: HashMap<TokenType, InfixParseFn>,
+    // How many loops enclose the current token within the current function, used to reject
+    // `break` and `continue` outside of loops
+    loop_depth: usize,
 }
 
 impl Parser {
@@ -37,6 +233,7 @@ impl Parser {
             errors: Vec::new(),
             prefix_parse_functions: HashMap::new(),
             infix_parse_functions: HashMap::new(),
+            loop_depth: 0,
         };
         // Advance the parser by two tokens so
         // both cur_token and peek_token are populated
@@ -46,6 +243,7 @@ impl Parser {
         // Register the prefix functions
         parser.register_prefix_function(TokenType::Ident, |parser| parser.parse_identifier());
         parser.register_prefix_function(TokenType::Int, |parser| parser.parse_integer_literal());
+        parser.register_prefix_function(TokenType::Float, |parser| parser.parse_float_literal());
         parser.register_prefix_function(TokenType::Null, |parser| parser.parse_null());
         parser.register_prefix_function(TokenType::True, |parser| parser.parse_boolean_literal());
         parser.register_prefix_function(TokenType::False, |parser| parser.parse_boolean_literal());
@@ -57,13 +255,18 @@ impl Parser {
         });
         parser
             .register_prefix_function(TokenType::LSquare, |parser| parser.parse_array_expression());
+        // Blocks are parsed directly by the statements that contain them, so a `{` at the start of
+        // an expression is always a hash literal
+        parser.register_prefix_function(TokenType::LBrace, |parser| parser.parse_hash_expression());
         parser.register_prefix_function(TokenType::DoubleQuotation, |parser| {
             parser.parse_string_expression()
         });
         parser.register_prefix_function(TokenType::If, |parser| parser.parse_if_expression());
+        parser.register_prefix_function(TokenType::Match, |parser| parser.parse_match_expression());
         parser.register_prefix_function(TokenType::Function, |parser| {
             parser.parse_function_literal()
         });
+        parser.register_prefix_function(TokenType::Illegal, |parser| parser.parse_illegal());
 
         // Register the infix functions
         parser.register_infix_function(TokenType::Eq, |parser, left| {
@@ -96,23 +299,51 @@ impl Parser {
         parser.register_infix_function(TokenType::Slash, |parser, left| {
             parser.parse_infix_expression(left)
         });
+        for token_type in [
+            TokenType::Percent,
+            TokenType::DoubleStar,
+            TokenType::Ampersand,
+            TokenType::Pipe,
+            TokenType::Caret,
+            TokenType::LShift,
+            TokenType::RShift,
+            TokenType::LogicalAnd,
+            TokenType::LogicalOr,
+        ] {
+            parser.register_infix_function(token_type, |parser, left| {
+                parser.parse_infix_expression(left)
+            });
+        }
+        parser.register_infix_function(TokenType::Assign, |parser, left| {
+            parser.parse_assign_expression(left)
+        });
         parser.register_infix_function(TokenType::LParen, |parser, left| {
             parser.parse_call_expression(left)
         });
         parser.register_infix_function(TokenType::LSquare, |parser, left| {
             parser.parse_index_expression(left)
         });
+        parser.register_infix_function(TokenType::Dot, |parser, left| {
+            parser.parse_member_expression(left)
+        });
+        parser.register_infix_function(TokenType::With, |parser, left| {
+            parser.parse_with_expression(left)
+        });
         parser
     }
 
+    /// Parses the whole input. Parsing doesn't stop at the first error: statements that fail to
+    /// parse are skipped, and the returned program contains every statement that parsed
+    /// successfully. Use `into_errors` to see what went wrong.
     pub fn parse_program(&mut self) -> Program {
         let mut program = Program::new(Vec::new());
         while self.cur_token.token_type != TokenType::Eof {
             if let Some(statement) = self.parse_statement() {
                 program.statements.push(statement);
             } else {
-                // If we failed to parse the statement, then just skip to the end to avoid the bad tokens.
-                self.skip_to_statement_end();
+                // If we failed to parse the statement, then skip past the bad tokens to the start
+                // of the next statement.
+                self.synchronize(false);
             }
         }
         program
@@ -125,6 +356,10 @@ impl Parser {
         match self.cur_token.token_type {
             TokenType::Let => self.parse_let_statement(),
             TokenType::Return => self.parse_return_statement(),
+            TokenType::While => self.parse_while_statement(),
+            TokenType::For => self.parse_for_statement(),
+            TokenType::Struct => self.parse_struct_statement(),
+            TokenType::Break | TokenType::Continue => self.parse_loop_control_statement(),
             // Default case is assume we are parsing an expression statement
             _ => self.parse_expression_statement(),
         }
@@ -140,14 +375,27 @@ impl Parser {
         };
         self.next_token();
         let mut statements = Vec::new();
-        while self.peek_token.token_type != TokenType::Eof {
-            let statement = self.parse_statement()?;
-            statements.push(statement);
-            if self.cur_token.token_type == TokenType::RBrace {
-                break;
+        while self.cur_token.token_type != TokenType::RBrace {
+            if self.cur_token.token_type == TokenType::Eof {
+                self.errors.push(ParseError::UnexpectedToken {
+                    expected: TokenType::RBrace,
+                    found: TokenType::Eof,
+                    span: self.cur_token.span,
+                });
+                return None;
+            }
+            if let Some(statement) = self.parse_statement() {
+                statements.push(statement);
+            } else {
+                // Skip to the next statement in this block, so one bad statement doesn't make us
+                // lose the rest of the block.
+                self.synchronize(true);
             }
         }
-        Some(BlockStatement::new(token, statements))
+        let mut block_statement = BlockStatement::new(token, statements);
+        // cur_token points to the RBrace here
+        block_statement.span = block_statement.span.to(self.cur_token.span);
+        Some(block_statement)
     }
 
     // When this function is called, cur_token should be pointing to the Let
@@ -160,13 +408,58 @@ impl Parser {
             return None;
         };
         let rec = self.expect_peek(TokenType::Rec);
-        // If the next token is TokenType::Ident, then we advance the token pointers.
-        // Then `cur_token` points to the Identifier token.
-        let name = if self.expect_peek(TokenType::Ident) {
-            Identifier::new(self.cur_token.clone(), &self.cur_token.literal)
+        let mutable = self.expect_peek(TokenType::Mut);
+        let (pattern, value) = self.parse_let_binding(rec)?;
+        // `let rec` can define several functions that refer to each other, separated by `and`
+        let mut and_bindings = Vec::new();
+        if rec {
+            self.check_rec_value(value.as_ref());
+            while self.expect_peek(TokenType::And) {
+                let (and_pattern, value) = self.parse_let_binding(rec)?;
+                self.check_rec_value(value.as_ref());
+                if let Some(name) = and_pattern.as_any().downcast_ref::<Identifier>() {
+                    let names = std::iter::once(&pattern)
+                        .chain(and_bindings.iter().map(|(pattern, _)| pattern))
+                        .filter_map(|pattern| pattern.as_any().downcast_ref::<Identifier>());
+                    self.check_duplicate_binding(names, name);
+                }
+                and_bindings.push((and_pattern, value));
+            }
+        }
+        // A missing semicolon is reported, but the statement is still kept since it was
+        // otherwise parsed successfully.
+        if !self.expect_peek(TokenType::Semicolon) {
+            self.expect_error(TokenType::Semicolon);
+        }
+        // Advance token to the start of the next statement
+        self.next_token();
+        Some(Box::new(
+            LetStatement::new(token, pattern, value, rec, mutable).with_and_bindings(and_bindings),
+        ))
+    }
+
+    // Parses `pattern = value` in a let statement. When this function is called, cur_token should
+    // be pointing to the token before the pattern, and when it returns it points to the end of the
+    // value.
+    fn parse_let_binding(&mut self, rec: bool) -> Option<(Box<dyn Pattern>, Box<dyn Expression>)> {
+        // Recursive functions are bound to names, while other values can be destructured
+        let pattern: Box<dyn Pattern> = if rec {
+            // If the next token is TokenType::Ident, then we advance the token pointers.
+            // Then `cur_token` points to the Identifier token.
+            if self.expect_peek(TokenType::Ident) {
+                Box::new(Identifier::new(
+                    self.cur_token.clone(),
+                    &self.cur_token.literal,
+                ))
+            } else {
+                self.expect_error(TokenType::Ident);
+                return None;
+            }
         } else {
-            self.expect_error(TokenType::Ident);
-            return None;
+            self.next_token();
+            let pattern = self.parse_pattern()?;
+            self.check_pattern_bindings(std::iter::once(pattern.as_ref()));
+            pattern
         };
         // We expect an Assign token after the Identifier token. If present,
         // then consume it and advance the token pointers. Otherwise, return early.
@@ -177,14 +470,158 @@ impl Parser {
         // Advance token to start of expression
         self.next_token();
         let value = self.parse_expression(Precedence::Lowest as i32)?;
-        // Advance token to the semicolon
-        self.next_token();
-        if self.cur_token.token_type != TokenType::Semicolon {
-            self.expect_error(TokenType::Semicolon);
-        } else {
+        Some((pattern, value))
+    }
+
+    // When this function is called, cur_token should point to the start of the pattern. When it
+    // returns, cur_token should point to the end of the pattern.
+    fn parse_pattern(&mut self) -> Option<Box<dyn Pattern>> {
+        match self.cur_token.token_type {
+            TokenType::Ident if self.cur_token.literal == "_" => {
+                Some(Box::new(WildcardPattern::new(self.cur_token.clone())))
+            }
+            TokenType::Ident => Some(Box::new(Identifier::new(
+                self.cur_token.clone(),
+                &self.cur_token.literal,
+            ))),
+            TokenType::LSquare => self.parse_array_pattern(),
+            TokenType::LBrace => self.parse_hash_pattern(),
+            TokenType::Int
+            | TokenType::Float
+            | TokenType::DoubleQuotation
+            | TokenType::True
+            | TokenType::False
+            | TokenType::Null
+            | TokenType::Minus => self.parse_literal_pattern(),
+            found => {
+                self.errors.push(ParseError::UnexpectedToken {
+                    expected: TokenType::Ident,
+                    found,
+                    span: self.cur_token.span,
+                });
+                None
+            }
+        }
+    }
+
+    // Reports an error for each name that's bound again by `patterns` after it was already bound
+    fn check_pattern_bindings<'a>(&mut self, patterns: impl Iterator<Item = &'a dyn Pattern>) {
+        let mut names = Vec::new();
+        for pattern in patterns {
+            collect_bindings(pattern, &mut names);
+        }
+        for (index, name) in names.iter().enumerate() {
+            self.check_duplicate_binding(names[..index].iter().copied(), name);
+        }
+    }
+
+    // When this function is called, cur_token should point to the first token of the literal.
+    // When it returns, cur_token should point to the last token of the literal.
+    fn parse_literal_pattern(&mut self) -> Option<Box<dyn Pattern>> {
+        let token = self.cur_token.clone();
+        let value = self.parse_expression(Precedence::Prefix as i32)?;
+        let any = value.as_any();
+        let is_literal = any.is::<IntegerLiteral>()
+            || any.is::<BigIntegerLiteral>()
+            || any.is::<FloatLiteral>()
+            || any.is::<StringExpression>()
+            || any.is::<BooleanLiteral>()
+            || any.is::<NullLiteral>()
+            || any
+                .downcast_ref::<PrefixExpression>()
+                .is_some_and(|prefix_expression| {
+                    let right = prefix_expression.right.as_any();
+                    prefix_expression.operator == "-"
+                        && (right.is::<IntegerLiteral>()
+                            || right.is::<BigIntegerLiteral>()
+                            || right.is::<FloatLiteral>())
+                });
+        if !is_literal {
+            self.errors
+                .push(ParseError::InvalidPattern { span: value.span() });
+            return None;
+        }
+        Some(Box::new(LiteralPattern::new(token, value)))
+    }
+
+    // When this function is called, cur_token should point to the LSquare.
+    // When it returns, cur_token should point to the RSquare.
+    fn parse_array_pattern(&mut self) -> Option<Box<dyn Pattern>> {
+        let token = self.cur_token.clone();
+        let mut items = Vec::new();
+        let mut rest = None;
+        while !self.expect_peek(TokenType::RSquare) {
+            // The rest of the array can only be bound after every other item
+            if self.expect_peek(TokenType::Ellipsis) {
+                if !self.expect_peek(TokenType::Ident) {
+                    self.expect_error(TokenType::Ident);
+                    return None;
+                }
+                rest = Some(Identifier::new(
+                    self.cur_token.clone(),
+                    &self.cur_token.literal,
+                ));
+                self.expect_peek(TokenType::Comma);
+                if !self.expect_peek(TokenType::RSquare) {
+                    self.expect_error(TokenType::RSquare);
+                    return None;
+                }
+                break;
+            }
             self.next_token();
+            items.push(self.parse_pattern()?);
+            // Items are separated by commas, and the last item may be followed by one
+            if self.peek_token.token_type != TokenType::RSquare
+                && !self.expect_peek(TokenType::Comma)
+            {
+                self.expect_error(TokenType::Comma);
+                return None;
+            }
+        }
+        let mut array_pattern = ArrayPattern::new(token, items, rest);
+        array_pattern.span = array_pattern.span.to(self.cur_token.span);
+        Some(Box::new(array_pattern))
+    }
+
+    // When this function is called, cur_token should point to the LBrace.
+    // When it returns, cur_token should point to the RBrace.
+    fn parse_hash_pattern(&mut self) -> Option<Box<dyn Pattern>> {
+        let token = self.cur_token.clone();
+        let mut pairs = Vec::new();
+        while !self.expect_peek(TokenType::RBrace) {
+            if !self.expect_peek(TokenType::Ident) {
+                self.expect_error(TokenType::Ident);
+                return None;
+            }
+            let key = Identifier::new(self.cur_token.clone(), &self.cur_token.literal);
+            // A key without a pattern binds its value to a name the same as the key
+            let pattern: Box<dyn Pattern> = if self.expect_peek(TokenType::Colon) {
+                self.next_token();
+                self.parse_pattern()?
+            } else {
+                Box::new(key.clone())
+            };
+            pairs.push((key, pattern));
+            // Pairs are separated by commas, and the last pair may be followed by one
+            if self.peek_token.token_type != TokenType::RBrace
+                && !self.expect_peek(TokenType::Comma)
+            {
+                self.expect_error(TokenType::Comma);
+                return None;
+            }
+        }
+        let mut hash_pattern = HashPattern::new(token, pairs);
+        hash_pattern.span = hash_pattern.span.to(self.cur_token.span);
+        Some(Box::new(hash_pattern))
+    }
+
+    // Only functions can be recursive, since any other value would have to be evaluated before
+    // the name it refers to is bound
+    fn check_rec_value(&mut self, value: &dyn Expression) {
+        if !value.as_any().is::<FunctionLiteral>() {
+            self.errors
+                .push(ParseError::RecNonFunction { span: value.span() });
         }
-        Some(Box::new(LetStatement::new(token, name, value, rec)))
     }
 
     // When this function is called, self.cur_token should be pointing to a token with
@@ -200,14 +637,149 @@ impl Parser {
         // Advance token to start of expression
         self.next_token();
         let return_value = self.parse_expression(Precedence::Lowest as i32)?;
-        // Advance token token to the semicolon
-        self.next_token();
-        if self.cur_token.token_type != TokenType::Semicolon {
+        if !self.expect_peek(TokenType::Semicolon) {
             self.expect_error(TokenType::Semicolon);
+        }
+        // Advance token to the start of the next statement
+        self.next_token();
+        Some(Box::new(ReturnStatement::new(token, return_value)))
+    }
+
+    // When this function is called, cur_token should be pointing to the While
+    fn parse_while_statement(&mut self) -> Option<Box<dyn Statement>> {
+        let token = self.cur_token.clone();
+        self.next_token();
+        let condition = self.parse_expression(Precedence::Lowest as i32)?;
+        let body = self.parse_loop_body()?;
+        Some(Box::new(WhileStatement::new(token, condition, body)))
+    }
+
+    // When this function is called, cur_token should be pointing to the Struct. When it returns,
+    // cur_token points to the start of the next statement.
+    fn parse_struct_statement(&mut self) -> Option<Box<dyn Statement>> {
+        let token = self.cur_token.clone();
+        let name = if self.expect_peek(TokenType::Ident) {
+            Identifier::new(self.cur_token.clone(), &self.cur_token.literal)
         } else {
+            self.expect_error(TokenType::Ident);
+            return None;
+        };
+        if !self.expect_peek(TokenType::LBrace) {
+            self.expect_error(TokenType::LBrace);
+            return None;
+        }
+        let mut fields: Vec<Identifier> = Vec::new();
+        while !self.expect_peek(TokenType::RBrace) {
+            if !self.expect_peek(TokenType::Ident) {
+                self.expect_error(TokenType::Ident);
+                return None;
+            }
+            let field = Identifier::new(self.cur_token.clone(), &self.cur_token.literal);
+            self.check_duplicate_field(fields.iter(), &field);
+            fields.push(field);
+            // Fields are separated by commas, and the last field may be followed by one
+            if self.peek_token.token_type != TokenType::RBrace
+                && !self.expect_peek(TokenType::Comma)
+            {
+                self.expect_error(TokenType::Comma);
+                return None;
+            }
+        }
+        let mut struct_statement = StructStatement::new(token, name, fields);
+        struct_statement.span = struct_statement.span.to(self.cur_token.span);
+        // Advance token past the RBrace, and past an optional semicolon after it
+        self.next_token();
+        if self.cur_token.token_type == TokenType::Semicolon {
             self.next_token();
         }
-        Some(Box::new(ReturnStatement::new(token, return_value)))
+        Some(Box::new(struct_statement))
+    }
+
+    // Reports an error if `field` has the same name as one of `fields`
+    fn check_duplicate_field<'a>(
+        &mut self,
+        mut fields: impl Iterator<Item = &'a Identifier>,
+        field: &Identifier,
+    ) {
+        if fields.any(|other| other.value == field.value) {
+            self.errors.push(ParseError::DuplicateField {
+                field: field.value.clone(),
+                span: field.span,
+            });
+        }
+    }
+
+    // Reports an error if `name` is the same as one of `names`
+    fn check_duplicate_binding<'a>(
+        &mut self,
+        mut names: impl Iterator<Item = &'a Identifier>,
+        name: &Identifier,
+    ) {
+        if names.any(|other| other.value == name.value) {
+            self.errors.push(ParseError::DuplicateBinding {
+                name: name.value.clone(),
+                span: name.span,
+            });
+        }
+    }
+
+    // When this function is called, cur_token should be pointing to the For
+    fn parse_for_statement(&mut self) -> Option<Box<dyn Statement>> {
+        let token = self.cur_token.clone();
+        let variable = if self.expect_peek(TokenType::Ident) {
+            Identifier::new(self.cur_token.clone(), &self.cur_token.literal)
+        } else {
+            self.expect_error(TokenType::Ident);
+            return None;
+        };
+        if !self.expect_peek(TokenType::In) {
+            self.expect_error(TokenType::In);
+            return None;
+        }
+        self.next_token();
+        let iterable = self.parse_expression(Precedence::Lowest as i32)?;
+        let body = self.parse_loop_body()?;
+        Some(Box::new(ForStatement::new(token, variable, iterable, body)))
+    }
+
+    // When this function is called, peek_token should be pointing to the LBrace that starts the
+    // body of a loop. When it returns, cur_token points to the start of the next statement.
+    fn parse_loop_body(&mut self) -> Option<BlockStatement> {
+        if !self.expect_peek(TokenType::LBrace) {
+            self.expect_error(TokenType::LBrace);
+            return None;
+        }
+        self.loop_depth += 1;
+        let body = self.parse_block_statement();
+        self.loop_depth -= 1;
+        let body = body?;
+        // Advance token past the RBrace, and past an optional semicolon after it
+        self.next_token();
+        if self.cur_token.token_type == TokenType::Semicolon {
+            self.next_token();
+        }
+        Some(body)
+    }
+
+    // When this function is called, cur_token should be pointing to the Break or Continue
+    fn parse_loop_control_statement(&mut self) -> Option<Box<dyn Statement>> {
+        let token = self.cur_token.clone();
+        if self.loop_depth == 0 {
+            self.errors.push(ParseError::OutsideLoop {
+                keyword: token.token_type,
+                span: token.span,
+            });
+        }
+        if !self.expect_peek(TokenType::Semicolon) {
+            self.expect_error(TokenType::Semicolon);
+        }
+        // Advance token to the start of the next statement
+        self.next_token();
+        if token.token_type == TokenType::Break {
+            Some(Box::new(BreakStatement::new(token)))
+        } else {
+            Some(Box::new(ContinueStatement::new(token)))
+        }
     }
 
     fn parse_expression_statement(&mut self) -> Option<Box<dyn Statement>> {
@@ -286,23 +858,102 @@ impl Parser {
 
     fn parse_integer_literal(&mut self) -> Option<Box<dyn Expression>> {
         let token = self.cur_token.clone();
-        match token.literal.parse::<i64>() {
-            Ok(value) => Some(Box::new(IntegerLiteral::new(token, value))),
-            Err(_) => {
-                self.errors
-                    .push(format!("Could not parse {} as integer", token.literal));
+        let (radix, digits) = self.validate_number_literal(&token, false)?;
+        if let Ok(value) = i64::from_str_radix(&digits, radix) {
+            return Some(Box::new(IntegerLiteral::new(token, value)));
+        }
+        match BigInt::parse_bytes(digits.as_bytes(), radix) {
+            Some(value) => Some(Box::new(BigIntegerLiteral::new(token, value))),
+            None => {
+                self.errors.push(ParseError::InvalidIntegerLiteral {
+                    literal: token.literal,
+                    span: token.span,
+                });
                 None
             }
         }
     }
 
+    fn parse_float_literal(&mut self) -> Option<Box<dyn Expression>> {
+        let token = self.cur_token.clone();
+        let (_, digits) = self.validate_number_literal(&token, true)?;
+        match digits.parse::<f64>() {
+            Ok(value) if value.is_finite() => Some(Box::new(FloatLiteral::new(token, value))),
+            _ => {
+                self.errors.push(ParseError::InvalidFloatLiteral {
+                    literal: token.literal,
+                    span: token.span,
+                });
+                None
+            }
+        }
+    }
+
+    /// Checks that every character of a numeric literal is a digit in its base, and that `_`
+    /// separators only appear between two digits. Returns the base of the literal and its digits
+    /// with the prefix and separators removed. Float literals may also contain a `.` and an
+    /// exponent. On failure, an error pointing at the first offending character is added.
+    fn validate_number_literal(&mut self, token: &Token, is_float: bool) -> Option<(u32, String)> {
+        let literal = &token.literal;
+        let (radix, prefix) = match literal.get(..2) {
+            Some("0x") => (16, "0x"),
+            Some("0o") => (8, "0o"),
+            Some("0b") => (2, "0b"),
+            _ => (10, ""),
+        };
+        let chars: Vec<(usize, char)> = literal.char_indices().skip(prefix.len()).collect();
+        if chars.is_empty() {
+            self.errors.push(ParseError::MissingDigits {
+                prefix: prefix.to_string(),
+                span: token.span,
+            });
+            return None;
+        }
+
+        let is_digit = |c: char| c.is_digit(radix);
+        // The span of the character at byte `offset` in the literal. Literals never span lines.
+        let char_span = |offset: usize, c: char| Span {
+            start: token.span.start + offset,
+            end: token.span.start + offset + c.len_utf8(),
+            line: token.span.line,
+            column: token.span.column + literal[..offset].chars().count(),
+        };
+        for (i, &(offset, c)) in chars.iter().enumerate() {
+            if c == '_' {
+                let after_digit = i > 0 && is_digit(chars[i - 1].1);
+                let before_digit = chars.get(i + 1).is_some_and(|&(_, c)| is_digit(c));
+                if !after_digit || !before_digit {
+                    self.errors.push(ParseError::MisplacedSeparator {
+                        span: char_span(offset, c),
+                    });
+                    return None;
+                }
+            } else if !(is_digit(c) || is_float && matches!(c, '.' | 'e' | 'E' | '+' | '-')) {
+                self.errors.push(ParseError::InvalidDigit {
+                    digit: c,
+                    radix,
+                    span: char_span(offset, c),
+                });
+                return None;
+            }
+        }
+        let digits = chars
+            .iter()
+            .map(|&(_, c)| c)
+            .filter(|&c| c != '_')
+            .collect();
+        Some((radix, digits))
+    }
+
     fn parse_boolean_literal(&mut self) -> Option<Box<dyn Expression>> {
         let token = self.cur_token.clone();
         match token.literal.parse::<bool>() {
             Ok(value) => Some(Box::new(BooleanLiteral::new(token, value))),
             Err(_) => {
-                self.errors
-                    .push(format!("Could not parse {} as a bool", token.literal));
+                self.errors.push(ParseError::InvalidBooleanLiteral {
+                    literal: token.literal,
+                    span: token.span,
+                });
                 None
             }
         }
@@ -313,6 +964,16 @@ impl Parser {
         Some(Box::new(NullLiteral::new(token)))
     }
 
+    // Illegal tokens never form a valid expression, but they get their own prefix function so
+    // the error message can name the offending character.
+    fn parse_illegal(&mut self) -> Option<Box<dyn Expression>> {
+        self.errors.push(ParseError::IllegalCharacter {
+            character: self.cur_token.literal.clone(),
+            span: self.cur_token.span,
+        });
+        None
+    }
+
     pub fn parse_prefix_expression(&mut self) -> Option<Box<dyn Expression>> {
         let token = self.cur_token.clone();
         let operator = token.literal.clone();
@@ -321,21 +982,46 @@ impl Parser {
         Some(Box::new(PrefixExpression::new(token, &operator, right)))
     }
 
+    // When this function is called, cur_token should point to the Assign
+    fn parse_assign_expression(
+        &mut self,
+        left: Box<dyn Expression>,
+    ) -> Option<Box<dyn Expression>> {
+        let Some(name) = left.as_any().downcast_ref::<Identifier>() else {
+            self.errors
+                .push(ParseError::InvalidAssignmentTarget { span: left.span() });
+            return None;
+        };
+        let token = self.cur_token.clone();
+        self.next_token();
+        // Assignment is right associative, so a = b = 1 assigns 1 to both
+        let value = self.parse_expression(Precedence::Assign as i32 - 1)?;
+        Some(Box::new(AssignExpression::new(token, name.clone(), value)))
+    }
+
     fn parse_infix_expression(&mut self, left: Box<dyn Expression>) -> Option<Box<dyn Expression>> {
         let token = self.cur_token.clone();
         let operator = token.literal.clone();
-        let precendence = Parser::token_to_precedence(self.cur_token.token_type);
+        let mut precendence = Parser::token_to_precedence(self.cur_token.token_type) as i32;
+        // Right associative operators parse their right operand with a lower precedence, so that
+        // another use of the same operator is included in it. This makes 2 ** 3 ** 2 parse as
+        // 2 ** (3 ** 2).
+        if Parser::is_right_associative(self.cur_token.token_type) {
+            precendence -= 1;
+        }
         self.next_token();
-        let right = self.parse_expression(precendence as i32)?;
+        let right = self.parse_expression(precendence)?;
         Some(Box::new(InfixExpression::new(
             token, &operator, left, right,
         )))
     }
 
     fn parse_call_expression(&mut self, left: Box<dyn Expression>) -> Option<Box<dyn Expression>> {
-        if !(left.as_any().is::<Identifier>() || left.as_any().is::<FunctionLiteral>()) {
+        let any = left.as_any();
+        if !(any.is::<Identifier>() || any.is::<FunctionLiteral>() || any.is::<MemberExpression>())
+        {
             self.errors
-                .push("Expected function literal or identifier in call position".to_string());
+                .push(ParseError::InvalidCallTarget { span: left.span() });
             return None;
         }
         let token = if self.cur_token.token_type == TokenType::LParen {
@@ -344,7 +1030,61 @@ impl Parser {
             return None;
         };
         let arguments = self.parse_argument_list()?;
-        Some(Box::new(CallExpression::new(token, left, arguments)))
+        let mut call_expression = CallExpression::new(token, left, arguments);
+        // cur_token points to the RParen here
+        call_expression.span = call_expression.span.to(self.cur_token.span);
+        Some(Box::new(call_expression))
+    }
+
+    // When this function is called, cur_token should point to the Dot.
+    // When it returns, cur_token should point to the name of the field.
+    fn parse_member_expression(
+        &mut self,
+        left: Box<dyn Expression>,
+    ) -> Option<Box<dyn Expression>> {
+        let token = self.cur_token.clone();
+        if !self.expect_peek(TokenType::Ident) {
+            self.expect_error(TokenType::Ident);
+            return None;
+        }
+        let field = Identifier::new(self.cur_token.clone(), &self.cur_token.literal);
+        Some(Box::new(MemberExpression::new(token, left, field)))
+    }
+
+    // When this function is called, cur_token should point to the With.
+    // When it returns, cur_token should point to the RBrace after the updated fields.
+    fn parse_with_expression(&mut self, left: Box<dyn Expression>) -> Option<Box<dyn Expression>> {
+        let token = self.cur_token.clone();
+        if !self.expect_peek(TokenType::LBrace) {
+            self.expect_error(TokenType::LBrace);
+            return None;
+        }
+        let mut updates: Vec<(Identifier, Box<dyn Expression>)> = Vec::new();
+        while !self.expect_peek(TokenType::RBrace) {
+            if !self.expect_peek(TokenType::Ident) {
+                self.expect_error(TokenType::Ident);
+                return None;
+            }
+            let field = Identifier::new(self.cur_token.clone(), &self.cur_token.literal);
+            self.check_duplicate_field(updates.iter().map(|(other, _)| other), &field);
+            if !self.expect_peek(TokenType::Colon) {
+                self.expect_error(TokenType::Colon);
+                return None;
+            }
+            self.next_token();
+            let value = self.parse_expression(Precedence::Lowest as i32)?;
+            updates.push((field, value));
+            // Updates are separated by commas, and the last update may be followed by one
+            if self.peek_token.token_type != TokenType::RBrace
+                && !self.expect_peek(TokenType::Comma)
+            {
+                self.expect_error(TokenType::Comma);
+                return None;
+            }
+        }
+        let mut with_expression = WithExpression::new(token, left, updates);
+        with_expression.span = with_expression.span.to(self.cur_token.span);
+        Some(Box::new(with_expression))
     }
 
     fn parse_index_expression(&mut self, left: Box<dyn Expression>) -> Option<Box<dyn Expression>> {
@@ -355,12 +1095,41 @@ impl Parser {
         };
         // Advance cur_token so it points to the first token of the index value
         self.next_token();
-        let index = self.parse_expression(Precedence::Lowest as i32)?;
-        if !self.expect_peek(TokenType::RSquare) {
-            self.expect_error(TokenType::RSquare);
-            return None;
+        let start = if self.cur_token.token_type == TokenType::Colon {
+            None
+        } else {
+            Some(self.parse_expression(Precedence::Lowest as i32)?)
+        };
+        match start {
+            Some(index) if self.peek_token.token_type != TokenType::Colon => {
+                if !self.expect_peek(TokenType::RSquare) {
+                    self.expect_error(TokenType::RSquare);
+                    return None;
+                }
+                let mut index_expression = IndexExpression::new(token, left, index);
+                index_expression.span = index_expression.span.to(self.cur_token.span);
+                Some(Box::new(index_expression))
+            }
+            start => {
+                if start.is_some() {
+                    // Advance cur_token so it points to the `:`
+                    self.next_token();
+                }
+                let end = if self.peek_token.token_type == TokenType::RSquare {
+                    None
+                } else {
+                    self.next_token();
+                    Some(self.parse_expression(Precedence::Lowest as i32)?)
+                };
+                if !self.expect_peek(TokenType::RSquare) {
+                    self.expect_error(TokenType::RSquare);
+                    return None;
+                }
+                let mut slice_expression = SliceExpression::new(token, left, start, end);
+                slice_expression.span = slice_expression.span.to(self.cur_token.span);
+                Some(Box::new(slice_expression))
+            }
         }
-        Some(Box::new(IndexExpression::new(token, left, index)))
     }
 
     fn parse_grouped_expression(&mut self) -> Option<Box<dyn Expression>> {
@@ -431,6 +1200,56 @@ impl Parser {
         Some(Box::new(IfExpression::new(token, consequences, None)))
     }
 
+    // When this function is called, cur_token should point to the `match` keyword.
+    // When it returns, cur_token should point to the RBrace after the last arm.
+    fn parse_match_expression(&mut self) -> Option<Box<dyn Expression>> {
+        let token = self.cur_token.clone();
+        self.next_token();
+        let subject = self.parse_expression(Precedence::Lowest as i32)?;
+        if !self.expect_peek(TokenType::LBrace) {
+            self.expect_error(TokenType::LBrace);
+            return None;
+        }
+        let mut arms = Vec::new();
+        while !self.expect_peek(TokenType::RBrace) {
+            self.next_token();
+            let pattern = self.parse_pattern()?;
+            self.check_pattern_bindings(std::iter::once(pattern.as_ref()));
+            let guard = if self.expect_peek(TokenType::If) {
+                self.next_token();
+                Some(self.parse_expression(Precedence::Lowest as i32)?)
+            } else {
+                None
+            };
+            if !self.expect_peek(TokenType::FatArrow) {
+                self.expect_error(TokenType::FatArrow);
+                return None;
+            }
+            self.next_token();
+            // A `{` after the arrow starts a block, like the branches of an if expression, so a
+            // hash literal has to be wrapped in parentheses
+            let is_block = self.cur_token.token_type == TokenType::LBrace;
+            let value: Box<dyn Expression> = if is_block {
+                Box::new(self.parse_block_statement()?)
+            } else {
+                self.parse_expression(Precedence::Lowest as i32)?
+            };
+            arms.push(MatchArm::new(pattern, guard, value));
+            // Arms are separated by commas, and the last arm may be followed by one. The comma
+            // after a block is optional.
+            if self.peek_token.token_type != TokenType::RBrace
+                && !self.expect_peek(TokenType::Comma)
+                && !is_block
+            {
+                self.expect_error(TokenType::Comma);
+                return None;
+            }
+        }
+        let mut match_expression = MatchExpression::new(token, subject, arms);
+        match_expression.span = match_expression.span.to(self.cur_token.span);
+        Some(Box::new(match_expression))
+    }
+
     fn parse_function_literal(&mut self) -> Option<Box<dyn Expression>> {
         let token = if self.cur_token.token_type == TokenType::Function {
             self.cur_token.clone()
@@ -448,8 +1267,12 @@ impl Parser {
             self.expect_error(TokenType::LBrace);
             return None;
         }
-        // cur_token now points to the LBrace
-        let body = self.parse_block_statement()?;
+        // cur_token now points to the LBrace. Loops outside the function can't be broken out of
+        // from inside it.
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        let body = self.parse_block_statement();
+        self.loop_depth = loop_depth;
+        let body = body?;
 
         Some(Box::new(FunctionLiteral::new(token, parameters, body)))
     }
@@ -479,14 +1302,15 @@ impl Parser {
         Some(ret)
     }
 
-    fn parse_parameter_list(&mut self) -> Option<Vec<Identifier>> {
+    fn parse_parameter_list(&mut self) -> Option<Vec<Box<dyn Pattern>>> {
         // cur_token points to the LParen here
         let mut ret = Vec::new();
         if !self.expect_peek(TokenType::RParen) {
             self.next_token();
             loop {
-                let identifier = Identifier::new(self.cur_token.clone(), &self.cur_token.literal);
-                ret.push(identifier);
+                // Parameters can destructure their arguments
+                let parameter = self.parse_pattern()?;
+                ret.push(parameter);
                 // If the next token is RParen, then break out of the loop
                 if self.expect_peek(TokenType::RParen) {
                     break;
@@ -500,6 +1324,8 @@ impl Parser {
                 self.next_token();
             }
         }
+        // A name can only be bound once across all of the parameters
+        self.check_pattern_bindings(ret.iter().map(|parameter| parameter.as_ref()));
         // cur_token points to the RParen here
         Some(ret)
     }
@@ -534,7 +1360,41 @@ impl Parser {
             }
         }
         // cur_token points to the RSqaure here
-        Some(Box::new(ArrayExpression::new(token, items)))
+        let mut array_expression = ArrayExpression::new(token, items);
+        array_expression.span = array_expression.span.to(self.cur_token.span);
+        Some(Box::new(array_expression))
+    }
+
+    // When this function is called, cur_token should point to the LBrace.
+    // When it returns, cur_token should point to the RBrace.
+    fn parse_hash_expression(&mut self) -> Option<Box<dyn Expression>> {
+        let token = if self.cur_token.token_type == TokenType::LBrace {
+            self.cur_token.clone()
+        } else {
+            return None;
+        };
+        let mut pairs = Vec::new();
+        while !self.expect_peek(TokenType::RBrace) {
+            self.next_token();
+            let key = self.parse_expression(Precedence::Lowest as i32)?;
+            if !self.expect_peek(TokenType::Colon) {
+                self.expect_error(TokenType::Colon);
+                return None;
+            }
+            self.next_token();
+            let value = self.parse_expression(Precedence::Lowest as i32)?;
+            pairs.push((key, value));
+            // Pairs are separated by commas, and the last pair may be followed by one
+            if self.peek_token.token_type != TokenType::RBrace
+                && !self.expect_peek(TokenType::Comma)
+            {
+                self.expect_error(TokenType::Comma);
+                return None;
+            }
+        }
+        let mut hash_expression = HashExpression::new(token, pairs);
+        hash_expression.span = hash_expression.span.to(self.cur_token.span);
+        Some(Box::new(hash_expression))
     }
 
     // When this function is called, cur_token should point to the starting DoubleQuotation.
@@ -545,29 +1405,87 @@ impl Parser {
         } else {
             return None;
         };
+        // The lexer splits the contents of a string into several tokens around `${...}` segments
+        // and invalid escape sequences. Every invalid sequence is reported before giving up on
+        // the string.
+        let mut parts: Vec<Box<dyn Expression>> = Vec::new();
         let mut value = String::new();
-        if !self.expect_peek(TokenType::DoubleQuotation) {
+        let mut value_token = token.clone();
+        let mut valid = true;
+        loop {
             self.next_token();
-            value = self.cur_token.literal.clone();
-            if self.expect_peek(TokenType::DoubleQuotation) {
-                Some(Box::new(StringExpression::new(token, value)))
-            } else {
-                self.expect_error(TokenType::DoubleQuotation);
-                None
+            match self.cur_token.token_type {
+                TokenType::DoubleQuotation => break,
+                TokenType::StringValue => {
+                    if value.is_empty() {
+                        value_token = self.cur_token.clone();
+                    }
+                    value.push_str(&self.cur_token.literal);
+                }
+                TokenType::InvalidEscape => {
+                    self.errors.push(ParseError::InvalidEscape {
+                        sequence: self.cur_token.literal.clone(),
+                        span: self.cur_token.span,
+                    });
+                    valid = false;
+                }
+                TokenType::InterpolationStart => {
+                    if !value.is_empty() {
+                        let text = StringExpression::new(value_token.clone(), value);
+                        parts.push(Box::new(text));
+                        value = String::new();
+                    }
+                    self.next_token();
+                    parts.push(self.parse_expression(Precedence::Lowest as i32)?);
+                    if !self.expect_peek(TokenType::InterpolationEnd) {
+                        self.expect_error(TokenType::InterpolationEnd);
+                        return None;
+                    }
+                }
+                TokenType::UnterminatedString => {
+                    self.errors.push(ParseError::UnterminatedString {
+                        span: token.span.to(self.cur_token.span),
+                    });
+                    return None;
+                }
+                found => {
+                    self.errors.push(ParseError::UnexpectedToken {
+                        expected: TokenType::DoubleQuotation,
+                        found,
+                        span: self.cur_token.span,
+                    });
+                    return None;
+                }
             }
-        } else {
-            Some(Box::new(StringExpression::new(token, value)))
         }
+        if !valid {
+            return None;
+        }
+
+        if parts.is_empty() {
+            let mut string_expression = StringExpression::new(token, value);
+            string_expression.span = string_expression.span.to(self.cur_token.span);
+            return Some(Box::new(string_expression));
+        }
+        if !value.is_empty() {
+            parts.push(Box::new(StringExpression::new(value_token, value)));
+        }
+        let mut interpolated_string_expression = InterpolatedStringExpression::new(token, parts);
+        interpolated_string_expression.span =
+            interpolated_string_expression.span.to(self.cur_token.span);
+        Some(Box::new(interpolated_string_expression))
     }
 
     fn no_prefix_function_error(&mut self, token_type: TokenType) {
-        self.errors.push(format!(
-            "No prefix parse function found for {token_type:?} found"
-        ))
+        self.errors.push(ParseError::NoPrefixParseFn {
+            found: token_type,
+            span: self.cur_token.span,
+        })
     }
 
     fn token_to_precedence(token_type: TokenType) -> Precedence {
         match token_type {
+            TokenType::Assign => Precedence::Assign,
             TokenType::Eq => Precedence::Equals,
             TokenType::NotEq => Precedence::Equals,
             TokenType::LArrow => Precedence::LessGreater,
@@ -578,12 +1496,27 @@ impl Parser {
             TokenType::Minus => Precedence::Sum,
             TokenType::Star => Precedence::Product,
             TokenType::Slash => Precedence::Product,
+            TokenType::Percent => Precedence::Product,
+            TokenType::DoubleStar => Precedence::Power,
+            TokenType::Ampersand => Precedence::BitAnd,
+            TokenType::Pipe => Precedence::BitOr,
+            TokenType::Caret => Precedence::BitXor,
+            TokenType::LShift => Precedence::Shift,
+            TokenType::RShift => Precedence::Shift,
+            TokenType::LogicalAnd => Precedence::LogicalAnd,
+            TokenType::LogicalOr => Precedence::LogicalOr,
             TokenType::LSquare => Precedence::Call,
             TokenType::LParen => Precedence::Call,
+            TokenType::Dot => Precedence::Call,
+            TokenType::With => Precedence::Call,
             _ => Precedence::Lowest,
         }
     }
 
+    fn is_right_associative(token_type: TokenType) -> bool {
+        token_type == TokenType::DoubleStar
+    }
+
     /// If `peek_token` is equal to the expected token type, then
     /// advance the token pointers and return true. Otherwise
     /// returns false and the token pointers do not change.
@@ -598,19 +1531,48 @@ impl Parser {
 
     /// Adds a parser error indicating the expected token type was not found
     fn expect_error(&mut self, expected_token_type: TokenType) {
-        self.errors.push(format!(
-            "Expected  {:?}, found {:?} instead",
-            expected_token_type, self.peek_token.token_type
-        ))
+        self.errors.push(ParseError::UnexpectedToken {
+            expected: expected_token_type,
+            found: self.peek_token.token_type,
+            span: self.peek_token.span,
+        })
     }
 
-    fn skip_to_statement_end(&mut self) {
-        while self.cur_token.token_type != TokenType::Semicolon
-            && self.cur_token.token_type != TokenType::Eof
-        {
+    /// Skips tokens after a statement failed to parse, so that parsing can resume at the start of
+    /// the next statement. Stops after a `;`, or before a keyword that starts a statement, as long
+    /// as they are not nested inside braces that were opened while skipping. When `in_block` is true,
+    /// this also stops before the `}` that closes the enclosing block.
+    fn synchronize(&mut self, in_block: bool) {
+        let mut depth = 0;
+        loop {
+            match self.cur_token.token_type {
+                TokenType::Eof => return,
+                TokenType::Semicolon if depth == 0 => {
+                    self.next_token();
+                    return;
+                }
+                TokenType::RBrace if depth == 0 && in_block => return,
+                TokenType::LBrace => depth += 1,
+                TokenType::RBrace if depth > 0 => depth -= 1,
+                _ => {}
+            }
             self.next_token();
+            if depth == 0
+                && matches!(
+                    self.cur_token.token_type,
+                    TokenType::Let
+                        | TokenType::Return
+                        | TokenType::While
+                        | TokenType::For
+                        | TokenType::Break
+                        | TokenType::Continue
+                        | TokenType::Struct
+                        | TokenType::Match
+                )
+            {
+                return;
+            }
         }
-        self.next_token();
     }
 
     fn next_token(&mut self) {
@@ -618,8 +1580,8 @@ impl Parser {
         self.peek_token = self.lexer.next_token();
     }
 
-    fn get_errors(&self) -> &Vec<String> {
-        &self.errors
+    pub fn into_errors(self) -> Vec<ParseError> {
+        self.errors
     }
 
     fn register_prefix_function(&mut self, token_type: TokenType, prefix_function: PrefixParseFn) {
@@ -638,8 +1600,34 @@ impl Parser {
     }
 }
 
+/// Collects the names `pattern` binds, in the order they appear
+fn collect_bindings<'a>(pattern: &'a dyn Pattern, names: &mut Vec<&'a Identifier>) {
+    let any = pattern.as_any();
+    if let Some(identifier) = any.downcast_ref::<Identifier>() {
+        names.push(identifier);
+    } else if let Some(array_pattern) = any.downcast_ref::<ArrayPattern>() {
+        for item in &array_pattern.items {
+            collect_bindings(item.as_ref(), names);
+        }
+        names.extend(&array_pattern.rest);
+    } else if let Some(hash_pattern) = any.downcast_ref::<HashPattern>() {
+        for (_, value) in &hash_pattern.pairs {
+            collect_bindings(value.as_ref(), names);
+        }
+    }
+}
+
+/// Parses all of the input from `lexer`, returning the (possibly partial) program along with
+/// every error that was found.
+pub fn parse(lexer: Lexer) -> (Program, Vec<ParseError>) {
+    let mut parser = Parser::new(lexer);
+    let program = parser.parse_program();
+    (program, parser.into_errors())
+}
+
+#[cfg(test)]
 pub fn has_parser_errors(parser: &Parser) -> bool {
-    let errors = parser.get_errors();
+    let errors = &parser.errors;
     if !errors.is_empty() {
         eprintln!("Parser has {} errors(s)", errors.len());
         for error in errors {
@@ -653,10 +1641,19 @@ pub fn has_parser_errors(parser: &Parser) -> bool {
 
 enum Precedence {
     Lowest,
+    Assign,
+    LogicalOr,
+    LogicalAnd,
     Equals,
     LessGreater,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shift,
     Sum,
     Product,
     Prefix,
+    // Binds tighter than prefix operators, so -2 ** 2 is -(2 ** 2)
+    Power,
     Call,
 }
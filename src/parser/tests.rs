@@ -1,17 +1,21 @@
 #[cfg(test)]
-use crate::ast::StringExpression;
+use crate::ast::{
+    ArrayExpression, BigIntegerLiteral, BooleanLiteral, CallExpression, ExpressionStatement,
+    FloatLiteral, FunctionLiteral, Identifier, IfExpression, IndexExpression, InfixExpression,
+    IntegerLiteral, LetStatement, Node, NullLiteral, PrefixExpression, ReturnStatement,
+};
 #[cfg(test)]
 use crate::ast::{
-    ArrayExpression, BooleanLiteral, CallExpression, ExpressionStatement, FunctionLiteral,
-    Identifier, IfExpression, IndexExpression, InfixExpression, IntegerLiteral, LetStatement, Node,
-    NullLiteral, PrefixExpression, ReturnStatement,
+    ArrayPattern, ForStatement, HashExpression, HashPattern, InterpolatedStringExpression,
+    MatchExpression, MemberExpression, Pattern, SliceExpression, StringExpression, StructStatement,
+    WildcardPattern, WithExpression,
 };
 #[cfg(test)]
 use crate::lexer::Lexer;
 #[cfg(test)]
-use crate::parser::{Parser, has_parser_errors};
+use crate::parser::{ParseError, Parser, has_parser_errors, parse};
 #[cfg(test)]
-use crate::token::TokenType;
+use crate::token::{Span, TokenType};
 
 #[test]
 fn let_statements() {
@@ -45,6 +49,77 @@ fn let_statements() {
     }
 }
 
+#[test]
+fn mutable_bindings_and_assignment() {
+    let tests = vec![
+        ("let mut x = 5;", "let mut x = 5;"),
+        ("x = 1;", "(x = 1);"),
+        ("x = y = 1 + 2;", "(x = (y = (1 + 2)));"),
+        ("x = a || b;", "(x = (a || b));"),
+        ("f(x = 1);", "f((x = 1));"),
+    ];
+
+    for (input, expected) in tests {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program();
+        assert!(!has_parser_errors(&parser), "Input: {input}");
+        assert_eq!(program.statements.len(), 1, "Input: {input}");
+        assert_eq!(program.statements[0].string(), expected);
+    }
+
+    let (program, _) = parse(Lexer::new("let mut x = 5;"));
+    let let_statement = program.statements[0]
+        .as_any()
+        .downcast_ref::<LetStatement>()
+        .expect("Expected let statement");
+    assert!(let_statement.mutable);
+    assert!(!let_statement.rec);
+
+    let (_, errors) = parse(Lexer::new("a[0] = 1;"));
+    assert_eq!(
+        errors,
+        vec![ParseError::InvalidAssignmentTarget {
+            span: Span::new(0, 4, 1, 1),
+        }]
+    );
+}
+
+#[test]
+fn loop_statements() {
+    let tests = vec![
+        ("while x < 3 { x; }", "while (x < 3) { x; }"),
+        ("for x in [1, 2] { x; };", "for x in [1, 2] { x; }"),
+        (
+            "for c in \"ab\" { continue; break; }",
+            "for c in \"ab\" { continue; break; }",
+        ),
+        (
+            "while true { for x in xs { break; } }",
+            "while true { for x in xs { break; } }",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program();
+        assert!(!has_parser_errors(&parser), "Input: {input}");
+        assert_eq!(program.statements.len(), 1, "Input: {input}");
+        assert_eq!(program.statements[0].string(), expected);
+    }
+
+    let (program, errors) = parse(Lexer::new("for i in range(3) { i; } let y = 1;"));
+    assert!(errors.is_empty());
+    let for_statement = program.statements[0]
+        .as_any()
+        .downcast_ref::<ForStatement>()
+        .expect("Expected for statement");
+    assert_eq!(for_statement.variable.value, "i");
+    assert_eq!(for_statement.iterable.string(), "range(3)");
+    assert!(program.statements[1].as_any().is::<LetStatement>());
+}
+
 #[test]
 fn let_rec_statements() {
     let input = "
@@ -73,6 +148,229 @@ fn let_rec_statements() {
             true
         ))
     }
+
+    let (program, errors) = parse(Lexer::new(
+        "let rec is_even = fun(n) { is_odd(n) } and is_odd = fun(n) { is_even(n) };",
+    ));
+    assert!(errors.is_empty());
+    let let_statement = program.statements[0]
+        .as_any()
+        .downcast_ref::<LetStatement>()
+        .expect("Expected let statement");
+    let names: Vec<String> = let_statement
+        .bindings()
+        .map(|(pattern, _)| pattern.string())
+        .collect();
+    assert_eq!(names, ["is_even", "is_odd"]);
+    assert_eq!(let_statement.span, Span::new(0, 73, 1, 1));
+    assert_eq!(
+        let_statement.string(),
+        "let rec is_even = fun(n) { is_odd(n); } and is_odd = fun(n) { is_even(n); };"
+    );
+}
+
+#[test]
+fn destructuring_patterns() {
+    let tests = vec![
+        ("let [a, b] = x;", "let [a, b] = x;"),
+        ("let [head, ...rest] = x;", "let [head, ...rest] = x;"),
+        ("let [...all,] = x;", "let [...all] = x;"),
+        ("let [] = x;", "let [] = x;"),
+        ("let [[a, b], c,] = x;", "let [[a, b], c] = x;"),
+        ("let {name, age} = x;", "let {name, age} = x;"),
+        (
+            "let {name: n, tags: [first, ...others]} = x;",
+            "let {name: n, tags: [first, ...others]} = x;",
+        ),
+        (
+            "let mut {point: {x, y}} = p;",
+            "let mut {point: {x, y}} = p;",
+        ),
+        ("fun([a, b], {c}) { a };", "fun([a, b],{c}) { a; };"),
+    ];
+    for (input, expected) in tests {
+        let (program, errors) = parse(Lexer::new(input));
+        assert!(errors.is_empty(), "{input}: {errors:?}");
+        assert_eq!(program.string(), expected);
+    }
+
+    let (program, _) = parse(Lexer::new("let [x, ...xs] = list;"));
+    let let_statement = program.statements[0]
+        .as_any()
+        .downcast_ref::<LetStatement>()
+        .expect("Expected let statement");
+    let array_pattern = let_statement
+        .pattern
+        .as_any()
+        .downcast_ref::<ArrayPattern>()
+        .expect("Expected array pattern");
+    assert_eq!(array_pattern.items.len(), 1);
+    assert_eq!(
+        array_pattern.rest.as_ref().map(|rest| rest.value.as_str()),
+        Some("xs")
+    );
+    assert_eq!(array_pattern.span, Span::new(4, 14, 1, 5));
+
+    let (program, _) = parse(Lexer::new("let {a, b: [c]} = h;"));
+    let let_statement = program.statements[0]
+        .as_any()
+        .downcast_ref::<LetStatement>()
+        .expect("Expected let statement");
+    let hash_pattern = let_statement
+        .pattern
+        .as_any()
+        .downcast_ref::<HashPattern>()
+        .expect("Expected hash pattern");
+    let keys: Vec<&str> = hash_pattern
+        .pairs
+        .iter()
+        .map(|(key, _)| key.value.as_str())
+        .collect();
+    assert_eq!(keys, ["a", "b"]);
+    assert_eq!(hash_pattern.span, Span::new(4, 15, 1, 5));
+}
+
+#[test]
+fn match_expressions() {
+    let tests = vec![
+        ("match x { 1 => a, _ => b }", "match x { 1 => a, _ => b };"),
+        (
+            "match x { -1 => a, 2.5 => b, }",
+            "match x { (-1) => a, 2.5 => b };",
+        ),
+        (
+            r#"match x { "a" => 1, true => 2, null => 3 }"#,
+            r#"match x { "a" => 1, true => 2, null => 3 };"#,
+        ),
+        (
+            "match xs { [] => 0, [x, ...rest] if x > 0 => x + 1, [_, 0] => 2 }",
+            "match xs { [] => 0, [x, ...rest] if (x > 0) => (x + 1), [_, 0] => 2 };",
+        ),
+        (
+            r#"match p { {name: "Ada", age} => age, {name} => name }"#,
+            r#"match p { {name: "Ada", age} => age, {name} => name };"#,
+        ),
+        (
+            "let y = match x { n => n * 2 } + 1;",
+            "let y = (match x { n => (n * 2) } + 1);",
+        ),
+        ("match x {}", "match x {  };"),
+        // A `{` after the arrow starts a block, and the comma after a block is optional
+        (
+            r#"match x { 1 => { println("x"); 2 }, _ => 0 }"#,
+            r#"match x { 1 => { println("x"); 2; }, _ => 0 };"#,
+        ),
+        (
+            "match x { 1 => { a } _ => { b } }",
+            "match x { 1 => { a; }, _ => { b; } };",
+        ),
+        (
+            r#"match x { _ => ({"a": 1}) }"#,
+            r#"match x { _ => {"a": 1} };"#,
+        ),
+        // Literals and wildcards can be used in let patterns too
+        ("let [_, 1] = x;", "let [_, 1] = x;"),
+    ];
+    for (input, expected) in tests {
+        let (program, errors) = parse(Lexer::new(input));
+        assert!(errors.is_empty(), "{input}: {errors:?}");
+        assert_eq!(program.string(), expected);
+    }
+
+    let (program, _) = parse(Lexer::new("match n {\n  0 => 1,\n  _ if n > 0 => 2\n}"));
+    let statement = program.statements[0]
+        .as_any()
+        .downcast_ref::<ExpressionStatement>()
+        .expect("Expected expression statement");
+    let match_expression = statement
+        .expression
+        .as_any()
+        .downcast_ref::<MatchExpression>()
+        .expect("Expected match expression");
+    assert_eq!(match_expression.subject.string(), "n");
+    assert_eq!(match_expression.arms.len(), 2);
+    assert!(match_expression.arms[0].guard.is_none());
+    assert!(
+        match_expression.arms[1]
+            .pattern
+            .as_any()
+            .is::<WildcardPattern>()
+    );
+    assert_eq!(
+        match_expression.arms[1]
+            .guard
+            .as_ref()
+            .map(|guard| guard.string()),
+        Some("(n > 0)".to_string())
+    );
+    assert_eq!(match_expression.span, Span::new(0, 39, 1, 1));
+}
+
+#[test]
+fn records() {
+    let tests = vec![
+        ("struct Point { x, y }", "struct Point { x, y }"),
+        ("struct Point { x, y, };", "struct Point { x, y }"),
+        ("struct Unit {}", "struct Unit {  }"),
+        ("p.x;", "p.x;"),
+        ("line.start.x + 1;", "(line.start.x + 1);"),
+        ("-p.x;", "(-p.x);"),
+        ("points[0].x;", "points[0].x;"),
+        ("p.f(1, 2);", "p.f(1, 2);"),
+        ("shape.area.scale(2).x;", "shape.area.scale(2).x;"),
+        ("p with { x: 3 };", "(p with {x: 3});"),
+        (
+            "p with { x: p.x + 1, y: 0, };",
+            "(p with {x: (p.x + 1), y: 0});",
+        ),
+        ("(p with { x: 3 }).x;", "(p with {x: 3}).x;"),
+        ("p with { x: 3 } == q;", "((p with {x: 3}) == q);"),
+        ("p with {};", "(p with {});"),
+    ];
+    for (input, expected) in tests {
+        let (program, errors) = parse(Lexer::new(input));
+        assert!(errors.is_empty(), "{input}: {errors:?}");
+        assert_eq!(program.string(), expected);
+    }
+
+    let (program, _) = parse(Lexer::new("struct Point {\n  x,\n  y\n}\np.y"));
+    let struct_statement = program.statements[0]
+        .as_any()
+        .downcast_ref::<StructStatement>()
+        .expect("Expected struct statement");
+    assert_eq!(struct_statement.name.value, "Point");
+    let fields: Vec<&str> = struct_statement
+        .fields
+        .iter()
+        .map(|field| field.value.as_str())
+        .collect();
+    assert_eq!(fields, ["x", "y"]);
+    assert_eq!(struct_statement.span, Span::new(0, 25, 1, 1));
+    let statement = program.statements[1]
+        .as_any()
+        .downcast_ref::<ExpressionStatement>()
+        .expect("Expected expression statement");
+    let member_expression = statement
+        .expression
+        .as_any()
+        .downcast_ref::<MemberExpression>()
+        .expect("Expected member expression");
+    assert_eq!(member_expression.record.string(), "p");
+    assert_eq!(member_expression.field.value, "y");
+    assert_eq!(member_expression.span, Span::new(26, 29, 5, 1));
+
+    let (program, _) = parse(Lexer::new("p with { x: 1 }"));
+    let statement = program.statements[0]
+        .as_any()
+        .downcast_ref::<ExpressionStatement>()
+        .expect("Expected expression statement");
+    let with_expression = statement
+        .expression
+        .as_any()
+        .downcast_ref::<WithExpression>()
+        .expect("Expected with expression");
+    assert_eq!(with_expression.updates.len(), 1);
+    assert_eq!(with_expression.span, Span::new(0, 15, 1, 1));
 }
 
 #[test]
@@ -87,13 +385,12 @@ fn return_statements() {
     let program = parser.parse_program();
     assert!(!has_parser_errors(&parser));
     assert!(program.statements.len() == 2);
-    for i in 0..program.statements.len() {
-        let statement = &program.statements[i];
+    for (statement, expected_value) in program.statements.iter().zip(expected_values) {
         let return_statement = statement
             .as_any()
             .downcast_ref::<ReturnStatement>()
             .expect("Expected return statement");
-        assert!(check_return_statement(return_statement, expected_values[i]))
+        assert!(check_return_statement(return_statement, expected_value))
     }
 }
 
@@ -283,6 +580,86 @@ fn integer_literal_expression() {
     assert_eq!(integer_literal.token_literal(), "10");
 }
 
+#[test]
+fn big_integer_literal_expression() {
+    let input = "99999999999999999999;";
+    let (program, errors) = parse(Lexer::new(input));
+    assert!(errors.is_empty());
+    let expression_statement = program.statements[0]
+        .as_any()
+        .downcast_ref::<ExpressionStatement>()
+        .expect("Expected expression statement");
+    let big_integer_literal = expression_statement
+        .expression
+        .as_any()
+        .downcast_ref::<BigIntegerLiteral>()
+        .expect("Expected big integer literal expression");
+    assert_eq!(
+        big_integer_literal.value.to_string(),
+        "99999999999999999999"
+    );
+    assert_eq!(big_integer_literal.string(), "99999999999999999999");
+}
+
+#[test]
+fn prefixed_and_separated_integer_literals() {
+    let tests = vec![
+        ("0x1F;", 31),
+        ("0xff;", 255),
+        ("0o17;", 15),
+        ("0b1010;", 10),
+        ("1_000_000;", 1_000_000),
+        ("0b1111_0000;", 0b1111_0000),
+        ("0x7FFF_FFFF_FFFF_FFFF;", i64::MAX),
+        ("0;", 0),
+    ];
+
+    for (input, expected) in tests {
+        let (program, errors) = parse(Lexer::new(input));
+        assert!(errors.is_empty(), "Input: {input}, errors: {errors:?}");
+        let expression_statement = program.statements[0]
+            .as_any()
+            .downcast_ref::<ExpressionStatement>()
+            .expect("Expected expression statement");
+        let integer_literal = expression_statement
+            .expression
+            .as_any()
+            .downcast_ref::<IntegerLiteral>()
+            .expect("Expected integer literal expression");
+        assert_eq!(integer_literal.value, expected, "Input: {input}");
+    }
+
+    // Prefixed literals too large for an i64 become big integers
+    let (program, errors) = parse(Lexer::new("0xFFFF_FFFF_FFFF_FFFF;"));
+    assert!(errors.is_empty());
+    assert_eq!(program.statements[0].string(), "18446744073709551615;");
+}
+
+#[test]
+fn float_literal_expression() {
+    let tests = vec![
+        ("2.75;", 2.75),
+        (".5;", 0.5),
+        ("1e-9;", 1e-9),
+        ("2.5E+3;", 2500.0),
+    ];
+
+    for (input, expected) in tests {
+        let (program, errors) = parse(Lexer::new(input));
+        assert!(errors.is_empty());
+        let expression_statement = program.statements[0]
+            .as_any()
+            .downcast_ref::<ExpressionStatement>()
+            .expect("Expected expression statement");
+        let float_literal = expression_statement
+            .expression
+            .as_any()
+            .downcast_ref::<FloatLiteral>()
+            .expect("Expected float literal expression");
+        assert_eq!(float_literal.value, expected);
+    }
+}
+
 #[test]
 fn boolean_literal_expression() {
     let input = "false;";
@@ -412,6 +789,15 @@ fn infix_expressions() {
         vec!["true == true;", "true", "==", "true"],
         vec!["true != false;", "true", "!=", "false"],
         vec!["false == false;", "false", "==", "false"],
+        vec!["5 % 5;", "5", "%", "5"],
+        vec!["5 ** 5;", "5", "**", "5"],
+        vec!["5 & 5;", "5", "&", "5"],
+        vec!["5 | 5;", "5", "|", "5"],
+        vec!["5 ^ 5;", "5", "^", "5"],
+        vec!["5 << 5;", "5", "<<", "5"],
+        vec!["5 >> 5;", "5", ">>", "5"],
+        vec!["true && false;", "true", "&&", "false"],
+        vec!["true || false;", "true", "||", "false"],
     ];
 
     for test in tests {
@@ -470,6 +856,18 @@ fn operator_precedence() {
         ("add(2 * 3, 4 + 5);", "add((2 * 3), (4 + 5))"),
         ("-add(2, 3);", "(-add(2, 3))"),
         ("!add(true, false);", "(!add(true, false))"),
+        ("a || b && c;", "(a || (b && c))"),
+        ("a && b || c;", "((a && b) || c)"),
+        ("a == b && c != d;", "((a == b) && (c != d))"),
+        ("5 % 3 + 1;", "((5 % 3) + 1)"),
+        ("2 ** 3 ** 2;", "(2 ** (3 ** 2))"),
+        ("2 * 3 ** 2;", "(2 * (3 ** 2))"),
+        ("-2 ** 2;", "(-(2 ** 2))"),
+        ("2 ** -1;", "(2 ** (-1))"),
+        ("a | b ^ c & d;", "(a | (b ^ (c & d)))"),
+        ("1 << 2 + 3;", "(1 << (2 + 3))"),
+        ("a & 1 == 0;", "((a & 1) == 0)"),
+        ("1 << 2 < 1 >> 2;", "((1 << 2) < (1 >> 2))"),
     ];
 
     for (input, expected) in tests {
@@ -566,6 +964,12 @@ fn test_string_expression() {
             r#""123 random string !wowo! !@#12309""#,
             "123 random string !wowo! !@#12309",
         ),
+        (r#""""#, ""),
+        (r#""a\tb\nc""#, "a\tb\nc"),
+        (r#""say \"hi\" \\o/""#, "say \"hi\" \\o/"),
+        (r#""\u{48}\u{e9}\u{1F600}\0""#, "Hé😀\0"),
+        (r#"r"C:\new\table""#, "C:\\new\\table"),
+        (r#"r"""#, ""),
     ];
 
     for (input, expected_value) in tests {
@@ -590,6 +994,63 @@ fn test_string_expression() {
     }
 }
 
+#[test]
+fn interpolated_string_expression() {
+    let tests = vec![
+        (
+            r#""Hello ${name}!""#,
+            vec!["Hello ", "name", "!"],
+            r#""Hello ${name}!""#,
+        ),
+        (
+            r#""${a}${b + 1}""#,
+            vec!["a", "(b + 1)"],
+            r#""${a}${(b + 1)}""#,
+        ),
+        (
+            r#""n\t${len([1, 2])} \${x}""#,
+            vec!["n\t", "len([1, 2])", " ${x}"],
+            r#""n\t${len([1, 2])} \${x}""#,
+        ),
+        (
+            r#""outer ${"inner ${x}"}""#,
+            vec!["outer ", r#""inner ${x}""#],
+            r#""outer ${"inner ${x}"}""#,
+        ),
+    ];
+
+    for (input, expected_parts, expected_string) in tests {
+        let (program, errors) = parse(Lexer::new(input));
+        assert!(errors.is_empty(), "Input: {input}, errors: {errors:?}");
+        let expression_statement = program.statements[0]
+            .as_any()
+            .downcast_ref::<ExpressionStatement>()
+            .expect("Expected expression statement");
+        let interpolated_string_expression = expression_statement
+            .expression
+            .as_any()
+            .downcast_ref::<InterpolatedStringExpression>()
+            .expect("Expected interpolated string expression");
+
+        let parts: Vec<String> = interpolated_string_expression
+            .parts
+            .iter()
+            .map(
+                |part| match part.as_any().downcast_ref::<StringExpression>() {
+                    Some(string_expression) => string_expression.value.clone(),
+                    None => part.string(),
+                },
+            )
+            .collect();
+        assert_eq!(parts, expected_parts);
+        assert_eq!(interpolated_string_expression.string(), expected_string);
+        assert_eq!(
+            interpolated_string_expression.span,
+            Span::new(0, input.len(), 1, 1)
+        );
+    }
+}
+
 #[test]
 fn index_expressions() {
     let tests = vec![
@@ -643,6 +1104,80 @@ fn index_expressions() {
     }
 }
 
+#[test]
+fn hash_expressions() {
+    let tests = vec![
+        ("{};", "{}", 0),
+        (r#"{"one": 1, "two": 2};"#, r#"{"one": 1, "two": 2}"#, 2),
+        (r#"{"one": 1, "two": 2,};"#, r#"{"one": 1, "two": 2}"#, 2),
+        (
+            "{1 + 1: [a], true: {null: b}};",
+            "{(1 + 1): [a], true: {null: b}}",
+            2,
+        ),
+        ("{x: fun(y) { y }};", "{x: fun(y) { y; }}", 1),
+    ];
+
+    for (input, expected, length) in tests {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program();
+        assert!(!has_parser_errors(&parser), "Input: {input}");
+        assert_eq!(program.statements.len(), 1);
+
+        let statement = &program.statements[0];
+        let expression_statement = statement
+            .as_any()
+            .downcast_ref::<ExpressionStatement>()
+            .expect("Expected expression statement");
+        let hash_expression = expression_statement
+            .expression
+            .as_any()
+            .downcast_ref::<HashExpression>()
+            .expect("Expected hash expression");
+        assert_eq!(hash_expression.string(), expected);
+        assert_eq!(hash_expression.pairs.len(), length);
+    }
+
+    let (_, errors) = parse(Lexer::new(r#"{"a" 1};"#));
+    assert_eq!(
+        errors.first().map(ParseError::message),
+        Some("Expected `:`, found integer instead".to_string())
+    );
+}
+
+#[test]
+fn slice_expressions() {
+    let tests = vec![
+        ("arr[1:3];", "arr[1:3]"),
+        ("arr[:3];", "arr[:3]"),
+        ("arr[1:];", "arr[1:]"),
+        ("arr[:];", "arr[:]"),
+        ("arr[i + 1:-1];", "arr[(i + 1):(-1)]"),
+        ("\"hello\"[1:len(s)];", "\"hello\"[1:len(s)]"),
+    ];
+
+    for (input, expected) in tests {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program();
+        assert!(!has_parser_errors(&parser), "Input: {input}");
+        assert_eq!(program.statements.len(), 1);
+
+        let statement = &program.statements[0];
+        let expression_statement = statement
+            .as_any()
+            .downcast_ref::<ExpressionStatement>()
+            .expect("Expected expression statement");
+        let slice_expression = expression_statement
+            .expression
+            .as_any()
+            .downcast_ref::<SliceExpression>()
+            .expect("Expected slice expression");
+        assert_eq!(slice_expression.string(), expected);
+    }
+}
+
 #[cfg(test)]
 fn check_let_statement(
     let_statement: &LetStatement,
@@ -651,16 +1186,16 @@ fn check_let_statement(
     expected_rec: bool,
 ) -> bool {
     let_statement.token.token_type == TokenType::Let
-        && let_statement.name.value == expected_identifier_literal
+        && let_statement.pattern.string() == expected_identifier_literal
         && let_statement.value.string() == expected_expression_literal
         && let_statement.rec == expected_rec
 }
 
 #[cfg(test)]
-fn check_params_list(parameters: &Vec<Identifier>, expected: Vec<&str>) {
+fn check_params_list(parameters: &[Box<dyn Pattern>], expected: Vec<&str>) {
     assert_eq!(parameters.len(), expected.len());
     for (i, param) in parameters.iter().enumerate() {
-        assert_eq!(param.value, expected[i]);
+        assert_eq!(param.string(), expected[i]);
     }
 }
 
@@ -672,3 +1207,472 @@ fn check_return_statement(
     return_statement.token.token_type == TokenType::Return
         && return_statement.return_value.string() == expected_expression_literal
 }
+
+#[test]
+fn node_spans() {
+    let input = "let x = 1;\nadd(x, [1, 2])[0] + 2;";
+    let lexer = Lexer::new(input);
+    let mut parser = Parser::new(lexer);
+    let program = parser.parse_program();
+    assert!(!has_parser_errors(&parser));
+    assert_eq!(program.statements.len(), 2);
+
+    let let_statement = program.statements[0]
+        .as_any()
+        .downcast_ref::<LetStatement>()
+        .expect("Expected let statement");
+    assert_eq!(let_statement.span, Span::new(0, 9, 1, 1));
+
+    let expression_statement = program.statements[1]
+        .as_any()
+        .downcast_ref::<ExpressionStatement>()
+        .expect("Expected expression statement");
+    let infix_expression = expression_statement
+        .expression
+        .as_any()
+        .downcast_ref::<InfixExpression>()
+        .expect("Expected infix expression");
+    assert_eq!(infix_expression.span, Span::new(11, 32, 2, 1));
+
+    let index_expression = infix_expression
+        .left
+        .as_any()
+        .downcast_ref::<IndexExpression>()
+        .expect("Expected index expression");
+    assert_eq!(index_expression.span, Span::new(11, 28, 2, 1));
+
+    let call_expression = index_expression
+        .collection
+        .as_any()
+        .downcast_ref::<CallExpression>()
+        .expect("Expected call expression");
+    assert_eq!(call_expression.span, Span::new(11, 25, 2, 1));
+    assert_eq!(call_expression.arguments[1].span(), Span::new(18, 24, 2, 8));
+}
+
+#[test]
+fn parser_error_spans() {
+    let input = "let x = 1;\nlet = 5;";
+    let (_, errors) = parse(Lexer::new(input));
+    assert_eq!(
+        errors,
+        vec![ParseError::UnexpectedToken {
+            expected: TokenType::Ident,
+            found: TokenType::Assign,
+            span: Span::new(15, 16, 2, 5),
+        }]
+    );
+    assert_eq!(
+        errors[0].to_string(),
+        "2:5: Expected identifier, found `=` instead"
+    );
+}
+
+#[test]
+fn parser_error_variants() {
+    let tests = vec![
+        (
+            "let x 5;",
+            ParseError::UnexpectedToken {
+                expected: TokenType::Assign,
+                found: TokenType::Int,
+                span: Span::new(6, 7, 1, 7),
+            },
+        ),
+        (
+            "let x = };",
+            ParseError::NoPrefixParseFn {
+                found: TokenType::RBrace,
+                span: Span::new(8, 9, 1, 9),
+            },
+        ),
+        (
+            "12³;",
+            ParseError::InvalidDigit {
+                digit: '³',
+                radix: 10,
+                span: Span::new(2, 4, 1, 3),
+            },
+        ),
+        (
+            "let x = 0xZZ;",
+            ParseError::InvalidDigit {
+                digit: 'Z',
+                radix: 16,
+                span: Span::new(10, 11, 1, 11),
+            },
+        ),
+        (
+            "0b1021;",
+            ParseError::InvalidDigit {
+                digit: '2',
+                radix: 2,
+                span: Span::new(4, 5, 1, 5),
+            },
+        ),
+        (
+            "0o78;",
+            ParseError::InvalidDigit {
+                digit: '8',
+                radix: 8,
+                span: Span::new(3, 4, 1, 4),
+            },
+        ),
+        (
+            "1_000_;",
+            ParseError::MisplacedSeparator {
+                span: Span::new(5, 6, 1, 6),
+            },
+        ),
+        (
+            "0x_1;",
+            ParseError::MisplacedSeparator {
+                span: Span::new(2, 3, 1, 3),
+            },
+        ),
+        (
+            "1_.5;",
+            ParseError::MisplacedSeparator {
+                span: Span::new(1, 2, 1, 2),
+            },
+        ),
+        (
+            "0b;",
+            ParseError::MissingDigits {
+                prefix: "0b".to_string(),
+                span: Span::new(0, 2, 1, 1),
+            },
+        ),
+        (
+            "1e999;",
+            ParseError::InvalidFloatLiteral {
+                literal: "1e999".to_string(),
+                span: Span::new(0, 5, 1, 1),
+            },
+        ),
+        (
+            "break;",
+            ParseError::OutsideLoop {
+                keyword: TokenType::Break,
+                span: Span::new(0, 5, 1, 1),
+            },
+        ),
+        (
+            "while true { let f = fun() { continue; }; }",
+            ParseError::OutsideLoop {
+                keyword: TokenType::Continue,
+                span: Span::new(29, 37, 1, 30),
+            },
+        ),
+        (
+            "for x [1] { x }",
+            ParseError::UnexpectedToken {
+                expected: TokenType::In,
+                found: TokenType::LSquare,
+                span: Span::new(6, 7, 1, 7),
+            },
+        ),
+        (
+            "let rec x = 1;",
+            ParseError::RecNonFunction {
+                span: Span::new(12, 13, 1, 13),
+            },
+        ),
+        (
+            "let rec f = fun() { g() } and g = [f];",
+            ParseError::RecNonFunction {
+                span: Span::new(34, 37, 1, 35),
+            },
+        ),
+        (
+            "let [a, a] = [1, 2];",
+            ParseError::DuplicateBinding {
+                name: "a".to_string(),
+                span: Span::new(8, 9, 1, 9),
+            },
+        ),
+        (
+            "let [a, ...a] = x;",
+            ParseError::DuplicateBinding {
+                name: "a".to_string(),
+                span: Span::new(11, 12, 1, 12),
+            },
+        ),
+        (
+            "let {a, b: [a]} = h;",
+            ParseError::DuplicateBinding {
+                name: "a".to_string(),
+                span: Span::new(12, 13, 1, 13),
+            },
+        ),
+        (
+            "fun([x, x]) { x };",
+            ParseError::DuplicateBinding {
+                name: "x".to_string(),
+                span: Span::new(8, 9, 1, 9),
+            },
+        ),
+        (
+            "fun(x, x) { x };",
+            ParseError::DuplicateBinding {
+                name: "x".to_string(),
+                span: Span::new(7, 8, 1, 8),
+            },
+        ),
+        (
+            "match x { [y, y] => y }",
+            ParseError::DuplicateBinding {
+                name: "y".to_string(),
+                span: Span::new(14, 15, 1, 15),
+            },
+        ),
+        (
+            "let rec f = fun() { 1 } and f = fun() { 2 };",
+            ParseError::DuplicateBinding {
+                name: "f".to_string(),
+                span: Span::new(28, 29, 1, 29),
+            },
+        ),
+        (
+            "let rec f = fun() { g() } and g = fun() { f() } and g = fun() { 3 };",
+            ParseError::DuplicateBinding {
+                name: "g".to_string(),
+                span: Span::new(52, 53, 1, 53),
+            },
+        ),
+        (
+            "let [a, ...rest, b] = x;",
+            ParseError::UnexpectedToken {
+                expected: TokenType::RSquare,
+                found: TokenType::Ident,
+                span: Span::new(17, 18, 1, 18),
+            },
+        ),
+        (
+            "let {a: (b)} = x;",
+            ParseError::UnexpectedToken {
+                expected: TokenType::Ident,
+                found: TokenType::LParen,
+                span: Span::new(8, 9, 1, 9),
+            },
+        ),
+        (
+            "let rec [f] = [fun() { 1 }];",
+            ParseError::UnexpectedToken {
+                expected: TokenType::Ident,
+                found: TokenType::LSquare,
+                span: Span::new(8, 9, 1, 9),
+            },
+        ),
+        (
+            "match x { -y => 1 }",
+            ParseError::InvalidPattern {
+                span: Span::new(10, 12, 1, 11),
+            },
+        ),
+        (
+            r#"match x { "a${b}" => 1 }"#,
+            ParseError::InvalidPattern {
+                span: Span::new(10, 17, 1, 11),
+            },
+        ),
+        (
+            "match x { 1 + 2 => 3 }",
+            ParseError::UnexpectedToken {
+                expected: TokenType::FatArrow,
+                found: TokenType::Plus,
+                span: Span::new(12, 13, 1, 13),
+            },
+        ),
+        (
+            "match x { 1 => 2 3 => 4 }",
+            ParseError::UnexpectedToken {
+                expected: TokenType::Comma,
+                found: TokenType::Int,
+                span: Span::new(17, 18, 1, 18),
+            },
+        ),
+        (
+            "struct Point { x, y, x }",
+            ParseError::DuplicateField {
+                field: "x".to_string(),
+                span: Span::new(21, 22, 1, 22),
+            },
+        ),
+        (
+            "p with { x: 1, x: 2 };",
+            ParseError::DuplicateField {
+                field: "x".to_string(),
+                span: Span::new(15, 16, 1, 16),
+            },
+        ),
+        (
+            "struct { x }",
+            ParseError::UnexpectedToken {
+                expected: TokenType::Ident,
+                found: TokenType::LBrace,
+                span: Span::new(7, 8, 1, 8),
+            },
+        ),
+        (
+            "p.;",
+            ParseError::UnexpectedToken {
+                expected: TokenType::Ident,
+                found: TokenType::Semicolon,
+                span: Span::new(2, 3, 1, 3),
+            },
+        ),
+        (
+            "p with { x = 1 };",
+            ParseError::UnexpectedToken {
+                expected: TokenType::Colon,
+                found: TokenType::Assign,
+                span: Span::new(11, 12, 1, 12),
+            },
+        ),
+        (
+            "[1, 2](3);",
+            ParseError::InvalidCallTarget {
+                span: Span::new(0, 6, 1, 1),
+            },
+        ),
+        (
+            "let x = 1 + @;",
+            ParseError::IllegalCharacter {
+                character: "@".to_string(),
+                span: Span::new(12, 13, 1, 13),
+            },
+        ),
+        (
+            "fun() { 1",
+            ParseError::UnexpectedToken {
+                expected: TokenType::RBrace,
+                found: TokenType::Eof,
+                span: Span::new(9, 9, 1, 10),
+            },
+        ),
+        (
+            "let s = \"abc;\nlet t = 1;",
+            ParseError::UnterminatedString {
+                span: Span::new(8, 24, 1, 9),
+            },
+        ),
+        (
+            "\"a\\qb\";",
+            ParseError::InvalidEscape {
+                sequence: "\\q".to_string(),
+                span: Span::new(2, 4, 1, 3),
+            },
+        ),
+        (
+            "\"a ${} b\";",
+            ParseError::NoPrefixParseFn {
+                found: TokenType::InterpolationEnd,
+                span: Span::new(5, 6, 1, 6),
+            },
+        ),
+        (
+            "\"${1 2}\";",
+            ParseError::UnexpectedToken {
+                expected: TokenType::InterpolationEnd,
+                found: TokenType::Int,
+                span: Span::new(5, 6, 1, 6),
+            },
+        ),
+        (
+            "\"\\u{110000}\";",
+            ParseError::InvalidEscape {
+                sequence: "\\u{110000}".to_string(),
+                span: Span::new(1, 11, 1, 2),
+            },
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let (_, errors) = parse(Lexer::new(input));
+        assert_eq!(errors, vec![expected], "Input: {input}");
+    }
+}
+
+#[test]
+fn parser_reports_every_error_in_one_pass() {
+    let input = "
+        let a = 1;
+        let = 2;
+        let b = 3;
+        let c 4;
+        let d = 5;
+        let e = (1 + ;
+        let f = 6;
+    ";
+    let (program, errors) = parse(Lexer::new(input));
+    let lines: Vec<usize> = errors.iter().map(|error| error.span().line).collect();
+    assert_eq!(lines, vec![3, 5, 7]);
+    let statements: Vec<String> = program
+        .statements
+        .iter()
+        .map(|statement| statement.string())
+        .collect();
+    assert_eq!(
+        statements,
+        vec!["let a = 1;", "let b = 3;", "let d = 5;", "let f = 6;"]
+    );
+}
+
+#[test]
+fn parser_recovers_at_struct_and_match() {
+    let input = "
+        let = 1
+        struct Point { x, y }
+        let = 2
+        match b { _ => 1 };
+    ";
+    let (program, errors) = parse(Lexer::new(input));
+    let lines: Vec<usize> = errors.iter().map(|error| error.span().line).collect();
+    assert_eq!(lines, vec![2, 4]);
+    let statements: Vec<String> = program
+        .statements
+        .iter()
+        .map(|statement| statement.string())
+        .collect();
+    assert_eq!(
+        statements,
+        vec!["struct Point { x, y }", "match b { _ => 1 };"]
+    );
+}
+
+#[test]
+fn parser_recovers_inside_blocks() {
+    let input = "
+        let f = fun(x) {
+            let y = ;
+            let z = x + 1;
+            z
+        };
+        let g = fun() { [1, 2; 3 };
+        f(1);
+    ";
+    let (program, errors) = parse(Lexer::new(input));
+    let lines: Vec<usize> = errors.iter().map(|error| error.span().line).collect();
+    assert_eq!(lines, vec![3, 7]);
+    let statements: Vec<String> = program
+        .statements
+        .iter()
+        .map(|statement| statement.string())
+        .collect();
+    assert_eq!(
+        statements,
+        vec![
+            "let f = fun(x) { let z = (x + 1); z; };",
+            "let g = fun() { 3; };",
+            "f(1);"
+        ]
+    );
+}
+
+#[test]
+fn empty_block_statement() {
+    let (program, errors) = parse(Lexer::new("let f = fun() {};"));
+    assert!(errors.is_empty());
+    assert_eq!(program.string(), "let f = fun() {  };");
+}
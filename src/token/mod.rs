@@ -1,12 +1,16 @@
+use std::fmt::{self, Display};
+
 #[derive(Clone, Copy, Eq, Debug, Hash, PartialEq)]
 pub enum TokenType {
     Illegal,
     Eof,
     Ident,
     Int,
+    Float,
     Assign,
     Plus,
     Comma,
+    Colon,
     Semicolon,
     LParen,
     RParen,
@@ -16,14 +20,43 @@ pub enum TokenType {
     RSquare,
     DoubleQuotation,
     StringValue,
+    // A string that reached the end of the input without a closing quote
+    UnterminatedString,
+    // A backslash escape sequence in a string that isn't recognised, eg. `\q`
+    InvalidEscape,
+    // The `${` and `}` around an expression interpolated into a string
+    InterpolationStart,
+    InterpolationEnd,
     Function,
     Let,
     // Used in let rec statements
     Rec,
+    // Used in let mut statements
+    Mut,
+    // Separates the functions defined by a let rec statement
+    And,
+    // `...`, before the name bound to the rest of an array in an array pattern
+    Ellipsis,
+    // `.`, between a record and the name of one of its fields
+    Dot,
     Bang,
     Minus,
     Slash,
     Star,
+    Percent,
+    // **
+    DoubleStar,
+    Ampersand,
+    Pipe,
+    Caret,
+    // <<
+    LShift,
+    // >>
+    RShift,
+    // &&
+    LogicalAnd,
+    // ||
+    LogicalOr,
     LArrow,
     RArrow,
     True,
@@ -31,6 +64,17 @@ pub enum TokenType {
     If,
     Else,
     Return,
+    While,
+    For,
+    In,
+    Break,
+    Continue,
+    Match,
+    Struct,
+    // Updates the fields of a record, eg. `point with { x: 3 }`
+    With,
+    // `=>`, between the pattern and the value of an arm in a match expression
+    FatArrow,
     Eq,
     NotEq,
     GreaterEq,
@@ -38,10 +82,116 @@ pub enum TokenType {
     Null,
 }
 
+impl Display for TokenType {
+    // Describes the token type the way it would appear in source code. Used in error messages.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match self {
+            TokenType::Illegal => "illegal character",
+            TokenType::Eof => "end of input",
+            TokenType::Ident => "identifier",
+            TokenType::Int => "integer",
+            TokenType::Float => "float",
+            TokenType::Assign => "`=`",
+            TokenType::Plus => "`+`",
+            TokenType::Comma => "`,`",
+            TokenType::Semicolon => "`;`",
+            TokenType::LParen => "`(`",
+            TokenType::RParen => "`)`",
+            TokenType::LBrace => "`{`",
+            TokenType::RBrace => "`}`",
+            TokenType::Colon => "`:`",
+            TokenType::LSquare => "`[`",
+            TokenType::RSquare => "`]`",
+            TokenType::DoubleQuotation => "`\"`",
+            TokenType::StringValue => "string",
+            TokenType::UnterminatedString => "unterminated string",
+            TokenType::InvalidEscape => "invalid escape sequence",
+            TokenType::InterpolationStart => "`${`",
+            TokenType::InterpolationEnd => "`}`",
+            TokenType::Function => "`fun`",
+            TokenType::Let => "`let`",
+            TokenType::Rec => "`rec`",
+            TokenType::Mut => "`mut`",
+            TokenType::And => "`and`",
+            TokenType::Ellipsis => "`...`",
+            TokenType::Dot => "`.`",
+            TokenType::Bang => "`!`",
+            TokenType::Minus => "`-`",
+            TokenType::Slash => "`/`",
+            TokenType::Star => "`*`",
+            TokenType::Percent => "`%`",
+            TokenType::DoubleStar => "`**`",
+            TokenType::Ampersand => "`&`",
+            TokenType::Pipe => "`|`",
+            TokenType::Caret => "`^`",
+            TokenType::LShift => "`<<`",
+            TokenType::RShift => "`>>`",
+            TokenType::LogicalAnd => "`&&`",
+            TokenType::LogicalOr => "`||`",
+            TokenType::LArrow => "`<`",
+            TokenType::RArrow => "`>`",
+            TokenType::True => "`true`",
+            TokenType::False => "`false`",
+            TokenType::If => "`if`",
+            TokenType::Else => "`else`",
+            TokenType::Return => "`return`",
+            TokenType::While => "`while`",
+            TokenType::For => "`for`",
+            TokenType::In => "`in`",
+            TokenType::Break => "`break`",
+            TokenType::Continue => "`continue`",
+            TokenType::Match => "`match`",
+            TokenType::Struct => "`struct`",
+            TokenType::With => "`with`",
+            TokenType::FatArrow => "`=>`",
+            TokenType::Eq => "`==`",
+            TokenType::NotEq => "`!=`",
+            TokenType::GreaterEq => "`>=`",
+            TokenType::LessEq => "`<=`",
+            TokenType::Null => "`null`",
+        };
+        write!(f, "{description}")
+    }
+}
+
+/// A region of source code. `start` and `end` are byte offsets into the source (`end` is
+/// exclusive), while `line` and `column` are 1-based and describe where `start` is.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, column: usize) -> Self {
+        Self {
+            start,
+            end,
+            line,
+            column,
+        }
+    }
+
+    /// Returns a span that starts where `self` starts and ends where `other` ends.
+    pub fn to(self, other: Span) -> Span {
+        if other.end < self.end {
+            self
+        } else {
+            Span {
+                end: other.end,
+                ..self
+            }
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Token {
     pub token_type: TokenType,
     pub literal: String,
+    pub span: Span,
 }
 
 impl Token {
@@ -49,6 +199,15 @@ impl Token {
         Self {
             token_type,
             literal: literal.to_string(),
+            span: Span::default(),
+        }
+    }
+
+    pub fn with_span(token_type: TokenType, literal: &str, span: Span) -> Self {
+        Self {
+            token_type,
+            literal: literal.to_string(),
+            span,
         }
     }
 }